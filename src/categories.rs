@@ -0,0 +1,250 @@
+// Registered categories from the Desktop Menu Specification (version 1.1)
+
+pub const MAIN_CATEGORIES: [&str; 13] = [
+    "AudioVideo",
+    "Audio",
+    "Video",
+    "Development",
+    "Education",
+    "Game",
+    "Graphics",
+    "Network",
+    "Office",
+    "Science",
+    "Settings",
+    "System",
+    "Utility",
+];
+
+// Only valid together with OnlyShowIn, which this editor does not manage
+pub const RESERVED_CATEGORIES: [&str; 4] = ["Screensaver", "TrayIcon", "Applet", "Shell"];
+
+// Each additional category with its related categories: one of the alternatives
+// must be fully present; an empty list means it can be used anywhere.
+pub const ADDITIONAL_CATEGORIES: &[(&str, &[&[&str]])] = &[
+    ("Building", &[&["Development"]]),
+    ("Debugger", &[&["Development"]]),
+    ("IDE", &[&["Development"]]),
+    ("GUIDesigner", &[&["Development"]]),
+    ("Profiling", &[&["Development"]]),
+    ("RevisionControl", &[&["Development"]]),
+    ("Translation", &[&["Development"]]),
+    ("Calendar", &[&["Office"]]),
+    ("ContactManagement", &[&["Office"]]),
+    ("Database", &[&["Office"], &["Development"], &["AudioVideo"]]),
+    ("Dictionary", &[&["Office"], &["TextTools"]]),
+    ("Chart", &[&["Office"]]),
+    ("Email", &[&["Office"], &["Network"]]),
+    ("Finance", &[&["Office"]]),
+    ("FlowChart", &[&["Office"]]),
+    ("PDA", &[&["Office"]]),
+    ("ProjectManagement", &[&["Office"], &["Development"]]),
+    ("Presentation", &[&["Office"]]),
+    ("Spreadsheet", &[&["Office"]]),
+    ("WordProcessor", &[&["Office"]]),
+    ("2DGraphics", &[&["Graphics"]]),
+    ("VectorGraphics", &[&["Graphics", "2DGraphics"]]),
+    ("RasterGraphics", &[&["Graphics", "2DGraphics"]]),
+    ("3DGraphics", &[&["Graphics"]]),
+    ("Scanning", &[&["Graphics"]]),
+    ("OCR", &[&["Graphics", "Scanning"]]),
+    ("Photography", &[&["Graphics"], &["Office"]]),
+    ("Publishing", &[&["Graphics"], &["Office"]]),
+    ("Viewer", &[&["Graphics"], &["Office"]]),
+    ("TextTools", &[&["Utility"]]),
+    ("DesktopSettings", &[&["Settings"]]),
+    ("HardwareSettings", &[&["Settings"]]),
+    ("Printing", &[&["HardwareSettings", "Settings"]]),
+    ("PackageManager", &[&["Settings"]]),
+    ("Dialup", &[&["Network"]]),
+    ("InstantMessaging", &[&["Network"]]),
+    ("Chat", &[&["Network"]]),
+    ("IRCClient", &[&["Network"]]),
+    ("Feed", &[&["Network"]]),
+    ("FileTransfer", &[&["Network"]]),
+    ("HamRadio", &[&["Network"], &["Audio"]]),
+    ("News", &[&["Network"]]),
+    ("P2P", &[&["Network"]]),
+    ("RemoteAccess", &[&["Network"]]),
+    ("Telephony", &[&["Network"]]),
+    ("TelephonyTools", &[&["Utility"]]),
+    ("VideoConference", &[&["Network"]]),
+    ("WebBrowser", &[&["Network"]]),
+    ("WebDevelopment", &[&["Network"], &["Development"]]),
+    ("Midi", &[&["AudioVideo", "Audio"]]),
+    ("Mixer", &[&["AudioVideo", "Audio"]]),
+    ("Sequencer", &[&["AudioVideo", "Audio"]]),
+    ("Tuner", &[&["AudioVideo", "Audio"]]),
+    ("TV", &[&["AudioVideo", "Video"]]),
+    ("AudioVideoEditing", &[&["Audio"], &["Video"], &["AudioVideo"]]),
+    ("Player", &[&["Audio"], &["Video"], &["AudioVideo"]]),
+    ("Recorder", &[&["Audio"], &["Video"], &["AudioVideo"]]),
+    ("DiscBurning", &[&["AudioVideo"]]),
+    ("ActionGame", &[&["Game"]]),
+    ("AdventureGame", &[&["Game"]]),
+    ("ArcadeGame", &[&["Game"]]),
+    ("BoardGame", &[&["Game"]]),
+    ("BlocksGame", &[&["Game"]]),
+    ("CardGame", &[&["Game"]]),
+    ("KidsGame", &[&["Game"]]),
+    ("LogicGame", &[&["Game"]]),
+    ("RolePlaying", &[&["Game"]]),
+    ("Shooter", &[&["Game"]]),
+    ("Simulation", &[&["Game"]]),
+    ("SportsGame", &[&["Game"]]),
+    ("StrategyGame", &[&["Game"]]),
+    ("Art", &[&["Education"], &["Science"]]),
+    ("Construction", &[&["Education"], &["Science"]]),
+    ("Music", &[&["AudioVideo"], &["Education"]]),
+    ("Languages", &[&["Education"], &["Science"]]),
+    ("ArtificialIntelligence", &[&["Education"], &["Science"]]),
+    ("Astronomy", &[&["Education"], &["Science"]]),
+    ("Biology", &[&["Education"], &["Science"]]),
+    ("Chemistry", &[&["Education"], &["Science"]]),
+    ("ComputerScience", &[&["Education"], &["Science"]]),
+    ("DataVisualization", &[&["Education"], &["Science"]]),
+    ("Economy", &[&["Education"], &["Science"]]),
+    ("Electricity", &[&["Education"], &["Science"]]),
+    ("Geography", &[&["Education"], &["Science"]]),
+    ("Geology", &[&["Education"], &["Science"]]),
+    ("Geoscience", &[&["Education"], &["Science"]]),
+    ("History", &[&["Education"], &["Science"]]),
+    ("Humanities", &[&["Education"], &["Science"]]),
+    ("ImageProcessing", &[&["Education"], &["Science"]]),
+    ("Literature", &[&["Education"], &["Science"]]),
+    ("Maps", &[&["Education"], &["Science"], &["Utility"]]),
+    ("Math", &[&["Education"], &["Science"]]),
+    ("NumericalAnalysis", &[&["Education", "Math"], &["Science", "Math"]]),
+    ("MedicalSoftware", &[&["Education"], &["Science"]]),
+    ("Physics", &[&["Education"], &["Science"]]),
+    ("Robotics", &[&["Education"], &["Science"]]),
+    ("Spirituality", &[&["Education"], &["Science"], &["Utility"]]),
+    ("Sports", &[&["Education"], &["Science"]]),
+    ("ParallelComputing", &[&["Education", "ComputerScience"], &["Science", "ComputerScience"]]),
+    ("Amusement", &[]),
+    ("Archiving", &[&["Utility"]]),
+    ("Compression", &[&["Utility"]]),
+    ("Electronics", &[]),
+    ("Emulator", &[&["System"], &["Game"]]),
+    ("Engineering", &[]),
+    ("FileTools", &[&["Utility"], &["System"]]),
+    ("FileManager", &[&["System", "FileTools"]]),
+    ("TerminalEmulator", &[&["System"]]),
+    ("Filesystem", &[&["System"]]),
+    ("Monitor", &[&["System"], &["Network"]]),
+    ("Security", &[&["Settings"], &["System"]]),
+    ("Accessibility", &[&["Settings"], &["Utility"]]),
+    ("Calculator", &[&["Utility"]]),
+    ("Clock", &[&["Utility"]]),
+    ("TextEditor", &[&["Utility"]]),
+    ("Documentation", &[]),
+    ("Adult", &[]),
+    ("Core", &[]),
+    ("KDE", &[&["Qt"]]),
+    ("GNOME", &[&["GTK"]]),
+    ("XFCE", &[&["GTK"]]),
+    ("DDE", &[&["Qt"]]),
+    ("GTK", &[]),
+    ("Qt", &[]),
+    ("Motif", &[]),
+    ("Java", &[]),
+    ("ConsoleOnly", &[]),
+];
+
+pub fn parse_categories(value: &str) -> Vec<String> {
+    let mut categories: Vec<String> = Vec::new();
+    for category in value.split(';').map(|category| category.trim()).filter(|c| !c.is_empty()) {
+        if !categories.iter().any(|existing| existing == category) {
+            categories.push(category.to_string());
+        }
+    }
+    categories
+}
+
+pub fn format_categories(categories: &[String]) -> String {
+    categories.iter().map(|category| format!("{};", category)).collect()
+}
+
+pub fn is_main_category(category: &str) -> bool {
+    MAIN_CATEGORIES.contains(&category)
+}
+
+pub fn related_categories(category: &str) -> Option<&'static [&'static [&'static str]]> {
+    ADDITIONAL_CATEGORIES
+        .iter()
+        .find(|(name, _)| *name == category)
+        .map(|(_, related)| *related)
+}
+
+// Human readable form of the related-category rule, e.g. "Graphics;2DGraphics or Office"
+pub fn describe_related(related: &[&[&str]]) -> String {
+    related
+        .iter()
+        .map(|alternative| alternative.join(";"))
+        .collect::<Vec<String>>()
+        .join(" or ")
+}
+
+// Audio and Video are refinements of AudioVideo, so AudioVideo;Audio counts as one main category
+fn main_category_group(category: &str) -> &str {
+    match category {
+        "Audio" | "Video" => "AudioVideo",
+        other => other,
+    }
+}
+
+pub fn check_categories(categories: &[String]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let has = |name: &str| categories.iter().any(|category| category == name);
+
+    let mut main_groups: Vec<&str> = Vec::new();
+    for category in categories.iter().filter(|category| is_main_category(category)) {
+        let group = main_category_group(category);
+        if !main_groups.contains(&group) {
+            main_groups.push(group);
+        }
+    }
+    match main_groups.len() {
+        0 => warnings.push(String::from(
+            "No main category: menus will file this application under \"Other\"",
+        )),
+        1 => {}
+        _ => warnings.push(format!(
+            "More than one main category ({}): the application appears in several menus",
+            main_groups.join(", ")
+        )),
+    }
+
+    for refinement in ["Audio", "Video"] {
+        if has(refinement) && !has("AudioVideo") {
+            warnings.push(format!("{} must be used together with AudioVideo", refinement));
+        }
+    }
+
+    for category in categories {
+        if is_main_category(category) || category.starts_with("X-") {
+            continue;
+        }
+        if RESERVED_CATEGORIES.contains(&category.as_str()) {
+            warnings.push(format!("{} is reserved and requires OnlyShowIn", category));
+            continue;
+        }
+        match related_categories(category) {
+            Some(related) if !related.is_empty() => {
+                let satisfied = related
+                    .iter()
+                    .any(|alternative| alternative.iter().all(|required| has(required)));
+                if !satisfied {
+                    warnings.push(format!("{} requires {}", category, describe_related(related)));
+                }
+            }
+            Some(_) => {}
+            None => warnings.push(format!(
+                "{} is not a registered category; private categories must start with X-",
+                category
+            )),
+        }
+    }
+
+    warnings
+}
@@ -1,17 +1,22 @@
 use ini::Ini;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use crate::mime_apps::{data_dirs, data_home};
+
 #[derive(Debug, Clone)]
 pub struct DesktopEntry {
     pub path: PathBuf,
     pub name: String,
     pub exec: String,
+    pub try_exec: String,
     pub icon: String,
     pub comment: String,
     pub terminal: bool,
+    pub prefers_non_default_gpu: bool,
     pub categories: String,
+    pub working_directory: String,
     pub entry_type: String,
     pub mime_types: Vec<String>,
     pub mime_extensions: HashMap<String, String>,
@@ -28,10 +33,13 @@ impl DesktopEntry {
             path: path.to_path_buf(),
             name: section.get("Name").unwrap_or("").to_string(),
             exec: section.get("Exec").unwrap_or("").to_string(),
+            try_exec: section.get("TryExec").unwrap_or("").to_string(),
             icon: section.get("Icon").unwrap_or("").to_string(),
             comment: section.get("Comment").unwrap_or("").to_string(),
             terminal: section.get("Terminal").unwrap_or("false") == "true",
+            prefers_non_default_gpu: section.get("PrefersNonDefaultGPU") == Some("true"),
             categories: section.get("Categories").unwrap_or("").to_string(),
+            working_directory: section.get("Path").unwrap_or("").to_string(),
             entry_type: section.get("Type").unwrap_or("Application").to_string(),
             mime_types: section
                 .get("MimeType")
@@ -92,29 +100,113 @@ impl DesktopEntry {
             .set("Categories", &self.categories)
             .set("MimeType", &mime_value)
             .set("X-Manager-MimeExtensions", &extensions_value);
+        if !self.try_exec.is_empty() {
+            conf.with_section(Some("Desktop Entry"))
+                .set("TryExec", &self.try_exec);
+        }
+        if !self.working_directory.is_empty() {
+            conf.with_section(Some("Desktop Entry"))
+                .set("Path", &self.working_directory);
+        }
+        if self.prefers_non_default_gpu {
+            conf.with_section(Some("Desktop Entry"))
+                .set("PrefersNonDefaultGPU", "true");
+        }
 
         conf.write_to_file(&self.path)?;
         Ok(())
     }
+
+    // Rewrites only the MimeType= line, so keys and groups this struct does not model
+    // (Hidden, NoDisplay, actions, translations, ...) survive bulk edits
+    pub fn save_mime_types(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let value = if self.mime_types.is_empty() {
+            None
+        } else {
+            Some(format!("{};", self.mime_types.join(";")))
+        };
+        set_desktop_entry_key(&self.path, "MimeType", value.as_deref())
+    }
+
+    pub fn desktop_id(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
 }
 
-pub fn scan_desktop_files() -> Vec<DesktopEntry> {
-    let mut entries = Vec::new();
+// Sets or removes (None) one key of the [Desktop Entry] group, leaving every other line as written
+pub fn set_desktop_entry_key(path: &Path, key: &str, value: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)?;
+    let mut lines: Vec<String> = content.lines().map(|line| line.to_string()).collect();
+
+    let mut in_group = false;
+    let mut group_end = None;
+    let mut found = None;
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            in_group = trimmed == "[Desktop Entry]";
+            if in_group {
+                group_end = Some(index + 1);
+            }
+            continue;
+        }
+        if !in_group {
+            continue;
+        }
+        if !trimmed.is_empty() {
+            group_end = Some(index + 1);
+        }
+        if let Some((name, _)) = trimmed.split_once('=') {
+            if name.trim() == key {
+                found = Some(index);
+            }
+        }
+    }
+    let group_end = group_end.ok_or("Missing Desktop Entry section")?;
 
-    let home_path = format!(
-        "{}/.local/share/applications",
-        std::env::var("HOME").unwrap_or_default()
-    );
+    match (found, value) {
+        (Some(index), Some(value)) => lines[index] = format!("{}={}", key, value),
+        (Some(index), None) => {
+            lines.remove(index);
+        }
+        (None, Some(value)) => lines.insert(group_end, format!("{}={}", key, value)),
+        (None, None) => return Ok(()),
+    }
+
+    let mut updated = lines.join("\n");
+    updated.push('\n');
+    fs::write(path, updated)?;
+    Ok(())
+}
+
+pub fn application_dirs() -> Vec<String> {
+    let mut dirs = vec![format!("{}/applications", data_home())];
+    for dir in data_dirs() {
+        let applications = format!("{}/applications", dir);
+        if !dirs.contains(&applications) {
+            dirs.push(applications);
+        }
+    }
+
+    // Snap and Flatpak exports, in case the session didn't add them to XDG_DATA_DIRS
+    for extra in [
+        "/var/lib/snapd/desktop/applications",
+        "/var/lib/flatpak/exports/share/applications",
+    ] {
+        if !dirs.iter().any(|dir| dir == extra) {
+            dirs.push(String::from(extra));
+        }
+    }
+    dirs
+}
 
-    let search_paths = vec![
-        "/usr/share/applications",
-        "/usr/local/share/applications",
-        home_path.as_str(),
-        "/var/lib/snapd/desktop/applications",  // Snap applications
-        "/var/lib/flatpak/exports/share/applications",  // Flatpak applications
-    ];
+pub fn scan_desktop_files() -> Vec<DesktopEntry> {
+    let mut entries = Vec::new();
 
-    for dir in search_paths {
+    for dir in application_dirs() {
         if let Ok(read_dir) = fs::read_dir(dir) {
             for entry in read_dir.flatten() {
                 let path = entry.path();
@@ -132,6 +224,172 @@ pub fn scan_desktop_files() -> Vec<DesktopEntry> {
         }
     }
 
-    entries.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    entries.sort_by_key(|entry| entry.name.to_lowercase());
     entries
 }
+
+// A key such as Name with its Name[xx] translations
+#[derive(Debug, Clone, Default)]
+pub struct LocalizedString {
+    pub default: String,
+    pub translations: BTreeMap<String, String>,
+}
+
+impl LocalizedString {
+    fn from_section(section: &ini::Properties, key: &str) -> Self {
+        let prefix = format!("{}[", key);
+        LocalizedString {
+            default: section.get(key).unwrap_or("").to_string(),
+            translations: section
+                .iter()
+                .filter_map(|(name, value)| {
+                    let locale = name.strip_prefix(&prefix)?.strip_suffix(']')?;
+                    Some((locale.to_string(), value.to_string()))
+                })
+                .collect(),
+        }
+    }
+
+    // The value shown for a locale, and the translation key it came from (None when untranslated)
+    pub fn resolve(&self, locale: &str) -> (String, Option<String>) {
+        for variant in locale_variants(locale) {
+            if let Some(value) = self.translations.get(&variant) {
+                return (value.clone(), Some(variant));
+            }
+        }
+        (self.default.clone(), None)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DesktopAction {
+    pub id: String,
+    pub name: LocalizedString,
+    pub icon: String,
+    // Listed in Actions= but without a [Desktop Action id] group
+    pub missing_group: bool,
+}
+
+// The parts of an entry that launchers, menus and docks display
+#[derive(Debug, Clone, Default)]
+pub struct LauncherInfo {
+    pub name: LocalizedString,
+    pub generic_name: LocalizedString,
+    pub comment: LocalizedString,
+    pub icon: String,
+    pub actions: Vec<DesktopAction>,
+}
+
+impl LauncherInfo {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let conf = Ini::load_from_file(path)?;
+        let section = conf
+            .section(Some("Desktop Entry"))
+            .ok_or("Missing Desktop Entry section")?;
+
+        let actions = section
+            .get("Actions")
+            .unwrap_or("")
+            .split(';')
+            .map(|id| id.trim())
+            .filter(|id| !id.is_empty())
+            .map(|id| match conf.section(Some(format!("Desktop Action {}", id))) {
+                Some(action) => DesktopAction {
+                    id: id.to_string(),
+                    name: LocalizedString::from_section(action, "Name"),
+                    icon: action.get("Icon").unwrap_or("").to_string(),
+                    missing_group: false,
+                },
+                None => DesktopAction {
+                    id: id.to_string(),
+                    missing_group: true,
+                    ..Default::default()
+                },
+            })
+            .collect();
+
+        Ok(LauncherInfo {
+            name: LocalizedString::from_section(section, "Name"),
+            generic_name: LocalizedString::from_section(section, "GenericName"),
+            comment: LocalizedString::from_section(section, "Comment"),
+            icon: section.get("Icon").unwrap_or("").to_string(),
+            actions,
+        })
+    }
+
+    // Every locale some displayed key is translated into
+    pub fn locales(&self) -> BTreeSet<String> {
+        let mut locales: BTreeSet<String> = BTreeSet::new();
+        for value in [&self.name, &self.generic_name, &self.comment]
+            .into_iter()
+            .chain(self.actions.iter().map(|action| &action.name))
+        {
+            locales.extend(value.translations.keys().cloned());
+        }
+        locales
+    }
+}
+
+// Lowercase ASCII words joined by dashes, for file names derived from a display name
+pub fn file_name_slug(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// The locale used for messages, as set in the environment. Like gettext, the first entry of
+// LANGUAGE takes priority unless the locale itself is C or POSIX.
+pub fn current_locale() -> String {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_else(|| String::from("C"));
+    if locale_variants(&locale).is_empty() {
+        return locale;
+    }
+    std::env::var("LANGUAGE")
+        .ok()
+        .and_then(|language| {
+            language
+                .split(':')
+                .find(|entry| !entry.is_empty())
+                .map(|entry| entry.to_string())
+        })
+        .unwrap_or(locale)
+}
+
+// Keys to try for a locale, most specific first: lang_COUNTRY@MODIFIER, lang_COUNTRY,
+// lang@MODIFIER, lang. The encoding part is ignored.
+pub fn locale_variants(locale: &str) -> Vec<String> {
+    let (rest, modifier) = match locale.split_once('@') {
+        Some((rest, modifier)) => (rest, Some(modifier)),
+        None => (locale, None),
+    };
+    let rest = rest.split('.').next().unwrap_or(rest);
+    let (lang, country) = match rest.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (rest, None),
+    };
+    if lang.is_empty() || lang == "C" || lang == "POSIX" {
+        return Vec::new();
+    }
+
+    let mut variants = Vec::new();
+    if let (Some(country), Some(modifier)) = (country, modifier) {
+        variants.push(format!("{}_{}@{}", lang, country, modifier));
+    }
+    if let Some(country) = country {
+        variants.push(format!("{}_{}", lang, country));
+    }
+    if let Some(modifier) = modifier {
+        variants.push(format!("{}@{}", lang, modifier));
+    }
+    variants.push(lang.to_string());
+    variants
+}
@@ -0,0 +1,991 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::desktop_file::DesktopEntry;
+use crate::icon_theme::ICON_EXTENSIONS;
+use crate::mime_apps::data_home;
+
+// Variables kept when launching in a cleaned environment: enough to reach the
+// session, nothing inherited from this app's own launch environment.
+const CLEAN_ENVIRONMENT_KEEP: &[&str] = &[
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "SHELL",
+    "LANG",
+    "DISPLAY",
+    "WAYLAND_DISPLAY",
+    "XAUTHORITY",
+    "XDG_RUNTIME_DIR",
+    "XDG_SESSION_TYPE",
+    "XDG_CURRENT_DESKTOP",
+    "DBUS_SESSION_BUS_ADDRESS",
+];
+const CLEAN_ENVIRONMENT_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+// How many near-miss program names to offer when the Exec program is not found
+const MAX_SUGGESTIONS: usize = 5;
+
+// Shells whose `-c` script may start with variable assignments
+const SHELLS: &[&str] = &["sh", "bash", "dash", "zsh"];
+
+// Toolkit variables offered in the environment editor: name, typical value, what it does
+pub const COMMON_VARIABLES: &[(&str, &str, &str)] = &[
+    ("GDK_SCALE", "2", "Integer window scale for GTK apps"),
+    ("GDK_DPI_SCALE", "0.5", "Text scale for GTK apps, combined with GDK_SCALE"),
+    ("GDK_BACKEND", "x11", "GTK display backend (wayland, x11)"),
+    ("GTK_THEME", "Adwaita:dark", "GTK theme, with an optional :dark variant"),
+    ("GSK_RENDERER", "gl", "GTK 4 renderer (gl, ngl, vulkan, cairo)"),
+    ("QT_QPA_PLATFORM", "wayland", "Qt platform plugin (wayland, xcb)"),
+    ("QT_SCALE_FACTOR", "2", "Scale factor for Qt apps"),
+    ("QT_AUTO_SCREEN_SCALE_FACTOR", "1", "Let Qt pick the scale from the screen DPI"),
+    ("QT_STYLE_OVERRIDE", "kvantum", "Qt widget style"),
+    ("MOZ_ENABLE_WAYLAND", "1", "Run Firefox and Thunderbird natively on Wayland"),
+    ("ELECTRON_OZONE_PLATFORM_HINT", "auto", "Electron platform (auto, wayland, x11)"),
+    ("SDL_VIDEODRIVER", "wayland", "SDL video backend (wayland, x11)"),
+    ("_JAVA_AWT_WM_NONREPARENTING", "1", "Fixes blank Java windows on tiling compositors"),
+    ("DRI_PRIME", "1", "Render on the secondary GPU (Mesa)"),
+    ("__NV_PRIME_RENDER_OFFLOAD", "1", "Render on the NVIDIA GPU, with __GLX_VENDOR_LIBRARY_NAME"),
+    ("__GLX_VENDOR_LIBRARY_NAME", "nvidia", "OpenGL vendor library for PRIME offload"),
+    ("LIBGL_ALWAYS_SOFTWARE", "1", "Force software OpenGL rendering"),
+    ("LC_ALL", "C", "Override the locale for this app only"),
+];
+
+// Where installed flatpak apps are listed, one directory per app ID
+const FLATPAK_SYSTEM_APPS: &str = "/var/lib/flatpak/app";
+
+// Folders under $HOME where AppImages are usually kept
+const APPIMAGE_DIRS: &[&str] = &["Applications", "AppImages", "Downloads", "Desktop", "bin", ".local/bin"];
+const OPT_DIR: &str = "/opt";
+const USR_LOCAL_BIN: &str = "/usr/local/bin";
+// How deep to look inside /opt/<app> for an icon to suggest
+const OPT_ICON_SEARCH_DEPTH: usize = 3;
+
+// Terminal emulators tried in order, with the arguments placed before the command
+const TERMINALS: &[(&str, &[&str])] = &[
+    ("xdg-terminal-exec", &[]),
+    ("x-terminal-emulator", &["-e"]),
+    ("gnome-terminal", &["--"]),
+    ("kgx", &["--"]),
+    ("ptyxis", &["--"]),
+    ("konsole", &["-e"]),
+    ("xfce4-terminal", &["-x"]),
+    ("alacritty", &["-e"]),
+    ("kitty", &[]),
+    ("foot", &[]),
+    ("wezterm", &["start", "--"]),
+    ("xterm", &["-e"]),
+];
+
+#[derive(Debug, Clone)]
+pub enum ProgramCheck {
+    Found(PathBuf),
+    NotExecutable(PathBuf),
+    NotFound,
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+fn path_dirs() -> Vec<String> {
+    env::var("PATH")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| dir.to_string())
+        .collect()
+}
+
+// Resolves a program name the way TryExec does: absolute paths as-is, others through $PATH
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    match check_program(program) {
+        ProgramCheck::Found(path) => Some(path),
+        _ => None,
+    }
+}
+
+pub fn check_program(program: &str) -> ProgramCheck {
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return if is_executable(&path) {
+            ProgramCheck::Found(path)
+        } else if path.exists() {
+            ProgramCheck::NotExecutable(path)
+        } else {
+            ProgramCheck::NotFound
+        };
+    }
+
+    let mut not_executable = None;
+    for dir in path_dirs() {
+        let candidate = Path::new(&dir).join(program);
+        if is_executable(&candidate) {
+            return ProgramCheck::Found(candidate);
+        }
+        if not_executable.is_none() && candidate.is_file() {
+            not_executable = Some(candidate);
+        }
+    }
+    not_executable.map_or(ProgramCheck::NotFound, ProgramCheck::NotExecutable)
+}
+
+// Something the Command field can be completed to
+#[derive(Debug, Clone)]
+pub struct CommandCandidate {
+    pub name: String,
+    // Text filled into the Command field
+    pub command: String,
+    pub detail: String,
+    pub path: Option<PathBuf>,
+    // Found through $PATH, so launchers can run it by name
+    pub on_path: bool,
+}
+
+// Executables on $PATH and in ~/.local/bin, then installed flatpak apps as `flatpak run <id>`.
+// Programs outside $PATH are filled in by absolute path, since launchers would not find them.
+pub fn command_candidates() -> Vec<CommandCandidate> {
+    let mut candidates: Vec<CommandCandidate> = Vec::new();
+    let mut seen: BTreeSet<String> = BTreeSet::new();
+
+    let on_path = path_dirs();
+    let local_bin = format!("{}/.local/bin", env::var("HOME").unwrap_or_default());
+    let mut dirs = on_path.clone();
+    if !dirs.contains(&local_bin) {
+        dirs.push(local_bin);
+    }
+    for dir in &dirs {
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            continue;
+        };
+        let mut names: Vec<(String, PathBuf)> = read_dir
+            .flatten()
+            .filter(|item| is_executable(&item.path()))
+            .map(|item| (item.file_name().to_string_lossy().to_string(), item.path()))
+            .collect();
+        names.sort();
+        for (name, path) in names {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            let found_on_path = on_path.contains(dir);
+            let command = if found_on_path {
+                name.clone()
+            } else {
+                quote_exec_argument(&path.to_string_lossy())
+            };
+            candidates.push(CommandCandidate {
+                name,
+                command,
+                detail: path.display().to_string(),
+                path: Some(path),
+                on_path: found_on_path,
+            });
+        }
+    }
+
+    let user_apps = format!("{}/flatpak/app", data_home());
+    for (dir, scope) in [(user_apps.as_str(), "user"), (FLATPAK_SYSTEM_APPS, "system")] {
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            continue;
+        };
+        let mut ids: Vec<String> = read_dir
+            .flatten()
+            .map(|item| item.file_name().to_string_lossy().to_string())
+            .filter(|id| id.contains('.'))
+            .collect();
+        ids.sort();
+        for id in ids {
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            candidates.push(CommandCandidate {
+                command: format!("flatpak run {}", id),
+                detail: format!("Flatpak app ({})", scope),
+                name: id,
+                path: None,
+                on_path: false,
+            });
+        }
+    }
+    candidates
+}
+
+// How well `name` matches what was typed, lower is better: exact, prefix, start of a
+// -_. separated part, substring, then the letters in order
+fn match_rank(query: &str, name: &str) -> Option<usize> {
+    let name = name.to_lowercase();
+    if name == query {
+        Some(0)
+    } else if name.starts_with(query) {
+        Some(1)
+    } else if name
+        .match_indices(query)
+        .any(|(offset, _)| name[..offset].ends_with(['-', '_', '.']))
+    {
+        Some(2)
+    } else if name.contains(query) {
+        Some(3)
+    } else {
+        let mut letters = name.chars();
+        query.chars().all(|c| letters.any(|other| other == c)).then_some(4)
+    }
+}
+
+pub fn rank_candidates<'a>(query: &str, candidates: &'a [CommandCandidate], limit: usize) -> Vec<&'a CommandCandidate> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let mut ranked: Vec<(usize, &CommandCandidate)> = candidates
+        .iter()
+        .filter_map(|candidate| match_rank(&query, &candidate.name).map(|rank| (rank, candidate)))
+        .collect();
+    ranked.sort_by(|(rank, a), (other_rank, b)| {
+        rank.cmp(other_rank)
+            .then(a.name.len().cmp(&b.name.len()))
+            .then(a.name.cmp(&b.name))
+    });
+    ranked.into_iter().take(limit).map(|(_, candidate)| candidate).collect()
+}
+
+// The description in `whatis` output, e.g. "ls (1) - list directory contents"
+pub fn whatis_summary(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find_map(|line| line.split_once(" - "))
+        .map(|(_, description)| description.trim().to_string())
+        .filter(|description| !description.is_empty())
+}
+
+// The first line of `--help` output that describes the program rather than its usage
+pub fn help_summary(output: &str, program: &str) -> Option<String> {
+    let program = program.rsplit('/').next().unwrap_or(program);
+    output
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| {
+            !line.is_empty()
+                && !line.to_lowercase().starts_with("usage")
+                && !line.starts_with('-')
+                && !line.ends_with(':')
+                && line.split_whitespace().next() != Some(program)
+        })
+        .map(|line| line.to_string())
+}
+
+// The file an Exec line runs, after any env prefix, with symlinks resolved
+pub fn exec_program(exec: &str) -> Option<PathBuf> {
+    let environment = parse_exec_environment(exec);
+    let program = match environment.shell {
+        Some(shell) => shell.program,
+        None => split_exec(&environment.command).ok()?.into_iter().next()?,
+    };
+    let path = find_program(&program)?;
+    Some(fs::canonicalize(&path).unwrap_or(path))
+}
+
+// Executables on $PATH whose names are a small typo away from `name`, closest first
+pub fn similar_programs(name: &str, candidates: &[CommandCandidate]) -> Vec<String> {
+    let name = name.rsplit('/').next().unwrap_or(name);
+    if name.chars().count() < 3 {
+        return Vec::new();
+    }
+    let threshold = (name.chars().count() / 3).clamp(1, 3);
+
+    let mut matches: Vec<(usize, &String)> = candidates
+        .iter()
+        .filter(|candidate| candidate.on_path)
+        .map(|candidate| &candidate.name)
+        .filter_map(|candidate| {
+            let distance = edit_distance(name, candidate);
+            let close = distance <= threshold
+                || (name.len() >= 3 && candidate.starts_with(name))
+                || (candidate.len() >= 3 && name.starts_with(candidate.as_str()));
+            close.then_some((distance, candidate))
+        })
+        .collect();
+    matches.sort();
+    matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.clone())
+        .collect()
+}
+
+// Edit distance where swapping two adjacent characters counts as one typo
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in distances[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = best;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+pub struct ExecContext<'a> {
+    pub name: &'a str,
+    pub icon: &'a str,
+    pub desktop_file: &'a Path,
+    pub targets: &'a [String],
+}
+
+// Splits an Exec value into arguments following the desktop entry quoting rules:
+// arguments are separated by spaces, and inside double quotes a backslash
+// escapes '"', '`', '$' and '\'.
+pub fn split_exec(exec: &str) -> Result<Vec<String>, String> {
+    Ok(split_exec_spans(exec)?.into_iter().map(|(arg, _)| arg).collect())
+}
+
+// Like split_exec, with the byte offset each argument starts at
+pub fn split_exec_spans(exec: &str) -> Result<Vec<(String, usize)>, String> {
+    let mut args: Vec<(String, usize)> = Vec::new();
+    let mut current = String::new();
+    let mut start: Option<usize> = None;
+    let mut chars = exec.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '"' => {
+                start.get_or_insert(offset);
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.peek() {
+                            Some(&(_, next)) if matches!(next, '"' | '`' | '$' | '\\') => {
+                                current.push(next);
+                                chars.next();
+                            }
+                            _ => current.push('\\'),
+                        },
+                        Some((_, other)) => current.push(other),
+                        None => return Err(String::from("Unterminated double quote")),
+                    }
+                }
+            }
+            ' ' | '\t' | '\n' => {
+                if let Some(start) = start.take() {
+                    args.push((std::mem::take(&mut current), start));
+                }
+            }
+            _ => {
+                start.get_or_insert(offset);
+                current.push(c);
+            }
+        }
+    }
+    if let Some(start) = start {
+        args.push((current, start));
+    }
+    Ok(args)
+}
+
+// Quotes an argument for an Exec line, using double quotes only when it holds a reserved character
+pub fn quote_exec_argument(arg: &str) -> String {
+    let reserved = arg.is_empty() || arg.chars().any(|c| " \t\n\"'\\><~|&;$*?#()`".contains(c));
+    if !reserved {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShellWrapper {
+    pub program: String,
+    // Exec text after the script argument, e.g. "sh %U" for "$@" scripts
+    pub trailing: String,
+}
+
+// An Exec line split into the variables its `env VAR=value` or `sh -c "VAR=value ..."`
+// prefix sets and the command that follows. `command` is Exec text, or the rest of the
+// script when wrapped in a shell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecEnvironment {
+    pub variables: Vec<(String, String)>,
+    pub shell: Option<ShellWrapper>,
+    pub command: String,
+}
+
+pub fn is_valid_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn split_assignment(arg: &str) -> Option<(String, String)> {
+    let (name, value) = arg.split_once('=')?;
+    is_valid_variable_name(name).then(|| (name.to_string(), value.to_string()))
+}
+
+// A literal % in an Exec argument is written %%
+pub fn escape_percent(value: &str) -> String {
+    value.replace('%', "%%")
+}
+
+pub fn unescape_percent(value: &str) -> String {
+    value.replace("%%", "%")
+}
+
+// Reads the `VAR=value` words a shell script starts with. Stops at the first word that
+// is not a plain assignment, including values with expansions that cannot be edited as text.
+fn parse_shell_assignments(script: &str) -> (Vec<(String, String)>, &str) {
+    let mut variables: Vec<(String, String)> = Vec::new();
+    let mut rest = script.trim_start();
+
+    'words: while let Some((name, after_name)) = rest.split_once('=') {
+        if !is_valid_variable_name(name) {
+            break;
+        }
+
+        let mut value = String::new();
+        let mut chars = after_name.char_indices();
+        let mut end = after_name.len();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                c if c.is_whitespace() => {
+                    end = offset;
+                    break;
+                }
+                '\'' => loop {
+                    match chars.next() {
+                        Some((_, '\'')) => break,
+                        Some((_, other)) => value.push(other),
+                        None => break 'words,
+                    }
+                },
+                '"' => loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, next)) if matches!(next, '"' | '\\' | '$' | '`') => value.push(next),
+                            Some((_, next)) => {
+                                value.push('\\');
+                                value.push(next);
+                            }
+                            None => break 'words,
+                        },
+                        Some((_, '$' | '`')) | None => break 'words,
+                        Some((_, other)) => value.push(other),
+                    }
+                },
+                '\\' => match chars.next() {
+                    Some((_, next)) => value.push(next),
+                    None => break 'words,
+                },
+                '$' | '`' | ';' | '&' | '|' | '<' | '>' | '(' | ')' => break 'words,
+                _ => value.push(c),
+            }
+        }
+
+        variables.push((name.to_string(), value));
+        rest = after_name[end..].trim_start();
+    }
+    (variables, rest)
+}
+
+pub fn parse_exec_environment(exec: &str) -> ExecEnvironment {
+    let plain = ExecEnvironment {
+        variables: Vec::new(),
+        shell: None,
+        command: exec.trim().to_string(),
+    };
+    let Ok(args) = split_exec_spans(exec) else {
+        return plain;
+    };
+    let program_name = |arg: &str| arg.rsplit('/').next().unwrap_or(arg).to_string();
+
+    if args.first().is_some_and(|(arg, _)| program_name(arg) == "env") {
+        let variables: Vec<(String, String)> = args[1..]
+            .iter()
+            .map_while(|(arg, _)| split_assignment(arg))
+            .map(|(name, value)| (name, unescape_percent(&value)))
+            .collect();
+        if variables.is_empty() {
+            return plain;
+        }
+        // `env FOO=1` alone is still editable, the command just hasn't been written yet
+        let command = args
+            .get(variables.len() + 1)
+            .map(|(_, start)| exec[*start..].trim_end().to_string())
+            .unwrap_or_default();
+        return ExecEnvironment {
+            variables,
+            shell: None,
+            command,
+        };
+    }
+
+    match args.as_slice() {
+        [(program, _), (flag, _), (script, _), rest @ ..]
+            if SHELLS.contains(&program_name(program).as_str()) && flag == "-c" =>
+        {
+            let (variables, command) = parse_shell_assignments(script);
+            ExecEnvironment {
+                variables: variables
+                    .into_iter()
+                    .map(|(name, value)| (name, unescape_percent(&value)))
+                    .collect(),
+                shell: Some(ShellWrapper {
+                    program: program.clone(),
+                    trailing: rest
+                        .first()
+                        .map(|(_, start)| exec[*start..].trim_end().to_string())
+                        .unwrap_or_default(),
+                }),
+                command: command.to_string(),
+            }
+        }
+        _ => plain,
+    }
+}
+
+// Writes the Exec line back, with `env` only when there are variables to set
+pub fn format_exec_environment(environment: &ExecEnvironment) -> String {
+    match &environment.shell {
+        None if environment.variables.is_empty() => environment.command.clone(),
+        None => {
+            let mut parts = vec![String::from("env")];
+            parts.extend(
+                environment
+                    .variables
+                    .iter()
+                    .map(|(name, value)| quote_exec_argument(&format!("{}={}", name, escape_percent(value)))),
+            );
+            if !environment.command.is_empty() {
+                parts.push(environment.command.clone());
+            }
+            parts.join(" ")
+        }
+        Some(shell) => {
+            let mut script: Vec<String> = environment
+                .variables
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, quote_argument(&escape_percent(value))))
+                .collect();
+            if !environment.command.is_empty() {
+                script.push(environment.command.clone());
+            }
+            let mut parts = vec![shell.program.clone(), String::from("-c"), quote_exec_argument(&script.join(" "))];
+            if !shell.trailing.is_empty() {
+                parts.push(shell.trailing.clone());
+            }
+            parts.join(" ")
+        }
+    }
+}
+
+// Expands field codes the way a launcher would when opening `targets`. With several
+// targets and a single-file code (%f, %u) only the first is used, as for one launch.
+pub fn expand_exec(exec: &str, context: &ExecContext) -> Result<Vec<String>, String> {
+    let mut expanded: Vec<String> = Vec::new();
+
+    for arg in split_exec(exec)? {
+        match arg.as_str() {
+            "%F" | "%U" => {
+                expanded.extend(context.targets.iter().cloned());
+                continue;
+            }
+            "%f" | "%u" => {
+                expanded.extend(context.targets.first().cloned());
+                continue;
+            }
+            "%i" => {
+                if !context.icon.is_empty() {
+                    expanded.push(String::from("--icon"));
+                    expanded.push(context.icon.to_string());
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut result = String::new();
+        let mut chars = arg.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => result.push('%'),
+                Some('f') | Some('u') => {
+                    result.push_str(context.targets.first().map(|target| target.as_str()).unwrap_or(""))
+                }
+                Some('c') => result.push_str(context.name),
+                Some('k') => result.push_str(&context.desktop_file.to_string_lossy()),
+                // Deprecated codes are removed
+                Some('d') | Some('D') | Some('n') | Some('N') | Some('v') | Some('m') => {}
+                Some(code @ ('F' | 'U' | 'i')) => {
+                    return Err(format!("%{} must be an argument on its own: {}", code, arg));
+                }
+                Some(code) => return Err(format!("Unknown field code %{} in {}", code, arg)),
+                None => return Err(format!("Trailing % in {}", arg)),
+            }
+        }
+        expanded.push(result);
+    }
+
+    if expanded.is_empty() {
+        return Err(String::from("Exec is empty"));
+    }
+    Ok(expanded)
+}
+
+// The terminal to wrap Terminal=true entries in: $TERMINAL first, then the known emulators
+pub fn terminal_command() -> Option<Vec<String>> {
+    if let Ok(terminal) = env::var("TERMINAL") {
+        if !terminal.is_empty() && find_program(&terminal).is_some() {
+            return Some(vec![terminal, String::from("-e")]);
+        }
+    }
+
+    TERMINALS.iter().find_map(|(program, args)| {
+        find_program(program)?;
+        let mut command = vec![program.to_string()];
+        command.extend(args.iter().map(|arg| arg.to_string()));
+        Some(command)
+    })
+}
+
+// The arguments a launcher would run for an entry, wrapped in a terminal for Terminal=true.
+// `targets` are the files or URLs to open, quoted like Exec.
+pub fn launch_command(entry: &DesktopEntry, targets: &str) -> Result<Vec<String>, String> {
+    let targets = split_exec(targets)?;
+    let context = ExecContext {
+        name: &entry.name,
+        icon: &entry.icon,
+        desktop_file: &entry.path,
+        targets: &targets,
+    };
+    let mut command = expand_exec(&entry.exec, &context)?;
+    if entry.terminal {
+        let mut wrapped = terminal_command().ok_or("Terminal=true but no terminal emulator was found")?;
+        wrapped.append(&mut command);
+        command = wrapped;
+    }
+    Ok(command)
+}
+
+pub fn clean_environment() -> Vec<String> {
+    let mut environment: Vec<String> = CLEAN_ENVIRONMENT_KEEP
+        .iter()
+        .filter_map(|key| env::var(key).ok().map(|value| format!("{}={}", key, value)))
+        .collect();
+    environment.push(format!("PATH={}", CLEAN_ENVIRONMENT_PATH));
+    environment
+}
+
+// Quotes an argument for display so the command can be copied into a shell
+pub fn quote_argument(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OrphanProgram {
+    pub path: PathBuf,
+    pub name: String,
+    pub icon_file: Option<PathBuf>,
+    pub appimage: bool,
+    pub executable: bool,
+}
+
+fn is_appimage(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("appimage"))
+}
+
+// Every file an entry's Exec or TryExec mentions, by canonical path. Words inside sh -c
+// scripts and --option=value arguments count too, so wrapped programs are not reported.
+fn referenced_programs(entries: &[DesktopEntry]) -> BTreeSet<PathBuf> {
+    let mut referenced: BTreeSet<PathBuf> = BTreeSet::new();
+    for entry in entries {
+        let mut words: Vec<String> = split_exec(&entry.exec).unwrap_or_default();
+        words.push(entry.try_exec.clone());
+        for word in words.iter().flat_map(|arg| arg.split_whitespace()) {
+            let word = word.rsplit('=').next().unwrap_or(word);
+            let word = word.trim_matches(|c: char| "'\"();&|".contains(c));
+            if word.is_empty() || word.starts_with('-') || word.starts_with('%') {
+                continue;
+            }
+            let path = if word.contains('/') {
+                PathBuf::from(word)
+            } else {
+                match find_program(word) {
+                    Some(path) => path,
+                    None => continue,
+                }
+            };
+            if let Ok(canonical) = fs::canonicalize(&path) {
+                referenced.insert(canonical);
+            }
+            referenced.insert(path);
+        }
+    }
+    referenced
+}
+
+// Programs in ~/.local/bin, /opt/*/bin and /usr/local/bin, and AppImages in the usual
+// download folders, that no scanned entry runs
+pub fn orphan_programs(entries: &[DesktopEntry]) -> Vec<OrphanProgram> {
+    let home = env::var("HOME").unwrap_or_default();
+    let mut bin_dirs: Vec<PathBuf> = vec![PathBuf::from(format!("{}/.local/bin", home))];
+    let mut opt_apps: Vec<PathBuf> = fs::read_dir(OPT_DIR)
+        .map(|read_dir| read_dir.flatten().map(|item| item.path()).filter(|path| path.is_dir()).collect())
+        .unwrap_or_default();
+    opt_apps.sort();
+    bin_dirs.extend(opt_apps.iter().map(|app| app.join("bin")));
+    bin_dirs.push(PathBuf::from(USR_LOCAL_BIN));
+
+    let mut appimage_dirs: Vec<PathBuf> = APPIMAGE_DIRS
+        .iter()
+        .map(|dir| PathBuf::from(format!("{}/{}", home, dir)))
+        .collect();
+    appimage_dirs.push(PathBuf::from(OPT_DIR));
+    appimage_dirs.extend(opt_apps.iter().cloned());
+
+    let mut files: Vec<PathBuf> = Vec::new();
+    for (dirs, wanted) in [(&bin_dirs, is_executable as fn(&Path) -> bool), (&appimage_dirs, is_appimage)] {
+        for dir in dirs {
+            let Ok(read_dir) = fs::read_dir(dir) else {
+                continue;
+            };
+            let mut found: Vec<PathBuf> = read_dir
+                .flatten()
+                .map(|item| item.path())
+                .filter(|path| path.is_file() && wanted(path))
+                .collect();
+            found.sort();
+            files.extend(found);
+        }
+    }
+
+    let referenced = referenced_programs(entries);
+    let mut seen: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut orphans: Vec<OrphanProgram> = Vec::new();
+    for path in files {
+        let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if !seen.insert(canonical.clone()) || referenced.contains(&canonical) || referenced.contains(&path) {
+            continue;
+        }
+        orphans.push(OrphanProgram {
+            name: guess_launcher_name(&path),
+            icon_file: guess_icon_file(&canonical),
+            appimage: is_appimage(&path),
+            executable: is_executable(&path),
+            path,
+        });
+    }
+    orphans
+}
+
+// A readable name from a file name: "my_tool" becomes "My Tool", and "Obsidian-1.4.16.AppImage"
+// loses its version and extension
+pub fn guess_launcher_name(path: &Path) -> String {
+    let file_name = if is_appimage(path) {
+        path.file_stem()
+    } else {
+        path.file_name()
+    }
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default();
+
+    let chars: Vec<char> = file_name.chars().collect();
+    let version_start = (1..chars.len()).find(|&i| {
+        matches!(chars[i - 1], '-' | '_' | ' ')
+            && (chars[i].is_ascii_digit() || (chars[i] == 'v' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())))
+    });
+    let base: String = chars[..version_start.map_or(chars.len(), |i| i - 1)].iter().collect();
+
+    base.split(['-', '_', '.', ' '])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) if word.chars().all(|c| !c.is_uppercase()) => {
+                    first.to_uppercase().chain(chars).collect()
+                }
+                _ => word.to_string(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// An image shipped with an /opt application, preferring one named after the program
+fn guess_icon_file(program: &Path) -> Option<PathBuf> {
+    let relative = program.strip_prefix(OPT_DIR).ok()?;
+    let app_dir = Path::new(OPT_DIR).join(relative.components().next()?);
+    let program_name = program.file_stem()?.to_string_lossy().to_lowercase();
+    let app_name = app_dir.file_name()?.to_string_lossy().to_lowercase();
+
+    let mut images: Vec<PathBuf> = Vec::new();
+    collect_images(&app_dir, OPT_ICON_SEARCH_DEPTH, &mut images);
+    images
+        .into_iter()
+        .filter_map(|image| {
+            let stem = image.file_stem()?.to_string_lossy().to_lowercase();
+            let rank = if stem == program_name || stem == app_name {
+                0
+            } else if stem.contains(&program_name) || stem.contains(&app_name) {
+                1
+            } else if stem.contains("icon") || stem.contains("logo") {
+                2
+            } else {
+                return None;
+            };
+            // Vector images first, then the largest file as the likeliest high resolution one
+            let size = if image.extension().is_some_and(|ext| ext == "svg") {
+                u64::MAX
+            } else {
+                fs::metadata(&image).map(|meta| meta.len()).unwrap_or(0)
+            };
+            Some((rank, std::cmp::Reverse(size), image))
+        })
+        .min()
+        .map(|(_, _, image)| image)
+}
+
+fn collect_images(dir: &Path, depth: usize, images: &mut Vec<PathBuf>) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    for item in read_dir.flatten() {
+        let path = item.path();
+        if path.is_dir() {
+            if depth > 0 {
+                collect_images(&path, depth - 1, images);
+            }
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ICON_EXTENSIONS.contains(&ext))
+        {
+            images.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(exec: &str) -> ExecEnvironment {
+        let environment = parse_exec_environment(exec);
+        assert_eq!(parse_exec_environment(&format_exec_environment(&environment)), environment);
+        environment
+    }
+
+    #[test]
+    fn env_quoted_values_and_percent() {
+        let exec = r#"env "GTK_THEME=Adwaita dark" LABEL=100%% app --flag %U"#;
+        let environment = round_trip(exec);
+        assert_eq!(
+            environment.variables,
+            vec![
+                (String::from("GTK_THEME"), String::from("Adwaita dark")),
+                (String::from("LABEL"), String::from("100%")),
+            ]
+        );
+        assert_eq!(environment.shell, None);
+        assert_eq!(environment.command, "app --flag %U");
+        assert_eq!(format_exec_environment(&environment), exec);
+    }
+
+    #[test]
+    fn env_without_command() {
+        let environment = round_trip("env FOO=1");
+        assert_eq!(environment.variables, vec![(String::from("FOO"), String::from("1"))]);
+        assert_eq!(environment.command, "");
+        assert_eq!(format_exec_environment(&environment), "env FOO=1");
+    }
+
+    #[test]
+    fn env_without_variables_is_plain() {
+        let environment = round_trip("env app %U");
+        assert!(environment.variables.is_empty());
+        assert_eq!(environment.command, "env app %U");
+    }
+
+    #[test]
+    fn shell_script_with_arguments() {
+        let environment = round_trip(r#"sh -c "A=1 exec app \"$@\"" sh %U"#);
+        assert_eq!(environment.variables, vec![(String::from("A"), String::from("1"))]);
+        assert_eq!(
+            environment.shell,
+            Some(ShellWrapper {
+                program: String::from("sh"),
+                trailing: String::from("sh %U"),
+            })
+        );
+        assert_eq!(environment.command, r#"exec app "$@""#);
+        assert_eq!(
+            format_exec_environment(&environment),
+            r#"sh -c "A=1 exec app \"\$@\"" sh %U"#
+        );
+    }
+
+    #[test]
+    fn shell_values_with_spaces_and_percent() {
+        let mut environment = round_trip(r#"bash -c "exec app""#);
+        environment.variables.push((String::from("NAME"), String::from("a b 50%")));
+        let exec = format_exec_environment(&environment);
+        assert_eq!(parse_exec_environment(&exec), environment);
+    }
+
+    #[test]
+    fn removing_variables_drops_env() {
+        let mut environment = round_trip("env FOO=1 app %F");
+        environment.variables.clear();
+        assert_eq!(format_exec_environment(&environment), "app %F");
+    }
+
+    #[test]
+    fn split_exec_quoting() {
+        assert_eq!(
+            split_exec(r#"app "a b" "c\"d" "\$HOME""#).unwrap(),
+            vec!["app", "a b", "c\"d", "$HOME"]
+        );
+        assert!(split_exec(r#"app "open"#).is_err());
+        for arg in ["plain", "with space", "quote\"", "$VAR", "back\\slash", ""] {
+            assert_eq!(split_exec(&quote_exec_argument(arg)).unwrap(), vec![arg]);
+        }
+    }
+}
@@ -0,0 +1,758 @@
+use gtk4::gdk_pixbuf::{Colorspace, InterpType, Pixbuf};
+use ini::Ini;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+
+use crate::desktop_file::DesktopEntry;
+use crate::mime_apps::{data_dirs, data_home};
+
+pub const FALLBACK_THEME: &str = "hicolor";
+pub const ICON_EXTENSIONS: [&str; 3] = ["png", "svg", "xpm"];
+const ICON_CACHE_NAME: &str = "icon-theme.cache";
+const CACHE_HAS_XPM: u16 = 1;
+const CACHE_HAS_SVG: u16 = 2;
+const CACHE_HAS_PNG: u16 = 4;
+const CACHE_END_OF_CHAIN: u32 = 0xffff_ffff;
+
+pub const OTHER_CONTEXT: &str = "Other";
+
+// Fixed-size application icon directories shipped by hicolor
+pub const HICOLOR_APP_SIZES: [u32; 9] = [16, 22, 24, 32, 48, 64, 128, 256, 512];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectoryType {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThemeDirectory {
+    pub name: String,
+    pub size: u32,
+    pub scale: u32,
+    pub context: String,
+    pub kind: DirectoryType,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+}
+
+impl ThemeDirectory {
+    fn from_section(name: &str, section: &ini::Properties) -> Option<Self> {
+        let size: u32 = section.get("Size")?.trim().parse().ok()?;
+        let parse_or = |key: &str, default: u32| {
+            section
+                .get(key)
+                .and_then(|value| value.trim().parse().ok())
+                .unwrap_or(default)
+        };
+
+        let kind = match section.get("Type").map(|value| value.trim()) {
+            Some("Fixed") => DirectoryType::Fixed,
+            Some("Scalable") => DirectoryType::Scalable,
+            _ => DirectoryType::Threshold,
+        };
+
+        Some(ThemeDirectory {
+            name: name.to_string(),
+            size,
+            scale: parse_or("Scale", 1),
+            context: section.get("Context").unwrap_or("").trim().to_string(),
+            kind,
+            min_size: parse_or("MinSize", size),
+            max_size: parse_or("MaxSize", size),
+            threshold: parse_or("Threshold", 2),
+        })
+    }
+
+    // DirectoryMatchesSize from the Icon Theme Specification
+    pub fn matches_size(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+        match self.kind {
+            DirectoryType::Fixed => self.size == size,
+            DirectoryType::Scalable => self.min_size <= size && size <= self.max_size,
+            DirectoryType::Threshold => {
+                self.size.saturating_sub(self.threshold) <= size && size <= self.size + self.threshold
+            }
+        }
+    }
+
+    // DirectorySizeDistance from the Icon Theme Specification
+    pub fn size_distance(&self, size: u32, scale: u32) -> u32 {
+        let wanted = size * scale;
+        match self.kind {
+            DirectoryType::Fixed => (self.size * self.scale).abs_diff(wanted),
+            DirectoryType::Scalable => {
+                if wanted < self.min_size * self.scale {
+                    self.min_size * self.scale - wanted
+                } else {
+                    wanted.saturating_sub(self.max_size * self.scale)
+                }
+            }
+            DirectoryType::Threshold => {
+                if wanted < self.size.saturating_sub(self.threshold) * self.scale {
+                    (self.min_size * self.scale).saturating_sub(wanted)
+                } else if wanted > (self.size + self.threshold) * self.scale {
+                    wanted.saturating_sub(self.max_size * self.scale)
+                } else {
+                    0
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IconTheme {
+    pub name: String,
+    pub display_name: String,
+    pub roots: Vec<PathBuf>,
+    pub inherits: Vec<String>,
+    pub directories: Vec<ThemeDirectory>,
+    pub hidden: bool,
+    caches: Vec<Option<IconCache>>,
+}
+
+impl IconTheme {
+    fn load(name: &str, base_dirs: &[PathBuf]) -> Option<Self> {
+        let roots: Vec<PathBuf> = base_dirs
+            .iter()
+            .map(|base| base.join(name))
+            .filter(|root| root.is_dir())
+            .collect();
+
+        // The first index.theme found describes the theme; the other roots only add files
+        let index = roots
+            .iter()
+            .map(|root| root.join("index.theme"))
+            .find(|index| index.exists())?;
+        let conf = Ini::load_from_file(&index).ok()?;
+        let section = conf.section(Some("Icon Theme"))?;
+
+        let split_list = |value: Option<&str>| -> Vec<String> {
+            value
+                .unwrap_or("")
+                .split(',')
+                .map(|item| item.trim())
+                .filter(|item| !item.is_empty())
+                .map(|item| item.to_string())
+                .collect()
+        };
+
+        let mut directory_names = split_list(section.get("Directories"));
+        for scaled in split_list(section.get("ScaledDirectories")) {
+            if !directory_names.contains(&scaled) {
+                directory_names.push(scaled);
+            }
+        }
+
+        let directories = directory_names
+            .iter()
+            .filter_map(|dir| ThemeDirectory::from_section(dir, conf.section(Some(dir.as_str()))?))
+            .collect();
+
+        Some(IconTheme {
+            name: name.to_string(),
+            display_name: section.get("Name").unwrap_or(name).to_string(),
+            roots: roots.clone(),
+            inherits: split_list(section.get("Inherits")),
+            directories,
+            hidden: section.get("Hidden").map(|value| value.trim()) == Some("true"),
+            caches: roots.iter().map(|root| IconCache::load(root)).collect(),
+        })
+    }
+
+    fn lookup(&self, icon_name: &str, size: u32, scale: u32) -> Option<(PathBuf, ThemeDirectory, bool)> {
+        for directory in self.directories.iter().filter(|dir| dir.matches_size(size, scale)) {
+            if let Some(file) = self.find_file(directory, icon_name) {
+                return Some((file, directory.clone(), true));
+            }
+        }
+
+        let mut closest: Option<(PathBuf, ThemeDirectory, u32)> = None;
+        for directory in &self.directories {
+            let distance = directory.size_distance(size, scale);
+            if closest.as_ref().is_some_and(|(_, _, best)| distance >= *best) {
+                continue;
+            }
+            if let Some(file) = self.find_file(directory, icon_name) {
+                closest = Some((file, directory.clone(), distance));
+            }
+        }
+
+        closest.map(|(file, directory, _)| (file, directory, false))
+    }
+
+    // Icon names present in this theme's own directories, grouped by Context
+    pub fn icon_names(&self) -> BTreeMap<String, BTreeSet<String>> {
+        let mut names: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for directory in &self.directories {
+            let context = if directory.context.is_empty() {
+                OTHER_CONTEXT
+            } else {
+                directory.context.as_str()
+            };
+            for root in &self.roots {
+                let Ok(read_dir) = fs::read_dir(root.join(&directory.name)) else {
+                    continue;
+                };
+                for file in read_dir.flatten() {
+                    let path = file.path();
+                    let is_icon = path
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .is_some_and(|ext| ICON_EXTENSIONS.contains(&ext));
+                    if let (true, Some(stem)) = (is_icon, path.file_stem().and_then(|stem| stem.to_str())) {
+                        names.entry(context.to_string()).or_default().insert(stem.to_string());
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    // A valid cache is authoritative for its root; otherwise every extension is stat-ed
+    fn find_file(&self, directory: &ThemeDirectory, icon_name: &str) -> Option<PathBuf> {
+        for (root, cache) in self.roots.iter().zip(&self.caches) {
+            let dir = root.join(&directory.name);
+            match cache {
+                Some(cache) => {
+                    if let Some(extension) = cache.extension(icon_name, &directory.name) {
+                        return Some(dir.join(format!("{}.{}", icon_name, extension)));
+                    }
+                }
+                None => {
+                    for extension in ICON_EXTENSIONS {
+                        let candidate = dir.join(format!("{}.{}", icon_name, extension));
+                        if candidate.exists() {
+                            return Some(candidate);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+// Reader for the GTK icon-theme.cache format (version 1.0, big-endian):
+// header, a hash table of icon names and the list of theme directories.
+#[derive(Debug, Clone)]
+struct IconCache {
+    data: Vec<u8>,
+    hash_offset: usize,
+    directories: Vec<String>,
+}
+
+impl IconCache {
+    // Like GTK, a cache older than its theme directory is ignored as stale
+    fn load(root: &Path) -> Option<Self> {
+        let path = root.join(ICON_CACHE_NAME);
+        let cache_modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok()?;
+        let root_modified = fs::metadata(root).and_then(|meta| meta.modified()).ok()?;
+        if cache_modified < root_modified {
+            return None;
+        }
+
+        let data = fs::read(&path).ok()?;
+        if read_u16(&data, 0)? != 1 {
+            return None;
+        }
+        let hash_offset = read_u32(&data, 4)? as usize;
+        let directory_list = read_u32(&data, 8)? as usize;
+        let directory_count = read_u32(&data, directory_list)? as usize;
+        let directories = (0..directory_count)
+            .map(|index| {
+                let name_offset = read_u32(&data, directory_list + 4 + index * 4)?;
+                read_string(&data, name_offset as usize)
+            })
+            .collect::<Option<Vec<String>>>()?;
+
+        Some(IconCache {
+            data,
+            hash_offset,
+            directories,
+        })
+    }
+
+    fn extension(&self, icon_name: &str, directory: &str) -> Option<&'static str> {
+        let (_, flags) = self
+            .images(icon_name)?
+            .into_iter()
+            .find(|(index, _)| self.directories.get(*index).map(|dir| dir.as_str()) == Some(directory))?;
+
+        if flags & CACHE_HAS_PNG != 0 {
+            Some("png")
+        } else if flags & CACHE_HAS_SVG != 0 {
+            Some("svg")
+        } else if flags & CACHE_HAS_XPM != 0 {
+            Some("xpm")
+        } else {
+            None
+        }
+    }
+
+    // (directory index, flags) for every image of the icon
+    fn images(&self, icon_name: &str) -> Option<Vec<(usize, u16)>> {
+        let data = &self.data;
+        let buckets = read_u32(data, self.hash_offset)?;
+        if buckets == 0 {
+            return None;
+        }
+
+        let bucket = (icon_name_hash(icon_name) % buckets) as usize;
+        let mut icon = read_u32(data, self.hash_offset + 4 + bucket * 4)?;
+        while icon != CACHE_END_OF_CHAIN {
+            let offset = icon as usize;
+            let name = read_string(data, read_u32(data, offset + 4)? as usize)?;
+            if name == icon_name {
+                let image_list = read_u32(data, offset + 8)? as usize;
+                let count = read_u32(data, image_list)? as usize;
+                return (0..count)
+                    .map(|index| {
+                        let image = image_list + 4 + index * 8;
+                        Some((read_u16(data, image)? as usize, read_u16(data, image + 2)?))
+                    })
+                    .collect();
+            }
+            icon = read_u32(data, offset)?;
+        }
+        None
+    }
+}
+
+// Same hash as GTK's icon_name_hash, which reads the name as signed chars
+fn icon_name_hash(name: &str) -> u32 {
+    let mut bytes = name.bytes().map(|byte| byte as i8 as u32);
+    let Some(first) = bytes.next() else {
+        return 0;
+    };
+    bytes.fold(first, |hash, byte| (hash << 5).wrapping_sub(hash).wrapping_add(byte))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_string(data: &[u8], offset: usize) -> Option<String> {
+    let rest = data.get(offset..)?;
+    let end = rest.iter().position(|&byte| byte == 0)?;
+    Some(String::from_utf8_lossy(&rest[..end]).to_string())
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IconLookup {
+    pub file: Option<PathBuf>,
+    pub theme: Option<String>,
+    pub directory: Option<ThemeDirectory>,
+    pub exact_size: bool,
+    pub walked: Vec<String>,
+}
+
+impl IconLookup {
+    pub fn describe(&self) -> String {
+        match (&self.file, &self.theme, &self.directory) {
+            (Some(file), Some(theme), Some(directory)) => format!(
+                "{} theme, {}px{} ({}){}: {}",
+                theme,
+                directory.size,
+                if directory.scale > 1 { format!("@{}", directory.scale) } else { String::new() },
+                directory.name,
+                if self.exact_size { "" } else { ", closest size" },
+                file.display()
+            ),
+            (Some(file), _, _) => format!("Fallback file: {}", file.display()),
+            _ => format!("Not found (searched {})", self.walked.join(" → ")),
+        }
+    }
+
+    // True when the file was drawn for this size instead of being rescaled to it
+    pub fn is_native(&self, size: u32, scale: u32) -> bool {
+        match (&self.file, &self.directory) {
+            (Some(file), _) if is_svg(file) => true,
+            // A fixed 48px asset is exactly what 24@2 needs: only the pixel count matters
+            (Some(_), Some(directory)) if directory.kind == DirectoryType::Fixed => {
+                directory.size * directory.scale == size * scale
+            }
+            (Some(_), Some(directory)) => directory.size == size && directory.scale == scale,
+            (Some(file), None) => pixel_size(file) == Some(size * scale),
+            _ => false,
+        }
+    }
+}
+
+pub struct IconResolver {
+    theme_name: String,
+    base_dirs: Vec<PathBuf>,
+    themes: RefCell<HashMap<String, Option<Rc<IconTheme>>>>,
+}
+
+impl IconResolver {
+    pub fn new(theme_name: &str) -> Self {
+        IconResolver {
+            theme_name: theme_name.to_string(),
+            base_dirs: icon_base_dirs(),
+            themes: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn theme_name(&self) -> &str {
+        &self.theme_name
+    }
+
+    // Forget loaded themes so newly created icon directories are picked up
+    pub fn clear_cache(&self) {
+        self.themes.borrow_mut().clear();
+    }
+
+    pub fn icon_exists(&self, icon_name: &str) -> bool {
+        self.lookup(icon_name, 48, 1).file.is_some()
+    }
+
+    pub fn theme(&self, name: &str) -> Option<Rc<IconTheme>> {
+        if let Some(cached) = self.themes.borrow().get(name) {
+            return cached.clone();
+        }
+        let loaded = IconTheme::load(name, &self.base_dirs).map(Rc::new);
+        self.themes.borrow_mut().insert(name.to_string(), loaded.clone());
+        loaded
+    }
+
+    // FindIcon from the Icon Theme Specification
+    pub fn lookup(&self, icon_name: &str, size: u32, scale: u32) -> IconLookup {
+        let mut result = IconLookup::default();
+
+        if icon_name.starts_with('/') {
+            result.walked.push(String::from("absolute path"));
+            if Path::new(icon_name).is_file() {
+                result.file = Some(PathBuf::from(icon_name));
+            }
+            return result;
+        }
+
+        // Icon=foo.png is invalid but common; look it up as "foo"
+        let icon_name = strip_icon_extension(icon_name);
+        let mut visited: Vec<String> = Vec::new();
+        for theme_name in [self.theme_name.as_str(), FALLBACK_THEME] {
+            if let Some((file, theme, directory, exact)) =
+                self.find_in_theme(icon_name, size, scale, theme_name, &mut visited)
+            {
+                result.walked = visited;
+                result.file = Some(file);
+                result.theme = Some(theme);
+                result.directory = Some(directory);
+                result.exact_size = exact;
+                return result;
+            }
+        }
+        result.walked = visited;
+
+        for base in &self.base_dirs {
+            result.walked.push(base.display().to_string());
+            for extension in ICON_EXTENSIONS {
+                let candidate = base.join(format!("{}.{}", icon_name, extension));
+                if candidate.is_file() {
+                    result.file = Some(candidate);
+                    return result;
+                }
+            }
+        }
+
+        result
+    }
+
+    // Every icon name reachable through the theme, its parents and hicolor
+    pub fn available_icons(&self) -> BTreeMap<String, BTreeSet<String>> {
+        let mut names: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let mut pending = vec![FALLBACK_THEME.to_string(), self.theme_name.clone()];
+        let mut visited: Vec<String> = Vec::new();
+
+        while let Some(theme_name) = pending.pop() {
+            if visited.contains(&theme_name) {
+                continue;
+            }
+            visited.push(theme_name.clone());
+
+            let Some(theme) = self.theme(&theme_name) else {
+                continue;
+            };
+            for (context, icons) in theme.icon_names() {
+                names.entry(context).or_default().extend(icons);
+            }
+            pending.extend(theme.inherits.iter().rev().cloned());
+        }
+
+        names
+    }
+
+    fn find_in_theme(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+        theme_name: &str,
+        visited: &mut Vec<String>,
+    ) -> Option<(PathBuf, String, ThemeDirectory, bool)> {
+        if visited.iter().any(|name| name == theme_name) {
+            return None;
+        }
+        visited.push(theme_name.to_string());
+
+        let theme = self.theme(theme_name)?;
+        if let Some((file, directory, exact)) = theme.lookup(icon_name, size, scale) {
+            return Some((file, theme.name.clone(), directory, exact));
+        }
+
+        for parent in &theme.inherits {
+            if let Some(found) = self.find_in_theme(icon_name, size, scale, parent, visited) {
+                return Some(found);
+            }
+        }
+        None
+    }
+}
+
+// Base directories in lookup order: $HOME/.icons, $XDG_DATA_DIRS/icons and /usr/share/pixmaps
+pub fn icon_base_dirs() -> Vec<PathBuf> {
+    let home = env::var("HOME").unwrap_or_default();
+    let mut dirs = vec![
+        PathBuf::from(format!("{}/.icons", home)),
+        PathBuf::from(format!("{}/icons", data_home())),
+    ];
+
+    for dir in data_dirs() {
+        let path = PathBuf::from(format!("{}/icons", dir));
+        if !dirs.contains(&path) {
+            dirs.push(path);
+        }
+    }
+
+    dirs.push(PathBuf::from("/usr/share/pixmaps"));
+    dirs
+}
+
+pub fn user_hicolor_dir() -> PathBuf {
+    PathBuf::from(format!("{}/icons/{}", data_home(), FALLBACK_THEME))
+}
+
+// Application icon path inside the user's hicolor theme; no size means scalable
+pub fn user_icon_path(size: Option<u32>, icon_name: &str, extension: &str) -> PathBuf {
+    let size_dir = match size {
+        Some(size) => format!("{}x{}", size, size),
+        None => String::from("scalable"),
+    };
+    user_hicolor_dir()
+        .join(size_dir)
+        .join("apps")
+        .join(format!("{}.{}", icon_name, extension))
+}
+
+#[derive(Debug, Clone)]
+pub struct OrphanedIcon {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+}
+
+// Application icons in user locations that no desktop entry refers to by name or path.
+// Themes installed under ~/.local/share/icons are left alone: only loose files, the
+// hicolor apps directories and pixmaps are considered.
+pub fn orphaned_user_icons(entries: &[DesktopEntry]) -> Vec<OrphanedIcon> {
+    // Icon= values like "foo.png" still point at foo.svg and friends through the theme lookup,
+    // so both the value itself and the value without its extension count as references
+    let mut referenced: BTreeSet<&str> = BTreeSet::new();
+    for icon in entries.iter().map(|entry| entry.icon.trim()).filter(|icon| !icon.is_empty()) {
+        referenced.insert(icon);
+        referenced.insert(strip_icon_extension(icon));
+    }
+
+    let home = env::var("HOME").unwrap_or_default();
+    let mut files = Vec::new();
+    for icons_dir in [
+        PathBuf::from(format!("{}/icons", data_home())),
+        PathBuf::from(format!("{}/.icons", home)),
+    ] {
+        collect_icon_files(&icons_dir, &mut files);
+        if let Ok(size_dirs) = fs::read_dir(icons_dir.join(FALLBACK_THEME)) {
+            for size_dir in size_dirs.flatten() {
+                collect_icon_files(&size_dir.path().join("apps"), &mut files);
+            }
+        }
+    }
+    collect_icon_files(&PathBuf::from(format!("{}/pixmaps", data_home())), &mut files);
+
+    let mut orphans: Vec<OrphanedIcon> = files
+        .into_iter()
+        .filter(|path| {
+            let by_path = referenced.contains(path.to_string_lossy().as_ref());
+            let by_name = [path.file_name(), path.file_stem()]
+                .into_iter()
+                .flatten()
+                .filter_map(|name| name.to_str())
+                .any(|name| referenced.contains(name));
+            !by_path && !by_name
+        })
+        .map(|path| OrphanedIcon {
+            size_bytes: fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0),
+            path,
+        })
+        .collect();
+
+    orphans.sort_by(|a, b| a.path.cmp(&b.path));
+    orphans
+}
+
+pub fn strip_icon_extension(icon: &str) -> &str {
+    ICON_EXTENSIONS
+        .iter()
+        .find_map(|extension| {
+            icon.strip_suffix(extension)
+                .and_then(|rest| rest.strip_suffix('.'))
+        })
+        .unwrap_or(icon)
+}
+
+fn collect_icon_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    for item in read_dir.flatten() {
+        let path = item.path();
+        let is_icon = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ICON_EXTENSIONS.contains(&ext));
+        if is_icon && path.is_file() {
+            files.push(path);
+        }
+    }
+}
+
+// Icon names become file names inside the theme, so they must not leave its directories
+pub fn check_icon_name(icon_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if icon_name.is_empty() || icon_name.contains('/') || icon_name == "." || icon_name == ".." {
+        return Err(format!("Invalid icon name '{}'", icon_name).into());
+    }
+    Ok(())
+}
+
+// Copies an image into the user's hicolor theme as `icon_name`, rendering every
+// standard size up to the source size (all of them for SVG) on a square canvas.
+pub fn import_icon(source: &Path, icon_name: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    check_icon_name(icon_name)?;
+
+    let is_svg = is_svg(source);
+    let source_size = pixel_size(source).ok_or("Unsupported image format")?;
+
+    let mut sizes: Vec<u32> = HICOLOR_APP_SIZES
+        .iter()
+        .copied()
+        .filter(|size| is_svg || *size <= source_size)
+        .collect();
+    if sizes.is_empty() {
+        sizes.push(HICOLOR_APP_SIZES[0]);
+    }
+
+    let mut written = Vec::new();
+    if is_svg {
+        let target = user_icon_path(None, icon_name, "svg");
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(source, &target)?;
+        written.push(target);
+    }
+
+    for size in sizes {
+        let scaled = Pixbuf::from_file_at_scale(source, size as i32, size as i32, true)?;
+        let canvas = Pixbuf::new(Colorspace::Rgb, true, 8, size as i32, size as i32)
+            .ok_or("Failed to allocate icon canvas")?;
+        canvas.fill(0);
+        let (scaled_width, scaled_height) = (scaled.width().min(size as i32), scaled.height().min(size as i32));
+        scaled.scale(
+            &canvas,
+            (size as i32 - scaled_width) / 2,
+            (size as i32 - scaled_height) / 2,
+            scaled_width,
+            scaled_height,
+            ((size as i32 - scaled_width) / 2) as f64,
+            ((size as i32 - scaled_height) / 2) as f64,
+            1.0,
+            1.0,
+            InterpType::Bilinear,
+        );
+
+        let target = user_icon_path(Some(size), icon_name, "png");
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        canvas.savev(&target, "png", &[])?;
+        written.push(target);
+    }
+
+    update_icon_cache(&user_hicolor_dir());
+    Ok(written)
+}
+
+pub fn is_svg(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"))
+}
+
+// Largest side of a raster image in pixels
+pub fn pixel_size(path: &Path) -> Option<u32> {
+    Pixbuf::file_info(path).map(|(_, width, height)| width.max(height) as u32)
+}
+
+pub fn update_icon_cache(theme_dir: &Path) {
+    // The user hicolor directory normally has no index.theme, hence --ignore-theme-index
+    match Command::new("gtk-update-icon-cache")
+        .arg("--force")
+        .arg("--ignore-theme-index")
+        .arg(theme_dir)
+        .status()
+    {
+        Ok(status) if status.success() => println!("Updated icon cache in {}", theme_dir.display()),
+        Ok(status) => eprintln!("gtk-update-icon-cache exited with {}", status),
+        Err(e) => eprintln!("Could not run gtk-update-icon-cache: {}", e),
+    }
+}
+
+// Themes that can be chosen for previews; cursor-only and hidden themes are skipped
+pub fn installed_themes() -> Vec<IconTheme> {
+    let base_dirs = icon_base_dirs();
+    let mut names: Vec<String> = Vec::new();
+    for base in &base_dirs {
+        let Ok(read_dir) = fs::read_dir(base) else {
+            continue;
+        };
+        for dir in read_dir.flatten() {
+            let name = dir.file_name().to_string_lossy().to_string();
+            if dir.path().join("index.theme").is_file() && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+
+    let mut themes: Vec<IconTheme> = names
+        .iter()
+        .filter_map(|name| IconTheme::load(name, &base_dirs))
+        .filter(|theme| !theme.hidden && !theme.directories.is_empty())
+        .collect();
+    themes.sort_by_key(|theme| theme.display_name.to_lowercase());
+    themes
+}
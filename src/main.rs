@@ -1,12 +1,22 @@
 use gtk4::prelude::*;
 use gtk4::Application;
 
+mod categories;
 mod desktop_file;
+mod exec;
+mod icon_theme;
+mod menu;
+mod mime_apps;
+mod mime_database;
+mod monogram;
+mod profiles;
 mod ui;
+mod wrappers;
 
 use ui::MainWindow;
 
 const APP_ID: &str = "com.manchatz.DesktopEntryManager";
+const CONFIG_DIR_NAME: &str = "manchatz-desktop-entry-manager";
 
 fn main() {
     let app = Application::builder()
@@ -0,0 +1,1656 @@
+use ini::Ini;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::desktop_file::file_name_slug;
+use crate::exec::find_program;
+use crate::mime_apps::{config_dirs, config_home, current_desktops, data_dirs, data_home};
+
+const MENU_FILE_NAME: &str = "applications.menu";
+
+// Minimal XML reader for menu files: elements, attributes, text, comments,
+// processing instructions and the DOCTYPE declaration.
+#[derive(Debug, Clone)]
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlNode>,
+}
+
+#[derive(Debug, Clone)]
+pub enum XmlNode {
+    Element(XmlElement),
+    Text(String),
+}
+
+impl XmlElement {
+    pub fn new(name: &str) -> Self {
+        XmlElement {
+            name: name.to_string(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_text(name: &str, text: &str) -> Self {
+        let mut element = XmlElement::new(name);
+        element.children.push(XmlNode::Text(text.to_string()));
+        element
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|child| match child {
+                XmlNode::Text(text) => Some(text.as_str()),
+                XmlNode::Element(_) => None,
+            })
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &XmlElement> {
+        self.children.iter().filter_map(|child| match child {
+            XmlNode::Element(element) => Some(element),
+            XmlNode::Text(_) => None,
+        })
+    }
+
+    pub fn to_xml(&self, indent: usize) -> String {
+        let padding = "  ".repeat(indent);
+        let attributes: String = self
+            .attributes
+            .iter()
+            .map(|(key, value)| format!(" {}=\"{}\"", key, escape_xml(value)))
+            .collect();
+
+        if self.children.is_empty() {
+            return format!("{}<{}{}/>\n", padding, self.name, attributes);
+        }
+        if self.elements().next().is_none() {
+            return format!(
+                "{}<{}{}>{}</{}>\n",
+                padding,
+                self.name,
+                attributes,
+                escape_xml(&self.text()),
+                self.name
+            );
+        }
+
+        let mut xml = format!("{}<{}{}>\n", padding, self.name, attributes);
+        for child in self.elements() {
+            xml.push_str(&child.to_xml(indent + 1));
+        }
+        xml.push_str(&format!("{}</{}>\n", padding, self.name));
+        xml
+    }
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape_xml(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find(';') else {
+            result.push_str(&rest[start..]);
+            return result;
+        };
+        let entity = &rest[start + 1..start + end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => result.push(c),
+            None => result.push_str(&rest[start..start + end + 1]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+pub fn parse_xml(source: &str) -> Result<XmlElement, String> {
+    // The stack holds the open elements; the document root is collected at depth zero
+    let mut stack: Vec<XmlElement> = Vec::new();
+    let mut root: Option<XmlElement> = None;
+    let mut rest = source;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("<!--") {
+            let end = after.find("-->").ok_or("Unterminated comment")?;
+            rest = &after[end + 3..];
+        } else if let Some(after) = rest.strip_prefix("<?") {
+            let end = after.find("?>").ok_or("Unterminated processing instruction")?;
+            rest = &after[end + 2..];
+        } else if let Some(after) = rest.strip_prefix("<!") {
+            // DOCTYPE, possibly with an internal subset in brackets
+            let mut depth = 0;
+            let end = after
+                .char_indices()
+                .find(|(_, c)| match c {
+                    '[' => {
+                        depth += 1;
+                        false
+                    }
+                    ']' => {
+                        depth -= 1;
+                        false
+                    }
+                    '>' => depth == 0,
+                    _ => false,
+                })
+                .map(|(index, _)| index)
+                .ok_or("Unterminated declaration")?;
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let end = after.find('>').ok_or("Unterminated closing tag")?;
+            let name = after[..end].trim();
+            let element = stack.pop().ok_or_else(|| format!("Unexpected </{}>", name))?;
+            if element.name != name {
+                return Err(format!("Expected </{}> but found </{}>", element.name, name));
+            }
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(XmlNode::Element(element)),
+                None => root = Some(element),
+            }
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('<') {
+            let end = tag_end(after).ok_or("Unterminated tag")?;
+            let tag = &after[..end];
+            let self_closing = tag.ends_with('/');
+            let element = parse_tag(tag.trim_end_matches('/'))?;
+            if self_closing {
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(XmlNode::Element(element)),
+                    None => root = Some(element),
+                }
+            } else {
+                stack.push(element);
+            }
+            rest = &after[end + 1..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let text = &rest[..end];
+            if let Some(parent) = stack.last_mut() {
+                if !text.trim().is_empty() {
+                    parent.children.push(XmlNode::Text(unescape_xml(text)));
+                }
+            }
+            rest = &rest[end..];
+        }
+    }
+
+    if let Some(open) = stack.last() {
+        return Err(format!("Missing </{}>", open.name));
+    }
+    root.ok_or_else(|| String::from("No root element"))
+}
+
+// Position of the '>' closing a tag, ignoring any inside quoted attribute values
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    for (index, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_tag(tag: &str) -> Result<XmlElement, String> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let mut element = XmlElement::new(&tag[..name_end]);
+    let mut rest = tag[name_end..].trim_start();
+
+    while !rest.is_empty() {
+        let equals = rest.find('=').ok_or_else(|| format!("Malformed attribute in <{}>", element.name))?;
+        let key = rest[..equals].trim().to_string();
+        let value_part = rest[equals + 1..].trim_start();
+        let quote = value_part
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| format!("Unquoted attribute in <{}>", element.name))?;
+        let value_end = value_part[1..]
+            .find(quote)
+            .ok_or_else(|| format!("Unterminated attribute in <{}>", element.name))?;
+        element
+            .attributes
+            .push((key, unescape_xml(&value_part[1..value_end + 1])));
+        rest = value_part[value_end + 2..].trim_start();
+    }
+
+    Ok(element)
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    All,
+    Filename(String),
+    Category(String),
+    And(Vec<Matcher>),
+    Or(Vec<Matcher>),
+    Not(Vec<Matcher>),
+}
+
+impl Matcher {
+    fn parse(element: &XmlElement) -> Option<Self> {
+        let children = || element.elements().filter_map(Matcher::parse).collect();
+        match element.name.as_str() {
+            "All" => Some(Matcher::All),
+            "Filename" => Some(Matcher::Filename(element.text())),
+            "Category" => Some(Matcher::Category(element.text())),
+            "And" => Some(Matcher::And(children())),
+            "Or" => Some(Matcher::Or(children())),
+            "Not" => Some(Matcher::Not(children())),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, entry: &MenuEntryInfo) -> bool {
+        match self {
+            Matcher::All => true,
+            Matcher::Filename(id) => entry.id == *id,
+            Matcher::Category(category) => entry.categories.iter().any(|c| c == category),
+            Matcher::And(matchers) => matchers.iter().all(|matcher| matcher.matches(entry)),
+            Matcher::Or(matchers) => matchers.iter().any(|matcher| matcher.matches(entry)),
+            Matcher::Not(matchers) => !matchers.iter().any(|matcher| matcher.matches(entry)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum LayoutItem {
+    Filename(String),
+    Menuname(String),
+    Separator,
+    MergeMenus,
+    MergeFiles,
+    MergeAll,
+}
+
+#[derive(Debug, Clone)]
+struct Layout {
+    items: Vec<LayoutItem>,
+    show_empty: bool,
+}
+
+impl Layout {
+    fn parse(element: &XmlElement, inherited_show_empty: bool) -> Self {
+        let mut items: Vec<LayoutItem> = element
+            .elements()
+            .filter_map(|item| match item.name.as_str() {
+                "Filename" => Some(LayoutItem::Filename(item.text())),
+                "Menuname" => Some(LayoutItem::Menuname(item.text())),
+                "Separator" => Some(LayoutItem::Separator),
+                "Merge" => match item.attribute("type") {
+                    Some("menus") => Some(LayoutItem::MergeMenus),
+                    Some("files") => Some(LayoutItem::MergeFiles),
+                    Some("all") => Some(LayoutItem::MergeAll),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        // A layout may only set show_empty; its items then follow the default order
+        if items.is_empty() {
+            items = Layout::default_layout().items;
+        }
+        Layout {
+            items,
+            show_empty: element
+                .attribute("show_empty")
+                .map(|value| value == "true")
+                .unwrap_or(inherited_show_empty),
+        }
+    }
+
+    fn default_layout() -> Self {
+        Layout {
+            items: vec![LayoutItem::MergeMenus, LayoutItem::MergeFiles],
+            show_empty: false,
+        }
+    }
+}
+
+// The subset of a desktop file that decides where, and whether, it appears in menus
+#[derive(Debug, Clone)]
+pub struct MenuEntryInfo {
+    pub id: String,
+    pub path: PathBuf,
+    pub name: String,
+    pub icon: String,
+    pub categories: Vec<String>,
+    pub no_display: bool,
+    pub hidden: bool,
+    pub only_show_in: Vec<String>,
+    pub not_show_in: Vec<String>,
+    pub try_exec: String,
+}
+
+impl MenuEntryInfo {
+    pub fn load(path: &Path, id: &str) -> Option<Self> {
+        let conf = Ini::load_from_file(path).ok()?;
+        let section = conf.section(Some("Desktop Entry"))?;
+        let list = |key: &str| -> Vec<String> {
+            section
+                .get(key)
+                .unwrap_or("")
+                .split(';')
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+                .map(|value| value.to_string())
+                .collect()
+        };
+        let flag = |key: &str| section.get(key).map(|value| value.trim()) == Some("true");
+
+        Some(MenuEntryInfo {
+            id: id.to_string(),
+            path: path.to_path_buf(),
+            name: section.get("Name").unwrap_or(id).to_string(),
+            icon: section.get("Icon").unwrap_or("").to_string(),
+            categories: list("Categories"),
+            no_display: flag("NoDisplay"),
+            hidden: flag("Hidden"),
+            only_show_in: list("OnlyShowIn"),
+            not_show_in: list("NotShowIn"),
+            try_exec: section.get("TryExec").unwrap_or("").trim().to_string(),
+        })
+    }
+
+    pub fn shown_in_current_desktop(&self) -> bool {
+        let desktops = current_desktops();
+        let listed = |list: &[String]| {
+            list.iter()
+                .any(|desktop| desktops.contains(&desktop.to_lowercase()))
+        };
+        if !self.only_show_in.is_empty() && !listed(&self.only_show_in) {
+            return false;
+        }
+        !listed(&self.not_show_in)
+    }
+
+    pub fn is_displayed(&self) -> bool {
+        !self.no_display
+            && !self.hidden
+            && self.shown_in_current_desktop()
+            && (self.try_exec.is_empty() || find_program(&self.try_exec).is_some())
+    }
+}
+
+// Desktop-file IDs include the path below the applications directory, joined with '-'
+fn collect_desktop_files(dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut items: Vec<PathBuf> = read_dir.flatten().map(|item| item.path()).collect();
+    items.sort();
+    for path in items {
+        let Some(file_name) = path.file_name().map(|name| name.to_string_lossy().to_string()) else {
+            continue;
+        };
+        if path.is_dir() {
+            collect_desktop_files(&path, &format!("{}{}-", prefix, file_name), files);
+        } else if file_name.ends_with(".desktop") {
+            files.push((format!("{}{}", prefix, file_name), path));
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DirectoryInfo {
+    pub path: PathBuf,
+    pub name: String,
+    pub icon: String,
+    pub no_display: bool,
+}
+
+fn load_directory_file(path: &Path) -> Option<DirectoryInfo> {
+    let conf = Ini::load_from_file(path).ok()?;
+    let section = conf.section(Some("Desktop Entry"))?;
+    Some(DirectoryInfo {
+        path: path.to_path_buf(),
+        name: section.get("Name").unwrap_or("").to_string(),
+        icon: section.get("Icon").unwrap_or("").to_string(),
+        no_display: section.get("NoDisplay").map(|value| value.trim()) == Some("true"),
+    })
+}
+
+// A <Menu> after merging, with its rules and the entries they selected
+#[derive(Debug, Clone)]
+struct RawMenu {
+    name: String,
+    app_dirs: Vec<PathBuf>,
+    directory_dirs: Vec<PathBuf>,
+    directories: Vec<String>,
+    deleted: bool,
+    only_unallocated: bool,
+    rules: Vec<(bool, Matcher)>,
+    layout: Option<XmlElement>,
+    default_layout: Option<XmlElement>,
+    submenus: Vec<RawMenu>,
+    matched: Vec<MenuEntryInfo>,
+}
+
+impl RawMenu {
+    fn from_element(element: &XmlElement) -> Self {
+        let mut menu = RawMenu {
+            name: String::new(),
+            app_dirs: Vec::new(),
+            directory_dirs: Vec::new(),
+            directories: Vec::new(),
+            deleted: false,
+            only_unallocated: false,
+            rules: Vec::new(),
+            layout: None,
+            default_layout: None,
+            submenus: Vec::new(),
+            matched: Vec::new(),
+        };
+
+        // Later elements win, so flags and single values simply keep the last one seen
+        for child in element.elements() {
+            match child.name.as_str() {
+                "Name" => menu.name = child.text(),
+                "AppDir" => push_last(&mut menu.app_dirs, PathBuf::from(child.text())),
+                "DirectoryDir" => push_last(&mut menu.directory_dirs, PathBuf::from(child.text())),
+                "Directory" => menu.directories.push(child.text()),
+                "Deleted" => menu.deleted = true,
+                "NotDeleted" => menu.deleted = false,
+                "OnlyUnallocated" => menu.only_unallocated = true,
+                "NotOnlyUnallocated" => menu.only_unallocated = false,
+                "Include" | "Exclude" => {
+                    let include = child.name == "Include";
+                    for matcher in child.elements().filter_map(Matcher::parse) {
+                        menu.rules.push((include, matcher));
+                    }
+                }
+                "Layout" => menu.layout = Some(child.clone()),
+                "DefaultLayout" => menu.default_layout = Some(child.clone()),
+                "Menu" => menu.submenus.push(RawMenu::from_element(child)),
+                _ => {}
+            }
+        }
+        menu
+    }
+
+    fn select(&self, pool: &HashMap<String, MenuEntryInfo>) -> Vec<MenuEntryInfo> {
+        let mut selected: Vec<String> = Vec::new();
+        for (include, matcher) in &self.rules {
+            if *include {
+                let mut ids: Vec<&String> = pool
+                    .iter()
+                    .filter(|(id, entry)| !selected.contains(id) && matcher.matches(entry))
+                    .map(|(id, _)| id)
+                    .collect();
+                ids.sort();
+                selected.extend(ids.into_iter().cloned());
+            } else {
+                selected.retain(|id| !pool.get(id).is_some_and(|entry| matcher.matches(entry)));
+            }
+        }
+        selected.iter().filter_map(|id| pool.get(id).cloned()).collect()
+    }
+}
+
+// Keeps the most recent occurrence last, since later directories take priority
+fn push_last(list: &mut Vec<PathBuf>, path: PathBuf) {
+    list.retain(|existing| existing != &path);
+    list.push(path);
+}
+
+struct EntryPool {
+    loaded: HashMap<PathBuf, Option<MenuEntryInfo>>,
+}
+
+impl EntryPool {
+    // Entries visible to a menu through its own and its parents' AppDirs
+    fn collect(&mut self, app_dirs: &[PathBuf]) -> HashMap<String, MenuEntryInfo> {
+        let mut pool = HashMap::new();
+        for dir in app_dirs {
+            let mut files = Vec::new();
+            collect_desktop_files(dir, "", &mut files);
+            for (id, path) in files {
+                let entry = self
+                    .loaded
+                    .entry(path.clone())
+                    .or_insert_with(|| MenuEntryInfo::load(&path, &id))
+                    .clone();
+                if let Some(entry) = entry {
+                    pool.insert(id, entry);
+                }
+            }
+        }
+        pool
+    }
+}
+
+fn allocate(
+    menu: &mut RawMenu,
+    inherited_dirs: &[PathBuf],
+    pools: &mut EntryPool,
+    allocated: &mut HashSet<String>,
+    unallocated_pass: bool,
+) {
+    let mut app_dirs = inherited_dirs.to_vec();
+    for dir in &menu.app_dirs {
+        push_last(&mut app_dirs, dir.clone());
+    }
+
+    if menu.only_unallocated == unallocated_pass {
+        let pool = pools.collect(&app_dirs);
+        menu.matched = menu.select(&pool);
+        if unallocated_pass {
+            menu.matched.retain(|entry| !allocated.contains(&entry.id));
+        } else {
+            allocated.extend(menu.matched.iter().map(|entry| entry.id.clone()));
+        }
+    }
+
+    for submenu in &mut menu.submenus {
+        allocate(submenu, &app_dirs, pools, allocated, unallocated_pass);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MenuNode {
+    pub name: String,
+    // Names of the enclosing submenus below the root, ending with this one
+    pub path: Vec<String>,
+    pub deleted: bool,
+    // Desktop IDs the rules selected, including entries that are not displayed
+    pub matched_ids: Vec<String>,
+    pub directory: Option<DirectoryInfo>,
+    pub items: Vec<MenuItem>,
+    pub visible: bool,
+}
+
+impl MenuNode {
+    pub fn display_name(&self) -> &str {
+        match &self.directory {
+            Some(directory) if !directory.name.is_empty() => &directory.name,
+            _ => &self.name,
+        }
+    }
+
+    pub fn submenu(&self, path: &[String]) -> Option<&MenuNode> {
+        let Some((first, rest)) = path.split_first() else {
+            return Some(self);
+        };
+        self.items.iter().find_map(|item| match item {
+            MenuItem::Submenu(submenu) if &submenu.name == first => submenu.submenu(rest),
+            _ => None,
+        })
+    }
+
+    pub fn all_nodes(&self) -> Vec<&MenuNode> {
+        let mut nodes = vec![self];
+        for item in &self.items {
+            if let MenuItem::Submenu(submenu) = item {
+                nodes.extend(submenu.all_nodes());
+            }
+        }
+        nodes
+    }
+
+    pub fn contains_entry(&self, desktop_id: &str) -> bool {
+        self.items
+            .iter()
+            .any(|item| matches!(item, MenuItem::Entry(entry) if entry.id == desktop_id))
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.items
+            .iter()
+            .map(|item| match item {
+                MenuItem::Entry(_) => 1,
+                MenuItem::Submenu(submenu) => submenu.entry_count(),
+                MenuItem::Separator => 0,
+            })
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MenuItem {
+    Submenu(MenuNode),
+    Entry(MenuEntryInfo),
+    Separator,
+}
+
+#[derive(Debug, Clone)]
+pub struct MenuTree {
+    pub source: PathBuf,
+    // True when XDG_MENU_PREFIX named no file and the root menu was picked from what is installed
+    pub source_guessed: bool,
+    pub merged_files: Vec<PathBuf>,
+    pub root: MenuNode,
+    pub hidden_menus: Vec<MenuNode>,
+}
+
+fn build_node(
+    menu: &RawMenu,
+    path: Vec<String>,
+    directory_dirs: &[PathBuf],
+    default_layout: Option<&XmlElement>,
+    hidden_menus: &mut Vec<MenuNode>,
+) -> MenuNode {
+    let mut dirs = directory_dirs.to_vec();
+    for dir in &menu.directory_dirs {
+        push_last(&mut dirs, dir.clone());
+    }
+
+    // The last <Directory> that resolves wins; later DirectoryDirs take priority
+    let directory = menu.directories.iter().rev().find_map(|name| {
+        dirs.iter()
+            .rev()
+            .map(|dir| dir.join(name))
+            .find(|candidate| candidate.is_file())
+            .and_then(|candidate| load_directory_file(&candidate))
+    });
+
+    let default_layout = menu.default_layout.as_ref().or(default_layout);
+    let inherited_show_empty = default_layout
+        .map(|element| Layout::parse(element, false).show_empty)
+        .unwrap_or(false);
+    let layout = menu
+        .layout
+        .as_ref()
+        .or(default_layout)
+        .map(|element| Layout::parse(element, inherited_show_empty))
+        .unwrap_or_else(Layout::default_layout);
+
+    let mut submenus: Vec<MenuNode> = Vec::new();
+    for submenu in menu.submenus.iter() {
+        let mut submenu_path = path.clone();
+        submenu_path.push(submenu.name.clone());
+        let node = build_node(submenu, submenu_path, &dirs, default_layout, hidden_menus);
+        if node.visible {
+            submenus.push(node);
+        } else {
+            hidden_menus.push(node);
+        }
+    }
+
+    let mut entries: Vec<MenuEntryInfo> = menu
+        .matched
+        .iter()
+        .filter(|entry| entry.is_displayed())
+        .cloned()
+        .collect();
+    entries.sort_by_key(|entry| entry.name.to_lowercase());
+    submenus.sort_by_key(|node| node.display_name().to_lowercase());
+
+    let items = apply_layout(&layout, submenus, entries);
+    let has_entries = items.iter().any(|item| !matches!(item, MenuItem::Separator));
+    let visible = !menu.deleted
+        && !directory.as_ref().is_some_and(|directory| directory.no_display)
+        && (has_entries || layout.show_empty);
+
+    MenuNode {
+        name: menu.name.clone(),
+        path,
+        deleted: menu.deleted,
+        matched_ids: menu.matched.iter().map(|entry| entry.id.clone()).collect(),
+        directory,
+        items,
+        visible,
+    }
+}
+
+fn apply_layout(layout: &Layout, submenus: Vec<MenuNode>, entries: Vec<MenuEntryInfo>) -> Vec<MenuItem> {
+    let mut submenus: Vec<Option<MenuNode>> = submenus.into_iter().map(Some).collect();
+    let mut entries: Vec<Option<MenuEntryInfo>> = entries.into_iter().map(Some).collect();
+
+    // Items named explicitly anywhere in the layout are not repeated by <Merge>
+    let named_menus: Vec<&String> = layout
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            LayoutItem::Menuname(name) => Some(name),
+            _ => None,
+        })
+        .collect();
+    let named_files: Vec<&String> = layout
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            LayoutItem::Filename(id) => Some(id),
+            _ => None,
+        })
+        .collect();
+
+    let mut items: Vec<MenuItem> = Vec::new();
+    for item in &layout.items {
+        match item {
+            LayoutItem::Menuname(name) => {
+                if let Some(node) = submenus
+                    .iter_mut()
+                    .find(|node| node.as_ref().is_some_and(|node| &node.name == name))
+                    .and_then(|slot| slot.take())
+                {
+                    items.push(MenuItem::Submenu(node));
+                }
+            }
+            LayoutItem::Filename(id) => {
+                if let Some(entry) = entries
+                    .iter_mut()
+                    .find(|entry| entry.as_ref().is_some_and(|entry| &entry.id == id))
+                    .and_then(|slot| slot.take())
+                {
+                    items.push(MenuItem::Entry(entry));
+                }
+            }
+            LayoutItem::Separator => items.push(MenuItem::Separator),
+            LayoutItem::MergeMenus | LayoutItem::MergeAll => {
+                let mut merged: Vec<(String, MenuItem)> = submenus
+                    .iter_mut()
+                    .filter(|node| node.as_ref().is_some_and(|node| !named_menus.contains(&&node.name)))
+                    .filter_map(|slot| slot.take())
+                    .map(|node| (node.display_name().to_lowercase(), MenuItem::Submenu(node)))
+                    .collect();
+                if matches!(item, LayoutItem::MergeAll) {
+                    merged.extend(
+                        entries
+                            .iter_mut()
+                            .filter(|entry| entry.as_ref().is_some_and(|entry| !named_files.contains(&&entry.id)))
+                            .filter_map(|slot| slot.take())
+                            .map(|entry| (entry.name.to_lowercase(), MenuItem::Entry(entry))),
+                    );
+                    merged.sort_by(|a, b| a.0.cmp(&b.0));
+                }
+                items.extend(merged.into_iter().map(|(_, item)| item));
+            }
+            LayoutItem::MergeFiles => {
+                items.extend(
+                    entries
+                        .iter_mut()
+                        .filter(|entry| entry.as_ref().is_some_and(|entry| !named_files.contains(&&entry.id)))
+                        .filter_map(|slot| slot.take())
+                        .map(MenuItem::Entry),
+                );
+            }
+        }
+    }
+
+    // Separators only make sense between two visible items
+    let mut cleaned: Vec<MenuItem> = Vec::new();
+    for item in items {
+        let is_separator = matches!(item, MenuItem::Separator);
+        if is_separator && cleaned.last().is_none_or(|last| matches!(last, MenuItem::Separator)) {
+            continue;
+        }
+        cleaned.push(item);
+    }
+    if matches!(cleaned.last(), Some(MenuItem::Separator)) {
+        cleaned.pop();
+    }
+    cleaned
+}
+
+// Menu prefix from XDG_MENU_PREFIX, e.g. "gnome-" for gnome-applications.menu
+fn menu_file_name() -> String {
+    format!("{}{}", env::var("XDG_MENU_PREFIX").unwrap_or_default(), MENU_FILE_NAME)
+}
+
+// Config directories from most to least important, as used for menu lookup
+pub fn menu_config_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from(config_home())];
+    dirs.extend(config_dirs().into_iter().map(PathBuf::from));
+    dirs.into_iter().map(|dir| dir.join("menus")).collect()
+}
+
+pub fn find_root_menu() -> Option<PathBuf> {
+    let dirs = menu_config_dirs();
+    let preferred = menu_file_name();
+    for dir in &dirs {
+        let candidate = dir.join(&preferred);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+    }
+
+    // Without XDG_MENU_PREFIX, fall back to whichever *applications.menu the system ships
+    for dir in &dirs {
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            continue;
+        };
+        let mut candidates: Vec<PathBuf> = read_dir
+            .flatten()
+            .map(|item| item.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.ends_with(MENU_FILE_NAME))
+            })
+            .collect();
+        candidates.sort();
+        if let Some(found) = candidates.into_iter().next() {
+            return Some(found);
+        }
+    }
+    None
+}
+
+// Name of the merge directory for a menu file: applications.menu uses applications-merged.
+// Any prefix is dropped, also when it was guessed rather than taken from XDG_MENU_PREFIX,
+// since desktops read applications-merged whatever their menu file is called.
+fn merge_dir_name(menu_file: &Path) -> String {
+    let stem = menu_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let menu_stem = MENU_FILE_NAME.trim_end_matches(".menu");
+    let base = match stem.find(menu_stem) {
+        Some(index) => &stem[index..],
+        None => stem
+            .strip_prefix(&env::var("XDG_MENU_PREFIX").unwrap_or_default())
+            .unwrap_or(&stem),
+    };
+    format!("{}-merged", base)
+}
+
+fn default_dirs(subdir: &str) -> Vec<PathBuf> {
+    // Least important first: later directories take priority
+    let mut dirs: Vec<PathBuf> = data_dirs()
+        .into_iter()
+        .rev()
+        .map(|dir| PathBuf::from(dir).join(subdir))
+        .collect();
+    dirs.push(PathBuf::from(data_home()).join(subdir));
+    dirs
+}
+
+fn resolve_path(base: &Path, value: &str) -> PathBuf {
+    let path = PathBuf::from(value);
+    if path.is_absolute() {
+        path
+    } else {
+        base.join(path)
+    }
+}
+
+struct MenuLoader {
+    root_file: PathBuf,
+    loaded_files: Vec<PathBuf>,
+}
+
+impl MenuLoader {
+    fn load_file(&mut self, path: &Path) -> Result<XmlElement, Box<dyn std::error::Error>> {
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if self.loaded_files.contains(&canonical) {
+            return Err(format!("{} is merged more than once", path.display()).into());
+        }
+        self.loaded_files.push(canonical);
+
+        let source = fs::read_to_string(path)?;
+        let mut root = parse_xml(&source).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if root.name != "Menu" {
+            return Err(format!("{}: root element is <{}>, not <Menu>", path.display(), root.name).into());
+        }
+        let base = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+        self.expand(&mut root, &base, path)?;
+        Ok(root)
+    }
+
+    // Replaces merge and default-directory elements with their content, in place
+    fn expand(&mut self, menu: &mut XmlElement, base: &Path, file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut expanded: Vec<XmlNode> = Vec::new();
+        for child in std::mem::take(&mut menu.children) {
+            let XmlNode::Element(mut element) = child else {
+                continue;
+            };
+            match element.name.as_str() {
+                "Menu" => {
+                    self.expand(&mut element, base, file)?;
+                    expanded.push(XmlNode::Element(element));
+                }
+                "AppDir" | "DirectoryDir" => {
+                    let path = resolve_path(base, &element.text());
+                    expanded.push(XmlNode::Element(XmlElement::with_text(
+                        &element.name,
+                        &path.to_string_lossy(),
+                    )));
+                }
+                "DefaultAppDirs" => {
+                    for dir in default_dirs("applications") {
+                        expanded.push(XmlNode::Element(XmlElement::with_text("AppDir", &dir.to_string_lossy())));
+                    }
+                }
+                "DefaultDirectoryDirs" => {
+                    for dir in default_dirs("desktop-directories") {
+                        expanded.push(XmlNode::Element(XmlElement::with_text(
+                            "DirectoryDir",
+                            &dir.to_string_lossy(),
+                        )));
+                    }
+                }
+                "MergeFile" => {
+                    let target = if element.attribute("type") == Some("parent") {
+                        self.parent_menu_file(file)
+                    } else {
+                        Some(resolve_path(base, &element.text()))
+                    };
+                    if let Some(target) = target.filter(|target| target.is_file()) {
+                        expanded.extend(self.merged_children(&target));
+                    }
+                }
+                "MergeDir" => {
+                    let dir = resolve_path(base, &element.text());
+                    expanded.extend(self.merge_dir(&dir));
+                }
+                "DefaultMergeDirs" => {
+                    // Least important first, so the user's merge files are applied last
+                    let name = merge_dir_name(&self.root_file);
+                    for dir in menu_config_dirs().into_iter().rev() {
+                        expanded.extend(self.merge_dir(&dir.join(&name)));
+                    }
+                }
+                _ => expanded.push(XmlNode::Element(element)),
+            }
+        }
+        menu.children = expanded;
+        Ok(())
+    }
+
+    fn merge_dir(&mut self, dir: &Path) -> Vec<XmlNode> {
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut files: Vec<PathBuf> = read_dir
+            .flatten()
+            .map(|item| item.path())
+            .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("menu"))
+            .collect();
+        files.sort();
+        files.iter().flat_map(|file| self.merged_children(file)).collect()
+    }
+
+    // The children of a merged file's root <Menu> take the place of the merge element
+    fn merged_children(&mut self, file: &Path) -> Vec<XmlNode> {
+        match self.load_file(file) {
+            Ok(root) => root
+                .children
+                .into_iter()
+                .filter(|child| !matches!(child, XmlNode::Element(element) if element.name == "Name"))
+                .collect(),
+            Err(e) => {
+                eprintln!("Skipping menu file: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    // <MergeFile type="parent"> loads the same menu file from the next less important config dir
+    fn parent_menu_file(&self, file: &Path) -> Option<PathBuf> {
+        let dirs = menu_config_dirs();
+        let file_name = file.file_name()?;
+        let position = dirs.iter().position(|dir| file.starts_with(dir))?;
+        dirs[position + 1..]
+            .iter()
+            .map(|dir| dir.join(file_name))
+            .find(|candidate| candidate.is_file())
+    }
+}
+
+// Folds later <Menu> elements with the same name into the first one, recursively,
+// then applies <Move> operations.
+fn consolidate(menu: &mut XmlElement) {
+    let mut merged: Vec<XmlNode> = Vec::new();
+    for child in std::mem::take(&mut menu.children) {
+        if let XmlNode::Element(element) = &child {
+            if element.name == "Menu" {
+                let name = menu_name(element);
+                if let Some(XmlNode::Element(existing)) = merged.iter_mut().find(|node| {
+                    matches!(node, XmlNode::Element(other) if other.name == "Menu" && menu_name(other) == name)
+                }) {
+                    existing.children.extend(element.children.iter().cloned());
+                    continue;
+                }
+            }
+        }
+        merged.push(child);
+    }
+    menu.children = merged;
+
+    let moves: Vec<(String, String)> = menu
+        .elements()
+        .filter(|element| element.name == "Move")
+        .filter_map(|element| {
+            let old = element.elements().find(|child| child.name == "Old")?.text();
+            let new = element.elements().find(|child| child.name == "New")?.text();
+            Some((old, new))
+        })
+        .collect();
+    for (old, new) in moves {
+        if let Some(moved) = take_menu(menu, &old) {
+            let new_path: Vec<String> = new.split('/').map(|name| name.to_string()).collect();
+            let target = ensure_menu(menu, &new_path);
+            target.children.extend(moved.children.into_iter().filter(
+                |child| !matches!(child, XmlNode::Element(element) if element.name == "Name"),
+            ));
+        }
+    }
+    if menu.elements().any(|element| element.name == "Move") {
+        menu.children
+            .retain(|child| !matches!(child, XmlNode::Element(element) if element.name == "Move"));
+        consolidate(menu);
+        return;
+    }
+
+    for child in menu.children.iter_mut() {
+        if let XmlNode::Element(element) = child {
+            if element.name == "Menu" {
+                consolidate(element);
+            }
+        }
+    }
+}
+
+fn menu_name(menu: &XmlElement) -> String {
+    menu.elements()
+        .filter(|child| child.name == "Name")
+        .last()
+        .map(|child| child.text())
+        .unwrap_or_default()
+}
+
+fn take_menu(menu: &mut XmlElement, path: &str) -> Option<XmlElement> {
+    let (first, rest) = match path.split_once('/') {
+        Some((first, rest)) => (first, Some(rest)),
+        None => (path, None),
+    };
+    let index = menu.children.iter().position(
+        |child| matches!(child, XmlNode::Element(element) if element.name == "Menu" && menu_name(element) == first),
+    )?;
+    match rest.filter(|rest| !rest.is_empty()) {
+        Some(rest) => match &mut menu.children[index] {
+            XmlNode::Element(child) => take_menu(child, rest),
+            XmlNode::Text(_) => None,
+        },
+        None => match menu.children.remove(index) {
+            XmlNode::Element(element) => Some(element),
+            XmlNode::Text(_) => None,
+        },
+    }
+}
+
+// Finds the submenu at a path of names below `menu`, creating missing levels
+fn ensure_menu<'a>(menu: &'a mut XmlElement, path: &[String]) -> &'a mut XmlElement {
+    let Some((first, rest)) = path.split_first() else {
+        return menu;
+    };
+    let index = match menu.children.iter().position(
+        |child| matches!(child, XmlNode::Element(element) if element.name == "Menu" && &menu_name(element) == first),
+    ) {
+        Some(index) => index,
+        None => {
+            let mut created = XmlElement::new("Menu");
+            created
+                .children
+                .push(XmlNode::Element(XmlElement::with_text("Name", first)));
+            menu.children.push(XmlNode::Element(created));
+            menu.children.len() - 1
+        }
+    };
+    let XmlNode::Element(child) = &mut menu.children[index] else {
+        unreachable!("menu children found by position are elements");
+    };
+    ensure_menu(child, rest)
+}
+
+pub fn load_menu_tree() -> Result<MenuTree, Box<dyn std::error::Error>> {
+    let source = find_root_menu().ok_or("No applications.menu found in the XDG config directories")?;
+    let mut loader = MenuLoader {
+        root_file: source.clone(),
+        loaded_files: Vec::new(),
+    };
+    let mut root = loader.load_file(&source)?;
+    consolidate(&mut root);
+
+    let mut raw = RawMenu::from_element(&root);
+    let mut pools = EntryPool {
+        loaded: HashMap::new(),
+    };
+    let mut allocated = HashSet::new();
+    allocate(&mut raw, &[], &mut pools, &mut allocated, false);
+    allocate(&mut raw, &[], &mut pools, &mut allocated, true);
+
+    let mut hidden_menus = Vec::new();
+    let root_node = build_node(&raw, Vec::new(), &[], None, &mut hidden_menus);
+
+    let source_guessed = source.file_name().and_then(|name| name.to_str()) != Some(menu_file_name().as_str());
+    Ok(MenuTree {
+        source,
+        source_guessed,
+        merged_files: loader.loaded_files,
+        root: root_node,
+        hidden_menus,
+    })
+}
+
+// Menu changes made in this app live in one merge file of their own, so they
+// can be reviewed or removed without touching the system menus or the entries.
+pub struct MenuEdits {
+    pub path: PathBuf,
+    root: XmlElement,
+}
+
+impl MenuEdits {
+    pub fn load(tree: &MenuTree) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = PathBuf::from(config_home())
+            .join("menus")
+            .join(merge_dir_name(&tree.source))
+            .join(format!("{}.menu", crate::CONFIG_DIR_NAME));
+        Self::open(path, tree, false)
+    }
+
+    // The user's own copy of the root menu file. A new one starts by merging the system file
+    // it replaces, so rules added here come after everything the system file (and its
+    // <DefaultMergeDirs/>) says, and win over a system <Include> the merge file cannot undo.
+    pub fn load_user_menu(tree: &MenuTree) -> Result<Self, Box<dyn std::error::Error>> {
+        let file_name = tree.source.file_name().ok_or("The root menu file has no name")?;
+        let path = PathBuf::from(config_home()).join("menus").join(file_name);
+        Self::open(path, tree, true)
+    }
+
+    fn open(path: PathBuf, tree: &MenuTree, merge_parent: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let root = if path.exists() {
+            let source = fs::read_to_string(&path)?;
+            parse_xml(&source).map_err(|e| format!("{}: {}", path.display(), e))?
+        } else {
+            let mut root = XmlElement::new("Menu");
+            root.children
+                .push(XmlNode::Element(XmlElement::with_text("Name", &tree.root.name)));
+            if merge_parent {
+                let mut merge = XmlElement::new("MergeFile");
+                merge.attributes.push(("type".to_string(), "parent".to_string()));
+                root.children.push(XmlNode::Element(merge));
+            }
+            root
+        };
+
+        Ok(MenuEdits { path, root })
+    }
+
+    pub fn add_submenu(&mut self, parent: &[String], name: &str, directory_file: &str) {
+        let mut path = parent.to_vec();
+        path.push(name.to_string());
+        let menu = ensure_menu(&mut self.root, &path);
+        let directory_dir = PathBuf::from(data_home()).join("desktop-directories");
+        menu.children.push(XmlNode::Element(XmlElement::with_text(
+            "DirectoryDir",
+            &directory_dir.to_string_lossy(),
+        )));
+        menu.children
+            .push(XmlNode::Element(XmlElement::with_text("Directory", directory_file)));
+
+        // Keep new submenus visible while they are still empty, so entries can be dropped on them
+        let mut layout = XmlElement::new("Layout");
+        layout.attributes.push(("show_empty".to_string(), "true".to_string()));
+        menu.children.push(XmlNode::Element(layout));
+    }
+
+    pub fn move_entry(&mut self, desktop_id: &str, from: &[String], to: &[String]) {
+        if from == to {
+            return;
+        }
+        for (path, rule) in [(to, "Include"), (from, "Exclude")] {
+            let menu = ensure_menu(&mut self.root, path);
+            remove_filename_rules(menu, desktop_id);
+            let mut element = XmlElement::new(rule);
+            element
+                .children
+                .push(XmlNode::Element(XmlElement::with_text("Filename", desktop_id)));
+            menu.children.push(XmlNode::Element(element));
+        }
+    }
+
+    pub fn exclude_entry(&mut self, desktop_id: &str, from: &[String]) {
+        let menu = ensure_menu(&mut self.root, from);
+        remove_filename_rules(menu, desktop_id);
+        let mut element = XmlElement::new("Exclude");
+        element
+            .children
+            .push(XmlNode::Element(XmlElement::with_text("Filename", desktop_id)));
+        menu.children.push(XmlNode::Element(element));
+    }
+
+    // Drops rules for an entry that "Override in User Menu" left in the user's root menu file.
+    // That file is read after the merge file, so an old <Exclude> there would hide the entry
+    // wherever a later move puts it. Files that do not merge the system menu are the user's own.
+    pub fn clear_user_menu_rules(tree: &MenuTree, desktop_id: &str) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+        let mut edits = Self::load_user_menu(tree)?;
+        let merges_parent = edits
+            .root
+            .elements()
+            .any(|element| element.name == "MergeFile" && element.attribute("type") == Some("parent"));
+        if !edits.path.exists() || !merges_parent {
+            return Ok(None);
+        }
+        if !remove_filename_rules_recursive(&mut edits.root, desktop_id) {
+            return Ok(None);
+        }
+        edits.save()?;
+        Ok(Some(edits.path))
+    }
+
+    pub fn set_hidden(&mut self, path: &[String], hidden: bool) {
+        let menu = ensure_menu(&mut self.root, path);
+        menu.children.retain(
+            |child| !matches!(child, XmlNode::Element(element) if element.name == "Deleted" || element.name == "NotDeleted"),
+        );
+        let flag = if hidden { "Deleted" } else { "NotDeleted" };
+        menu.children.push(XmlNode::Element(XmlElement::new(flag)));
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let contents = format!(
+            "<!DOCTYPE Menu PUBLIC \"-//freedesktop//DTD Menu 1.0//EN\"\n \"http://www.freedesktop.org/standards/menu-spec/menu-1.0.dtd\">\n{}",
+            self.root.to_xml(0)
+        );
+        fs::write(&self.path, contents)?;
+        Ok(())
+    }
+}
+
+// Like remove_filename_rules for a menu and all its submenus; true when anything was removed
+fn remove_filename_rules_recursive(menu: &mut XmlElement, desktop_id: &str) -> bool {
+    let before = menu.children.len();
+    remove_filename_rules(menu, desktop_id);
+    let mut changed = menu.children.len() != before;
+    for child in menu.children.iter_mut() {
+        if let XmlNode::Element(element) = child {
+            if element.name == "Menu" {
+                changed |= remove_filename_rules_recursive(element, desktop_id);
+            }
+        }
+    }
+    changed
+}
+
+// Drops earlier <Include>/<Exclude> rules this file wrote for a single entry
+fn remove_filename_rules(menu: &mut XmlElement, desktop_id: &str) {
+    menu.children.retain(|child| {
+        let XmlNode::Element(element) = child else {
+            return true;
+        };
+        if element.name != "Include" && element.name != "Exclude" {
+            return true;
+        }
+        let mut matchers = element.elements();
+        let single = matches!(
+            (matchers.next(), matchers.next()),
+            (Some(matcher), None) if matcher.name == "Filename" && matcher.text() == desktop_id
+        );
+        !single
+    });
+}
+
+// Writes a .directory file for a new submenu and returns its file name
+pub fn create_directory_file(name: &str, icon: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let dir = PathBuf::from(data_home()).join("desktop-directories");
+    fs::create_dir_all(&dir)?;
+
+    let slug = file_name_slug(name);
+    let base = format!("{}-{}", crate::CONFIG_DIR_NAME, if slug.is_empty() { "submenu" } else { &slug });
+
+    let mut file_name = format!("{}.directory", base);
+    let mut counter = 2;
+    while dir.join(&file_name).exists() {
+        file_name = format!("{}-{}.directory", base, counter);
+        counter += 1;
+    }
+
+    let mut conf = Ini::new();
+    conf.with_section(Some("Desktop Entry"))
+        .set("Type", "Directory")
+        .set("Name", name);
+    if !icon.is_empty() {
+        conf.with_section(Some("Desktop Entry")).set("Icon", icon);
+    }
+    conf.write_to_file(dir.join(&file_name))?;
+    Ok(file_name)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    Warning,
+}
+
+impl CheckStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "ok",
+            CheckStatus::Fail => "hides it",
+            CheckStatus::Warning => "check",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VisibilityCheck {
+    pub status: CheckStatus,
+    pub message: String,
+    pub source: String,
+    pub line: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MenuExplanation {
+    pub desktop_id: Option<String>,
+    pub checks: Vec<VisibilityCheck>,
+    pub shown_in: Vec<Vec<String>>,
+}
+
+impl MenuExplanation {
+    fn check(&mut self, status: CheckStatus, message: String, source: String, line: String) {
+        self.checks.push(VisibilityCheck {
+            status,
+            message,
+            source,
+            line,
+        });
+    }
+
+    pub fn blocking(&self) -> Vec<&VisibilityCheck> {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+            .collect()
+    }
+}
+
+// Line number and text of a key inside the [Desktop Entry] group
+fn desktop_key_line(content: &str, key: &str) -> Option<(usize, String)> {
+    let mut in_group = false;
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if let Some(group) = trimmed.strip_prefix('[').and_then(|g| g.strip_suffix(']')) {
+            in_group = group == "Desktop Entry";
+            continue;
+        }
+        if !in_group {
+            continue;
+        }
+        if let Some((name, _)) = trimmed.split_once('=') {
+            if name.trim() == key {
+                return Some((index + 1, trimmed.to_string()));
+            }
+        }
+    }
+    None
+}
+
+// Desktop-file ID for a path below one of the applications directories menus read
+fn desktop_id_for_path(path: &Path) -> Option<String> {
+    default_dirs("applications").iter().rev().find_map(|dir| {
+        let relative = path.strip_prefix(dir).ok()?;
+        let parts: Vec<String> = relative
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy().to_string())
+            .collect();
+        Some(parts.join("-"))
+    })
+}
+
+// The file that provides a desktop-file ID, the most important data directory winning
+pub fn desktop_id_owner(desktop_id: &str) -> Option<PathBuf> {
+    default_dirs("applications").iter().rev().find_map(|dir| {
+        let mut files = Vec::new();
+        collect_desktop_files(dir, "", &mut files);
+        files
+            .into_iter()
+            .find(|(id, _)| id == desktop_id)
+            .map(|(_, path)| path)
+    })
+}
+
+// Goes through every reason a desktop file can be missing from the menu, in the order
+// menu implementations apply them, and records the line responsible for each.
+pub fn explain_menu_visibility(path: &Path) -> MenuExplanation {
+    let mut explanation = MenuExplanation::default();
+    let location = |line: usize| format!("{}:{}", path.display(), line);
+    let file = path.display().to_string();
+
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            explanation.check(CheckStatus::Fail, format!("The file cannot be read: {}", e), file, String::new());
+            return explanation;
+        }
+    };
+
+    match Ini::load_from_str(&content) {
+        Ok(conf) if conf.section(Some("Desktop Entry")).is_none() => {
+            let first_group = content
+                .lines()
+                .enumerate()
+                .find(|(_, line)| line.trim_start().starts_with('['))
+                .map(|(index, line)| (location(index + 1), line.trim().to_string()))
+                .unwrap_or((file.clone(), String::new()));
+            explanation.check(
+                CheckStatus::Fail,
+                String::from("There is no [Desktop Entry] group, so the file is rejected"),
+                first_group.0,
+                first_group.1,
+            );
+            return explanation;
+        }
+        Ok(_) => explanation.check(
+            CheckStatus::Pass,
+            String::from("The file parses as a desktop entry"),
+            file.clone(),
+            String::new(),
+        ),
+        Err(e) => {
+            let line_text = content.lines().nth(e.line.saturating_sub(1)).unwrap_or("").trim().to_string();
+            explanation.check(
+                CheckStatus::Fail,
+                format!("The file does not parse ({}), so it is rejected", e.msg),
+                location(e.line),
+                line_text,
+            );
+            return explanation;
+        }
+    }
+
+    let key = |key: &str| desktop_key_line(&content, key);
+    let value = |key_line: &Option<(usize, String)>| {
+        key_line
+            .as_ref()
+            .and_then(|(_, text)| text.split_once('='))
+            .map(|(_, value)| value.trim().to_string())
+            .unwrap_or_default()
+    };
+    let list = |key_line: &Option<(usize, String)>| -> Vec<String> {
+        value(key_line)
+            .split(';')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    };
+    let source_of = |key_line: &Option<(usize, String)>| {
+        key_line
+            .as_ref()
+            .map(|(line, text)| (location(*line), text.clone()))
+            .unwrap_or((file.clone(), String::new()))
+    };
+
+    // Desktop-file ID and shadowing by a more important data directory
+    let desktop_id = desktop_id_for_path(path);
+    match &desktop_id {
+        None => explanation.check(
+            CheckStatus::Fail,
+            format!(
+                "The file is not inside an applications directory menus read ({})",
+                default_dirs("applications")
+                    .iter()
+                    .rev()
+                    .map(|dir| dir.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            file.clone(),
+            String::new(),
+        ),
+        Some(id) => match desktop_id_owner(id) {
+            Some(owner) if owner != path => explanation.check(
+                CheckStatus::Fail,
+                format!("Another file with the desktop ID '{}' takes priority: {}", id, owner.display()),
+                file.clone(),
+                String::new(),
+            ),
+            _ => explanation.check(
+                CheckStatus::Pass,
+                format!("No other file shadows the desktop ID '{}'", id),
+                file.clone(),
+                String::new(),
+            ),
+        },
+    }
+    explanation.desktop_id = desktop_id.clone();
+
+    for (flag, meaning) in [("Hidden", "deleted"), ("NoDisplay", "not shown in menus")] {
+        let key_line = key(flag);
+        let (source, line) = source_of(&key_line);
+        if value(&key_line) == "true" {
+            explanation.check(CheckStatus::Fail, format!("{}=true marks the entry as {}", flag, meaning), source, line);
+        } else {
+            explanation.check(CheckStatus::Pass, format!("{} is not set", flag), source, line);
+        }
+    }
+
+    let desktops = current_desktops();
+    let desktops_label = if desktops.is_empty() {
+        String::from("XDG_CURRENT_DESKTOP is not set")
+    } else {
+        format!("XDG_CURRENT_DESKTOP is {}", desktops.join(":"))
+    };
+    let only_show_in = key("OnlyShowIn");
+    let only = list(&only_show_in);
+    if !only.is_empty() {
+        let (source, line) = source_of(&only_show_in);
+        if only.iter().any(|desktop| desktops.contains(&desktop.to_lowercase())) {
+            explanation.check(CheckStatus::Pass, format!("OnlyShowIn includes the current desktop ({})", desktops_label), source, line);
+        } else {
+            explanation.check(CheckStatus::Fail, format!("OnlyShowIn does not list the current desktop ({})", desktops_label), source, line);
+        }
+    }
+    let not_show_in = key("NotShowIn");
+    let not = list(&not_show_in);
+    if !not.is_empty() {
+        let (source, line) = source_of(&not_show_in);
+        if not.iter().any(|desktop| desktops.contains(&desktop.to_lowercase())) {
+            explanation.check(CheckStatus::Fail, format!("NotShowIn lists the current desktop ({})", desktops_label), source, line);
+        } else {
+            explanation.check(CheckStatus::Pass, format!("NotShowIn does not list the current desktop ({})", desktops_label), source, line);
+        }
+    }
+
+    let try_exec = key("TryExec");
+    let program = value(&try_exec);
+    if !program.is_empty() {
+        let (source, line) = source_of(&try_exec);
+        match find_program(&program) {
+            Some(found) => explanation.check(CheckStatus::Pass, format!("TryExec program found at {}", found.display()), source, line),
+            None => explanation.check(
+                CheckStatus::Fail,
+                format!("TryExec program '{}' is not installed or not executable", program),
+                source,
+                line,
+            ),
+        }
+    }
+
+    // Finally, whether any menu's rules pick the entry up
+    let categories_line = key("Categories");
+    let (source, line) = source_of(&categories_line);
+    let Some(id) = desktop_id else {
+        return explanation;
+    };
+    let tree = match load_menu_tree() {
+        Ok(tree) => tree,
+        Err(e) => {
+            explanation.check(CheckStatus::Warning, format!("Menu rules could not be evaluated: {}", e), source, line);
+            return explanation;
+        }
+    };
+
+    explanation.shown_in = tree
+        .root
+        .all_nodes()
+        .into_iter()
+        .filter(|node| node.contains_entry(&id))
+        .map(|node| node.path.clone())
+        .collect();
+    let hidden_matches: Vec<String> = tree
+        .hidden_menus
+        .iter()
+        .flat_map(|node| node.all_nodes())
+        .filter(|node| node.matched_ids.contains(&id))
+        .map(|node| node.path.join(" / "))
+        .collect();
+    let matched_anywhere = tree
+        .root
+        .all_nodes()
+        .iter()
+        .any(|node| node.matched_ids.contains(&id));
+
+    if matched_anywhere {
+        explanation.check(CheckStatus::Pass, format!("Menu rules in {} include it", tree.source.display()), source, line);
+    } else if !hidden_matches.is_empty() {
+        explanation.check(
+            CheckStatus::Fail,
+            format!("Only hidden or empty submenus include it: {}", hidden_matches.join(", ")),
+            source,
+            line,
+        );
+    } else {
+        let message = if list(&categories_line).is_empty() {
+            format!("It has no categories and no menu in {} includes it by file name", tree.source.display())
+        } else {
+            format!("No menu in {} includes any of its categories", tree.source.display())
+        };
+        explanation.check(CheckStatus::Fail, message, source, line);
+    }
+
+    explanation
+}
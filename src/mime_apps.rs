@@ -0,0 +1,728 @@
+use ini::Ini;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::desktop_file::{application_dirs, DesktopEntry};
+use crate::mime_database::mime_database_paths;
+
+pub const DEFAULT_APPLICATIONS: &str = "Default Applications";
+pub const ADDED_ASSOCIATIONS: &str = "Added Associations";
+pub const REMOVED_ASSOCIATIONS: &str = "Removed Associations";
+const MIME_CACHE: &str = "MIME Cache";
+// The system-wide target is always written here, whatever XDG_CONFIG_DIRS lists first
+const SYSTEM_CONFIG_DIR: &str = "/etc/xdg";
+
+#[derive(Debug, Clone)]
+pub struct KeyLine {
+    pub line: usize,
+    pub text: String,
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AssociationFile {
+    pub path: String,
+    groups: HashMap<String, HashMap<String, KeyLine>>,
+}
+
+impl AssociationFile {
+    pub fn load(path: &str) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        let mut groups: HashMap<String, HashMap<String, KeyLine>> = HashMap::new();
+        let mut current_group = String::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            if let Some(group) = trimmed.strip_prefix('[').and_then(|g| g.strip_suffix(']')) {
+                current_group = group.to_string();
+                continue;
+            }
+
+            if let Some((key, value)) = trimmed.split_once('=') {
+                let values = value
+                    .split(';')
+                    .map(|v| v.trim())
+                    .filter(|v| !v.is_empty())
+                    .map(|v| v.to_string())
+                    .collect();
+                groups.entry(current_group.clone()).or_default().insert(
+                    key.trim().to_string(),
+                    KeyLine {
+                        line: index + 1,
+                        text: trimmed.to_string(),
+                        values,
+                    },
+                );
+            }
+        }
+
+        Some(AssociationFile {
+            path: path.to_string(),
+            groups,
+        })
+    }
+
+    pub fn lookup(&self, group: &str, mime_type: &str) -> Option<&KeyLine> {
+        self.groups.get(group).and_then(|keys| keys.get(mime_type))
+    }
+
+    pub fn keys(&self, group: &str) -> Vec<(&str, &KeyLine)> {
+        let mut keys: Vec<(&str, &KeyLine)> = self
+            .groups
+            .get(group)
+            .map(|keys| keys.iter().map(|(key, line)| (key.as_str(), line)).collect())
+            .unwrap_or_default();
+        keys.sort_by_key(|(_, key_line)| key_line.line);
+        keys
+    }
+
+    pub fn location(&self, key_line: &KeyLine) -> String {
+        format!("{}:{}", self.path, key_line.line)
+    }
+}
+
+pub fn config_home() -> String {
+    env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| format!("{}/.config", env::var("HOME").unwrap_or_default()))
+        .trim_end_matches('/')
+        .to_string()
+}
+
+pub fn config_dirs() -> Vec<String> {
+    env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| String::from("/etc/xdg"))
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| dir.trim_end_matches('/').to_string())
+        .collect()
+}
+
+pub fn data_home() -> String {
+    env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| format!("{}/.local/share", env::var("HOME").unwrap_or_default()))
+        .trim_end_matches('/')
+        .to_string()
+}
+
+pub fn data_dirs() -> Vec<String> {
+    env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| String::from("/usr/local/share:/usr/share"))
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| dir.trim_end_matches('/').to_string())
+        .collect()
+}
+
+pub fn current_desktops() -> Vec<String> {
+    env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|desktop| !desktop.is_empty())
+        .map(|desktop| desktop.to_lowercase())
+        .collect()
+}
+
+pub fn user_mimeapps_list_path() -> String {
+    format!("{}/mimeapps.list", config_home())
+}
+
+// Ordered from most to least important as in the MIME Applications Associations spec:
+// in every directory the $desktop-mimeapps.list variants come before mimeapps.list.
+pub fn mimeapps_list_paths() -> Vec<String> {
+    let desktops = current_desktops();
+    let mut dirs = vec![config_home()];
+    dirs.extend(config_dirs());
+    dirs.extend(application_dirs());
+
+    let mut paths = Vec::new();
+    for dir in dirs {
+        for desktop in &desktops {
+            paths.push(format!("{}/{}-mimeapps.list", dir, desktop));
+        }
+        paths.push(format!("{}/mimeapps.list", dir));
+    }
+    paths
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MimeAppsTarget {
+    Desktop,
+    User,
+    SystemDesktop,
+    System,
+}
+
+impl MimeAppsTarget {
+    pub fn path(&self) -> Option<String> {
+        match self {
+            MimeAppsTarget::Desktop => current_desktops()
+                .first()
+                .map(|desktop| format!("{}/{}-mimeapps.list", config_home(), desktop)),
+            MimeAppsTarget::User => Some(user_mimeapps_list_path()),
+            MimeAppsTarget::SystemDesktop => current_desktops()
+                .first()
+                .map(|desktop| format!("{}/{}-mimeapps.list", SYSTEM_CONFIG_DIR, desktop)),
+            MimeAppsTarget::System => Some(format!("{}/mimeapps.list", SYSTEM_CONFIG_DIR)),
+        }
+    }
+
+    pub fn is_system(&self) -> bool {
+        matches!(self, MimeAppsTarget::SystemDesktop | MimeAppsTarget::System)
+    }
+
+    pub fn label(&self) -> String {
+        let path = self.path().unwrap_or_default();
+        match self {
+            MimeAppsTarget::Desktop => format!("This desktop only ({})", path),
+            MimeAppsTarget::User => format!("All desktops ({})", path),
+            MimeAppsTarget::SystemDesktop => format!("System-wide, this desktop only ({})", path),
+            MimeAppsTarget::System => format!("System-wide ({})", path),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DefaultAssignment {
+    pub mime_type: String,
+    pub desktop_ids: Vec<String>,
+    pub source: String,
+    pub overridden_by: Option<String>,
+}
+
+// Every [Default Applications] line in the lookup chain, in precedence order
+pub fn default_assignments() -> Vec<DefaultAssignment> {
+    let mut winners: HashMap<String, String> = HashMap::new();
+    let mut assignments = Vec::new();
+
+    for file in load_association_files() {
+        for (mime_type, key_line) in file.keys(DEFAULT_APPLICATIONS) {
+            let source = file.location(key_line);
+            let overridden_by = winners.get(mime_type).cloned();
+            if overridden_by.is_none() {
+                winners.insert(mime_type.to_string(), source.clone());
+            }
+            assignments.push(DefaultAssignment {
+                mime_type: mime_type.to_string(),
+                desktop_ids: key_line.values.clone(),
+                source,
+                overridden_by,
+            });
+        }
+    }
+
+    assignments
+}
+
+pub fn set_default_application(
+    path: &str,
+    mime_type: &str,
+    desktop_id: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    set_default_applications(path, &[(mime_type.to_string(), desktop_id.to_string())])
+}
+
+pub fn set_default_applications(
+    path: &str,
+    defaults: &[(String, String)],
+) -> Result<(), Box<dyn std::error::Error>> {
+    update_mimeapps_list(path, |conf| {
+        for (mime_type, desktop_id) in defaults {
+            conf.with_section(Some(DEFAULT_APPLICATIONS))
+                .set(mime_type.as_str(), format!("{};", desktop_id));
+        }
+    })
+}
+
+// Makes `defaults` the whole [Default Applications] group of one file, dropping any
+// default it does not list
+pub fn replace_default_applications(
+    path: &str,
+    defaults: &[(String, String)],
+) -> Result<(), Box<dyn std::error::Error>> {
+    update_mimeapps_list(path, |conf| {
+        conf.delete(Some(DEFAULT_APPLICATIONS));
+        for (mime_type, desktop_id) in defaults {
+            conf.with_section(Some(DEFAULT_APPLICATIONS))
+                .set(mime_type.as_str(), format!("{};", desktop_id));
+        }
+    })
+}
+
+// The [Default Applications] group of a single file, first desktop ID per MIME type
+pub fn file_default_applications(path: &str) -> BTreeMap<String, String> {
+    AssociationFile::load(path)
+        .map(|file| {
+            file.keys(DEFAULT_APPLICATIONS)
+                .into_iter()
+                .filter_map(|(mime_type, key_line)| {
+                    Some((mime_type.to_string(), key_line.values.first()?.clone()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// The default that wins for every MIME type that has one in the lookup chain
+pub fn effective_defaults() -> BTreeMap<String, String> {
+    default_assignments()
+        .into_iter()
+        .filter(|assignment| assignment.overridden_by.is_none())
+        .filter_map(|assignment| {
+            let id = assignment.desktop_ids.first()?.clone();
+            Some((assignment.mime_type, id))
+        })
+        .collect()
+}
+
+pub fn set_association_removed(
+    path: &str,
+    mime_type: &str,
+    desktop_id: &str,
+    removed: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    update_mimeapps_list(path, |conf| {
+        let mut removed_ids = list_values(conf, REMOVED_ASSOCIATIONS, mime_type);
+        removed_ids.retain(|id| id != desktop_id);
+
+        if removed {
+            removed_ids.push(desktop_id.to_string());
+
+            // An explicit addition in the same file would contradict the removal
+            let mut added_ids = list_values(conf, ADDED_ASSOCIATIONS, mime_type);
+            if added_ids.iter().any(|id| id == desktop_id) {
+                added_ids.retain(|id| id != desktop_id);
+                write_list_values(conf, ADDED_ASSOCIATIONS, mime_type, &added_ids);
+            }
+        }
+
+        write_list_values(conf, REMOVED_ASSOCIATIONS, mime_type, &removed_ids);
+    })
+}
+
+// Types the application is removed from, deciding each type as the resolver does: files in
+// lookup order, where the first file that removes or adds the application wins, and a removal
+// beats an addition in the same file
+pub fn removed_associations(desktop_id: &str) -> Vec<String> {
+    let mut decided: HashMap<String, bool> = HashMap::new();
+    for file in load_association_files() {
+        for (group, removed) in [(REMOVED_ASSOCIATIONS, true), (ADDED_ASSOCIATIONS, false)] {
+            for (mime_type, key_line) in file.keys(group) {
+                if key_line.values.iter().any(|id| id == desktop_id) {
+                    decided.entry(mime_type.to_string()).or_insert(removed);
+                }
+            }
+        }
+    }
+    let mut removed: Vec<String> = decided
+        .into_iter()
+        .filter(|(_, removed)| *removed)
+        .map(|(mime_type, _)| mime_type)
+        .collect();
+    removed.sort();
+    removed
+}
+
+fn update_mimeapps_list<F: FnOnce(&mut Ini)>(
+    path: &str,
+    update: F,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut conf = if Path::new(path).exists() {
+        Ini::load_from_file(path)?
+    } else {
+        Ini::new()
+    };
+
+    update(&mut conf);
+
+    if let Some(parent) = Path::new(path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    conf.write_to_file(path)?;
+    Ok(())
+}
+
+fn list_values(conf: &Ini, section: &str, key: &str) -> Vec<String> {
+    conf.get_from(Some(section), key)
+        .unwrap_or("")
+        .split(';')
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_string())
+        .collect()
+}
+
+fn write_list_values(conf: &mut Ini, section: &str, key: &str, values: &[String]) {
+    if values.is_empty() {
+        conf.delete_from(Some(section), key);
+    } else {
+        conf.with_section(Some(section))
+            .set(key, format!("{};", values.join(";")));
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MimeHierarchy {
+    aliases: HashMap<String, String>,
+    parents: HashMap<String, Vec<String>>,
+}
+
+impl MimeHierarchy {
+    pub fn load() -> Self {
+        let mut hierarchy = MimeHierarchy::default();
+
+        for path in mime_database_paths("aliases") {
+            for (alias, canonical) in read_pairs(&path) {
+                hierarchy.aliases.entry(alias).or_insert(canonical);
+            }
+        }
+
+        for path in mime_database_paths("subclasses") {
+            for (child, parent) in read_pairs(&path) {
+                let parents = hierarchy.parents.entry(child).or_default();
+                if !parents.contains(&parent) {
+                    parents.push(parent);
+                }
+            }
+        }
+
+        hierarchy
+    }
+
+    pub fn canonical(&self, mime_type: &str) -> Option<&str> {
+        self.aliases.get(mime_type).map(|value| value.as_str())
+    }
+
+    pub fn parent_chain(&self, mime_type: &str) -> Vec<String> {
+        let mut chain: Vec<String> = Vec::new();
+        let mut queue = vec![mime_type.to_string()];
+
+        while !queue.is_empty() {
+            let current = queue.remove(0);
+            let mut parents = self.parents.get(&current).cloned().unwrap_or_default();
+
+            // Implicit parents defined by the shared-mime-info spec
+            if current.starts_with("text/") && current != "text/plain" {
+                parents.push(String::from("text/plain"));
+            }
+            if !current.starts_with("inode/")
+                && !current.starts_with("x-scheme-handler/")
+                && current != "application/octet-stream"
+            {
+                parents.push(String::from("application/octet-stream"));
+            }
+
+            for parent in parents {
+                let parent = self.canonical(&parent).map(|c| c.to_string()).unwrap_or(parent);
+                if parent != mime_type && !chain.contains(&parent) {
+                    chain.push(parent.clone());
+                    queue.push(parent);
+                }
+            }
+        }
+
+        // The catch-all types are the least specific, keep them at the end
+        for generic in ["text/plain", "application/octet-stream"] {
+            if let Some(position) = chain.iter().position(|mime| mime == generic) {
+                let value = chain.remove(position);
+                chain.push(value);
+            }
+        }
+
+        chain
+    }
+}
+
+fn read_pairs(path: &str) -> Vec<(String, String)> {
+    fs::read_to_string(path)
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| {
+                    let mut parts = line.split_whitespace();
+                    Some((parts.next()?.to_string(), parts.next()?.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Default,
+    Included,
+    Removed,
+    NotInstalled,
+    AlreadyListed,
+    DefaultOverridden,
+}
+
+impl Verdict {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Verdict::Default => "chosen as default",
+            Verdict::Included => "included",
+            Verdict::Removed => "excluded (removed association)",
+            Verdict::NotInstalled => "excluded (not installed)",
+            Verdict::AlreadyListed => "ignored (already listed)",
+            Verdict::DefaultOverridden => "ignored (default already chosen)",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolutionStep {
+    pub desktop_id: String,
+    pub mime_type: String,
+    pub verdict: Verdict,
+    pub source: String,
+    pub line: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Resolution {
+    pub mime_type: String,
+    pub alias_of: Option<String>,
+    pub parents: Vec<String>,
+    pub candidates: Vec<String>,
+    pub default: Option<String>,
+    pub default_source: Option<String>,
+    pub steps: Vec<ResolutionStep>,
+}
+
+pub fn load_association_files() -> Vec<AssociationFile> {
+    mimeapps_list_paths()
+        .iter()
+        .filter_map(|path| AssociationFile::load(path))
+        .collect()
+}
+
+pub fn load_mime_caches() -> Vec<AssociationFile> {
+    application_dirs()
+        .iter()
+        .filter_map(|dir| AssociationFile::load(&format!("{}/mimeinfo.cache", dir)))
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct SchemeHandlers {
+    pub scheme: String,
+    pub handlers: Vec<String>,
+    pub default: Option<String>,
+    pub default_source: Option<String>,
+}
+
+pub fn scheme_handlers(entries: &[DesktopEntry]) -> Vec<SchemeHandlers> {
+    let mut schemes: Vec<String> = entries
+        .iter()
+        .flat_map(|entry| entry.mime_types.iter())
+        .filter_map(|mime| mime.strip_prefix("x-scheme-handler/"))
+        .map(|scheme| scheme.to_string())
+        .collect();
+    schemes.sort();
+    schemes.dedup();
+
+    let files = load_association_files();
+    let caches = load_mime_caches();
+    let hierarchy = MimeHierarchy::default();
+
+    schemes
+        .into_iter()
+        .map(|scheme| {
+            let mime_type = format!("x-scheme-handler/{}", scheme);
+            let resolution = resolve_with_sources(&mime_type, &hierarchy, entries, &files, &caches);
+            SchemeHandlers {
+                scheme,
+                handlers: resolution.candidates,
+                default: resolution.default,
+                default_source: resolution.default_source,
+            }
+        })
+        .collect()
+}
+
+pub fn resolve_mime_type(
+    requested: &str,
+    hierarchy: &MimeHierarchy,
+    entries: &[DesktopEntry],
+) -> Resolution {
+    let files = load_association_files();
+    let caches = load_mime_caches();
+    resolve_with_sources(requested, hierarchy, entries, &files, &caches)
+}
+
+fn resolve_with_sources(
+    requested: &str,
+    hierarchy: &MimeHierarchy,
+    entries: &[DesktopEntry],
+    files: &[AssociationFile],
+    caches: &[AssociationFile],
+) -> Resolution {
+    let mime_type = hierarchy
+        .canonical(requested)
+        .unwrap_or(requested)
+        .to_string();
+    let alias_of = if mime_type != requested {
+        Some(requested.to_string())
+    } else {
+        None
+    };
+
+    let mut installed: HashMap<String, &DesktopEntry> = HashMap::new();
+    for entry in entries {
+        installed.entry(entry.desktop_id()).or_insert(entry);
+    }
+
+    let mut resolution = Resolution {
+        parents: hierarchy.parent_chain(&mime_type),
+        mime_type: mime_type.clone(),
+        alias_of,
+        ..Default::default()
+    };
+
+    let mut chain = vec![mime_type.clone()];
+    chain.extend(resolution.parents.iter().cloned());
+
+    for current in &chain {
+        let mut removed: HashSet<String> = HashSet::new();
+        let mut seen: HashSet<String> = resolution.candidates.iter().cloned().collect();
+
+        for file in files {
+            if let Some(key_line) = file.lookup(REMOVED_ASSOCIATIONS, current) {
+                for id in &key_line.values {
+                    removed.insert(id.clone());
+                    resolution.steps.push(step(id, current, Verdict::Removed, file, key_line));
+                }
+            }
+
+            if let Some(key_line) = file.lookup(DEFAULT_APPLICATIONS, current) {
+                for id in &key_line.values {
+                    let verdict = if resolution.default.is_some() {
+                        Verdict::DefaultOverridden
+                    } else if !installed.contains_key(id) {
+                        Verdict::NotInstalled
+                    } else {
+                        resolution.default = Some(id.clone());
+                        resolution.default_source = Some(file.location(key_line));
+                        Verdict::Default
+                    };
+                    resolution.steps.push(step(id, current, verdict, file, key_line));
+                }
+            }
+
+            if let Some(key_line) = file.lookup(ADDED_ASSOCIATIONS, current) {
+                for id in &key_line.values {
+                    let verdict = classify(id, &removed, &installed, &mut seen);
+                    if verdict == Verdict::Included {
+                        resolution.candidates.push(id.clone());
+                    }
+                    resolution.steps.push(step(id, current, verdict, file, key_line));
+                }
+            }
+        }
+
+        for cache in caches {
+            if let Some(key_line) = cache.lookup(MIME_CACHE, current) {
+                for id in &key_line.values {
+                    let verdict = classify(id, &removed, &installed, &mut seen);
+                    if verdict == Verdict::Included {
+                        resolution.candidates.push(id.clone());
+                    }
+                    resolution.steps.push(step(id, current, verdict, cache, key_line));
+                }
+            }
+        }
+
+        // Entries whose MimeType= lists the type but are missing from any mimeinfo.cache
+        for entry in entries {
+            if !entry.mime_types.iter().any(|mime| mime == current) {
+                continue;
+            }
+            let id = entry.desktop_id();
+            if seen.contains(&id) {
+                continue;
+            }
+            let verdict = classify(&id, &removed, &installed, &mut seen);
+            if verdict == Verdict::Included {
+                resolution.candidates.push(id.clone());
+            }
+            let (source, line) = mime_type_line(&entry.path);
+            resolution.steps.push(ResolutionStep {
+                desktop_id: id,
+                mime_type: current.clone(),
+                verdict,
+                source,
+                line,
+            });
+        }
+    }
+
+    match &resolution.default {
+        Some(default) => {
+            resolution.candidates.retain(|id| id != default);
+            resolution.candidates.insert(0, default.clone());
+        }
+        None => resolution.default = resolution.candidates.first().cloned(),
+    }
+
+    resolution
+}
+
+fn classify(
+    id: &str,
+    removed: &HashSet<String>,
+    installed: &HashMap<String, &DesktopEntry>,
+    seen: &mut HashSet<String>,
+) -> Verdict {
+    if removed.contains(id) {
+        Verdict::Removed
+    } else if !installed.contains_key(id) {
+        Verdict::NotInstalled
+    } else if !seen.insert(id.to_string()) {
+        Verdict::AlreadyListed
+    } else {
+        Verdict::Included
+    }
+}
+
+fn step(
+    id: &str,
+    mime_type: &str,
+    verdict: Verdict,
+    file: &AssociationFile,
+    key_line: &KeyLine,
+) -> ResolutionStep {
+    ResolutionStep {
+        desktop_id: id.to_string(),
+        mime_type: mime_type.to_string(),
+        verdict,
+        source: file.location(key_line),
+        line: key_line.text.clone(),
+    }
+}
+
+fn mime_type_line(path: &Path) -> (String, String) {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim_start().starts_with("MimeType=") {
+            return (
+                format!("{}:{}", path.display(), index + 1),
+                line.trim().to_string(),
+            );
+        }
+    }
+    (path.display().to_string(), String::from("MimeType="))
+}
@@ -0,0 +1,318 @@
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+const MAGIC_HEADER: &[u8] = b"MIME-Magic\0\n";
+
+// Magic with at least this priority wins over a glob match (shared-mime-info spec)
+pub const MAGIC_OVERRIDES_GLOB_PRIORITY: u32 = 80;
+
+#[derive(Debug, Clone)]
+struct MagicMatch {
+    offset: usize,
+    range: usize,
+    value: Vec<u8>,
+    mask: Option<Vec<u8>>,
+    children: Vec<MagicMatch>,
+}
+
+#[derive(Debug, Clone)]
+struct MagicRule {
+    priority: u32,
+    mime_type: String,
+    matches: Vec<MagicMatch>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MagicDatabase {
+    rules: Vec<MagicRule>,
+}
+
+impl MagicDatabase {
+    pub fn load() -> Self {
+        let mut rules: Vec<MagicRule> = Vec::new();
+
+        // Directories are listed from most to least important, so a type already
+        // defined by an earlier magic file is not overridden by a later one.
+        for path in mime_database_paths("magic") {
+            let data = match fs::read(&path) {
+                Ok(data) => data,
+                Err(_) => continue,
+            };
+
+            match parse_magic(&data) {
+                Some(parsed) => {
+                    for rule in parsed {
+                        let already_defined = rules
+                            .iter()
+                            .any(|existing| existing.mime_type == rule.mime_type);
+                        if !already_defined {
+                            rules.push(rule);
+                        }
+                    }
+                }
+                None => eprintln!("Failed to parse MIME magic file: {}", path),
+            }
+        }
+
+        rules.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+        MagicDatabase { rules }
+    }
+
+    fn max_extent(&self) -> usize {
+        fn extent(matches: &[MagicMatch]) -> usize {
+            matches
+                .iter()
+                .map(|m| (m.offset + m.range - 1 + m.value.len()).max(extent(&m.children)))
+                .max()
+                .unwrap_or(0)
+        }
+
+        self.rules
+            .iter()
+            .map(|rule| extent(&rule.matches))
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn sniff(&self, data: &[u8]) -> Option<(String, u32)> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches.iter().any(|m| magic_matches(m, data)))
+            .map(|rule| (rule.mime_type.clone(), rule.priority))
+    }
+
+    pub fn sniff_file(&self, path: &Path) -> Option<(String, u32)> {
+        let data = read_file_head(path, self.max_extent()).ok()?;
+        self.sniff(&data)
+    }
+}
+
+pub fn read_file_head(path: &Path, limit: usize) -> std::io::Result<Vec<u8>> {
+    let file = fs::File::open(path)?;
+    let mut data = Vec::new();
+    file.take(limit as u64).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// Same heuristic as shared-mime-info: control characters other than
+// whitespace in the first bytes mean the file is binary.
+pub fn looks_like_text(data: &[u8]) -> bool {
+    data.iter()
+        .take(128)
+        .all(|&byte| byte >= 0x20 || matches!(byte, b'\n' | b'\r' | b'\t' | 0x0c | 0x1b))
+}
+
+fn magic_matches(rule: &MagicMatch, data: &[u8]) -> bool {
+    let len = rule.value.len();
+    let found = (rule.offset..rule.offset + rule.range).any(|start| {
+        let Some(window) = data.get(start..start + len) else {
+            return false;
+        };
+        match &rule.mask {
+            Some(mask) => window
+                .iter()
+                .zip(&rule.value)
+                .zip(mask)
+                .all(|((byte, value), mask)| byte & mask == value & mask),
+            None => window == rule.value.as_slice(),
+        }
+    });
+
+    found && (rule.children.is_empty() || rule.children.iter().any(|child| magic_matches(child, data)))
+}
+
+fn parse_magic(data: &[u8]) -> Option<Vec<MagicRule>> {
+    let mut rest = data.strip_prefix(MAGIC_HEADER)?;
+    let mut rules = Vec::new();
+
+    while !rest.is_empty() {
+        // Section header: [priority:mime/type]\n
+        let header_end = rest.iter().position(|&byte| byte == b'\n')?;
+        let header = std::str::from_utf8(&rest[..header_end]).ok()?;
+        let header = header.strip_prefix('[')?.strip_suffix(']')?;
+        let (priority, mime_type) = header.split_once(':')?;
+        rest = &rest[header_end + 1..];
+
+        let mut flat: Vec<(usize, MagicMatch)> = Vec::new();
+        while !rest.is_empty() && rest[0] != b'[' {
+            match parse_magic_line(rest) {
+                Some((indent, magic_match, consumed)) => {
+                    flat.push((indent, magic_match));
+                    rest = &rest[consumed..];
+                }
+                None => {
+                    // Unknown line syntax: skip to the next line as the spec requires
+                    let line_end = rest.iter().position(|&byte| byte == b'\n')?;
+                    rest = &rest[line_end + 1..];
+                }
+            }
+        }
+
+        rules.push(MagicRule {
+            priority: priority.parse().unwrap_or(50),
+            mime_type: mime_type.to_string(),
+            matches: nest_magic_matches(flat),
+        });
+    }
+
+    Some(rules)
+}
+
+// Line format: [indent]>offset=<u16 length><value>[&mask][~word-size][+range]\n
+fn parse_magic_line(line: &[u8]) -> Option<(usize, MagicMatch, usize)> {
+    let mut pos = 0;
+
+    let (indent, digits) = parse_decimal(&line[pos..]);
+    pos += digits;
+    if line.get(pos) != Some(&b'>') {
+        return None;
+    }
+    pos += 1;
+
+    let (offset, digits) = parse_decimal(&line[pos..]);
+    if digits == 0 || line.get(pos + digits) != Some(&b'=') {
+        return None;
+    }
+    pos += digits + 1;
+
+    let length_bytes = line.get(pos..pos + 2)?;
+    let length = u16::from_be_bytes([length_bytes[0], length_bytes[1]]) as usize;
+    pos += 2;
+
+    let mut value = line.get(pos..pos + length)?.to_vec();
+    pos += length;
+
+    let mut mask = None;
+    let mut word_size = 1;
+    let mut range = 1;
+
+    loop {
+        match line.get(pos)? {
+            b'&' => {
+                mask = Some(line.get(pos + 1..pos + 1 + length)?.to_vec());
+                pos += 1 + length;
+            }
+            b'~' => {
+                let (size, digits) = parse_decimal(&line[pos + 1..]);
+                word_size = size;
+                pos += 1 + digits;
+            }
+            b'+' => {
+                let (size, digits) = parse_decimal(&line[pos + 1..]);
+                range = size.max(1);
+                pos += 1 + digits;
+            }
+            b'\n' => {
+                pos += 1;
+                break;
+            }
+            _ => return None,
+        }
+    }
+
+    // Multi-byte words are stored big-endian and compared in host byte order
+    if cfg!(target_endian = "little") && word_size > 1 {
+        for chunk in value.chunks_mut(word_size) {
+            chunk.reverse();
+        }
+        if let Some(mask) = mask.as_mut() {
+            for chunk in mask.chunks_mut(word_size) {
+                chunk.reverse();
+            }
+        }
+    }
+
+    Some((
+        indent,
+        MagicMatch {
+            offset,
+            range,
+            value,
+            mask,
+            children: Vec::new(),
+        },
+        pos,
+    ))
+}
+
+fn parse_decimal(data: &[u8]) -> (usize, usize) {
+    let digits = data.iter().take_while(|byte| byte.is_ascii_digit()).count();
+    let value = data[..digits]
+        .iter()
+        .fold(0usize, |acc, byte| acc.saturating_mul(10).saturating_add((byte - b'0') as usize));
+    (value, digits)
+}
+
+fn nest_magic_matches(flat: Vec<(usize, MagicMatch)>) -> Vec<MagicMatch> {
+    // Stack of (indent, match) for the current chain of open parents
+    let mut roots: Vec<MagicMatch> = Vec::new();
+    let mut stack: Vec<(usize, MagicMatch)> = Vec::new();
+
+    for (indent, magic_match) in flat {
+        while let Some((top_indent, _)) = stack.last() {
+            if *top_indent < indent {
+                break;
+            }
+            let (_, finished) = stack.pop().unwrap();
+            attach_magic_match(&mut stack, &mut roots, finished);
+        }
+        stack.push((indent, magic_match));
+    }
+
+    while let Some((_, finished)) = stack.pop() {
+        attach_magic_match(&mut stack, &mut roots, finished);
+    }
+
+    roots
+}
+
+fn attach_magic_match(
+    stack: &mut [(usize, MagicMatch)],
+    roots: &mut Vec<MagicMatch>,
+    finished: MagicMatch,
+) {
+    match stack.last_mut() {
+        Some((_, parent)) => parent.children.push(finished),
+        None => roots.push(finished),
+    }
+}
+
+pub fn mime_database_paths(file_name: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+
+    if let Ok(xdg_data_home) = env::var("XDG_DATA_HOME") {
+        if !xdg_data_home.is_empty() {
+            push_unique_path(&mut paths, format!(
+                "{}/mime/{}",
+                xdg_data_home.trim_end_matches('/'),
+                file_name
+            ));
+        }
+    }
+
+    if let Ok(home) = env::var("HOME") {
+        push_unique_path(&mut paths, format!("{}/.local/share/mime/{}", home, file_name));
+    }
+
+    if let Ok(xdg_dirs) = env::var("XDG_DATA_DIRS") {
+        for dir in xdg_dirs.split(':') {
+            let trimmed = dir.trim();
+            if !trimmed.is_empty() {
+                push_unique_path(&mut paths, format!("{}/mime/{}", trimmed.trim_end_matches('/'), file_name));
+            }
+        }
+    }
+
+    push_unique_path(&mut paths, format!("/usr/local/share/mime/{}", file_name));
+    push_unique_path(&mut paths, format!("/usr/share/mime/{}", file_name));
+
+    paths
+}
+
+fn push_unique_path(paths: &mut Vec<String>, candidate: String) {
+    if !paths.contains(&candidate) {
+        paths.push(candidate);
+    }
+}
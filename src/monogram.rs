@@ -0,0 +1,173 @@
+use gtk4::cairo::{Context, Format, ImageSurface};
+use gtk4::gdk_pixbuf::{Colorspace, Pixbuf};
+use gtk4::{glib, pango};
+use std::f64::consts::PI;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::icon_theme::{check_icon_name, update_icon_cache, user_hicolor_dir, user_icon_path, HICOLOR_APP_SIZES};
+
+// Up to two initials: the first letters of the first two words of the name
+pub fn monogram_initials(name: &str) -> String {
+    let initials: String = name
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '_' || c == '.')
+        .filter_map(|word| word.chars().find(|c| c.is_alphanumeric()))
+        .take(2)
+        .flat_map(|c| c.to_uppercase())
+        .collect();
+
+    if initials.is_empty() {
+        String::from("?")
+    } else {
+        initials
+    }
+}
+
+// A stable hue per name (FNV-1a), with fixed saturation and lightness so white text stays readable
+pub fn monogram_color(name: &str) -> (f64, f64, f64) {
+    let hash = name
+        .bytes()
+        .fold(0x811c9dc5u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193));
+    hsl_to_rgb((hash % 360) as f64, 0.55, 0.42)
+}
+
+fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> (f64, f64, f64) {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = lightness - chroma / 2.0;
+    let (r, g, b) = match hue as u32 {
+        0..=59 => (chroma, x, 0.0),
+        60..=119 => (x, chroma, 0.0),
+        120..=179 => (0.0, chroma, x),
+        180..=239 => (0.0, x, chroma),
+        240..=299 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    (r + m, g + m, b + m)
+}
+
+fn rounded_square(cr: &Context, size: f64) {
+    let margin = size / 16.0;
+    let radius = size * 0.22;
+    let (left, top, right, bottom) = (margin, margin, size - margin, size - margin);
+    cr.new_sub_path();
+    cr.arc(right - radius, top + radius, radius, -PI / 2.0, 0.0);
+    cr.arc(right - radius, bottom - radius, radius, 0.0, PI / 2.0);
+    cr.arc(left + radius, bottom - radius, radius, PI / 2.0, PI);
+    cr.arc(left + radius, top + radius, radius, PI, 3.0 * PI / 2.0);
+    cr.close_path();
+}
+
+pub fn render_monogram(initials: &str, color: (f64, f64, f64), size: u32) -> Result<Pixbuf, Box<dyn std::error::Error>> {
+    let surface = ImageSurface::create(Format::ARgb32, size as i32, size as i32)?;
+    {
+        let cr = Context::new(&surface)?;
+        let side = size as f64;
+
+        rounded_square(&cr, side);
+        cr.set_source_rgb(color.0, color.1, color.2);
+        cr.fill()?;
+
+        // Pango picks fallback fonts and shapes the text, so initials from any script render
+        let layout = pangocairo::functions::create_layout(&cr);
+        let mut font = pango::FontDescription::from_string("Sans Bold");
+        let font_size = side * if initials.chars().count() > 1 { 0.38 } else { 0.48 };
+        font.set_absolute_size(font_size * pango::SCALE as f64);
+        layout.set_font_description(Some(&font));
+        layout.set_text(initials);
+
+        // Centre the drawn glyphs rather than the line box, which includes ascent and descent
+        let (ink, _) = layout.extents();
+        let scale = pango::SCALE as f64;
+        cr.move_to(
+            side / 2.0 - (ink.x() as f64 + ink.width() as f64 / 2.0) / scale,
+            side / 2.0 - (ink.y() as f64 + ink.height() as f64 / 2.0) / scale,
+        );
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        pangocairo::functions::show_layout(&cr, &layout);
+    }
+    surface_to_pixbuf(surface)
+}
+
+// Cairo stores premultiplied native-endian ARGB; GdkPixbuf wants straight RGBA bytes
+fn surface_to_pixbuf(mut surface: ImageSurface) -> Result<Pixbuf, Box<dyn std::error::Error>> {
+    surface.flush();
+    let (width, height, stride) = (surface.width(), surface.height(), surface.stride() as usize);
+    let data = surface.data()?;
+
+    let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+    for row in data.chunks(stride).take(height as usize) {
+        for pixel in row[..width as usize * 4].chunks(4) {
+            let argb = u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+            let alpha = (argb >> 24) & 0xff;
+            let unpremultiply = |channel: u32| {
+                (channel * 255 + alpha / 2)
+                    .checked_div(alpha)
+                    .map_or(0, |value| value.min(255) as u8)
+            };
+            rgba.push(unpremultiply((argb >> 16) & 0xff));
+            rgba.push(unpremultiply((argb >> 8) & 0xff));
+            rgba.push(unpremultiply(argb & 0xff));
+            rgba.push(alpha as u8);
+        }
+    }
+
+    Ok(Pixbuf::from_bytes(
+        &glib::Bytes::from_owned(rgba),
+        Colorspace::Rgb,
+        true,
+        8,
+        width,
+        height,
+        width * 4,
+    ))
+}
+
+pub fn monogram_svg(initials: &str, color: (f64, f64, f64)) -> String {
+    let to_byte = |channel: f64| (channel * 255.0).round() as u8;
+    let font_size = if initials.chars().count() > 1 { 49 } else { 61 };
+    let escaped = initials
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="128" height="128" viewBox="0 0 128 128">
+  <rect x="8" y="8" width="112" height="112" rx="28" fill="#{:02x}{:02x}{:02x}"/>
+  <text x="64" y="64" dy="0.35em" text-anchor="middle" font-family="sans-serif" font-weight="bold" font-size="{}" fill="#ffffff">{}</text>
+</svg>
+"##,
+        to_byte(color.0),
+        to_byte(color.1),
+        to_byte(color.2),
+        font_size,
+        escaped
+    )
+}
+
+// Installs a monogram for `name` into the user hicolor theme as `icon_name`
+pub fn install_monogram(name: &str, icon_name: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    check_icon_name(icon_name)?;
+
+    let initials = monogram_initials(name);
+    let color = monogram_color(name);
+    let mut written = Vec::new();
+
+    let svg_path = user_icon_path(None, icon_name, "svg");
+    if let Some(parent) = svg_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&svg_path, monogram_svg(&initials, color))?;
+    written.push(svg_path);
+
+    for size in HICOLOR_APP_SIZES {
+        let png_path = user_icon_path(Some(size), icon_name, "png");
+        if let Some(parent) = png_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        render_monogram(&initials, color, size)?.savev(&png_path, "png", &[])?;
+        written.push(png_path);
+    }
+
+    update_icon_cache(&user_hicolor_dir());
+    Ok(written)
+}
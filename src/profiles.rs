@@ -0,0 +1,140 @@
+use ini::Ini;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::mime_apps::{config_home, file_default_applications, replace_default_applications, DEFAULT_APPLICATIONS};
+
+const PROFILE_EXTENSION: &str = "list";
+
+#[derive(Debug, Clone)]
+pub struct DefaultsProfile {
+    pub name: String,
+    pub defaults: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProfileDifference {
+    pub mime_type: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+impl DefaultsProfile {
+    // Only the target file's own defaults, so applying the profile later does not copy
+    // system and desktop defaults into it
+    pub fn capture(name: &str, mimeapps_path: &str) -> Self {
+        DefaultsProfile {
+            name: name.to_string(),
+            defaults: file_default_applications(mimeapps_path),
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        profiles_dir().join(format!("{}.{}", self.name, PROFILE_EXTENSION))
+    }
+
+    // Profiles use the [Default Applications] layout of mimeapps.list so they can be shared as-is
+    pub fn load(path: &std::path::Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let conf = Ini::load_from_file(path)?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .ok_or("Invalid profile file name")?;
+
+        let defaults = conf
+            .section(Some(DEFAULT_APPLICATIONS))
+            .map(|section| {
+                section
+                    .iter()
+                    .filter_map(|(mime_type, value)| {
+                        let id = value.split(';').map(|id| id.trim()).find(|id| !id.is_empty())?;
+                        Some((mime_type.to_string(), id.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(DefaultsProfile { name, defaults })
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if !is_valid_profile_name(&self.name) {
+            return Err(format!("Invalid profile name '{}'", self.name).into());
+        }
+
+        let mut conf = Ini::new();
+        for (mime_type, desktop_id) in &self.defaults {
+            conf.with_section(Some(DEFAULT_APPLICATIONS))
+                .set(mime_type.as_str(), format!("{};", desktop_id));
+        }
+
+        fs::create_dir_all(profiles_dir())?;
+        conf.write_to_file(self.path())?;
+        Ok(())
+    }
+
+    // Replaces the file's whole [Default Applications] group, so defaults from the previous
+    // profile do not linger
+    pub fn apply(&self, mimeapps_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let defaults: Vec<(String, String)> = self
+            .defaults
+            .iter()
+            .map(|(mime_type, desktop_id)| (mime_type.clone(), desktop_id.clone()))
+            .collect();
+        replace_default_applications(mimeapps_path, &defaults)
+    }
+
+    pub fn diff(&self, other: &DefaultsProfile) -> Vec<ProfileDifference> {
+        let mime_types: BTreeSet<&String> = self.defaults.keys().chain(other.defaults.keys()).collect();
+
+        mime_types
+            .into_iter()
+            .filter_map(|mime_type| {
+                let left = self.defaults.get(mime_type).cloned();
+                let right = other.defaults.get(mime_type).cloned();
+                if left == right {
+                    None
+                } else {
+                    Some(ProfileDifference {
+                        mime_type: mime_type.clone(),
+                        left,
+                        right,
+                    })
+                }
+            })
+            .collect()
+    }
+}
+
+pub fn profiles_dir() -> PathBuf {
+    PathBuf::from(format!("{}/{}/profiles", config_home(), crate::CONFIG_DIR_NAME))
+}
+
+pub fn is_valid_profile_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && !name.starts_with('.')
+        && !name.contains(|c: char| c == '/' || c == '\\' || c.is_control())
+}
+
+pub fn list_profiles() -> Vec<DefaultsProfile> {
+    let mut profiles: Vec<DefaultsProfile> = fs::read_dir(profiles_dir())
+        .map(|read_dir| {
+            read_dir
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some(PROFILE_EXTENSION))
+                .filter_map(|path| match DefaultsProfile::load(&path) {
+                    Ok(profile) => Some(profile),
+                    Err(e) => {
+                        eprintln!("Failed to load profile {}: {}", path.display(), e);
+                        None
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    profiles.sort_by_key(|profile| profile.name.to_lowercase());
+    profiles
+}
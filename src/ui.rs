@@ -6,14 +6,45 @@ use gtk4::{
 };
 use gtk4::gio;
 use gtk4::glib::clone::Downgrade;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::env;
 
-use crate::desktop_file::{scan_desktop_files, DesktopEntry};
+use crate::categories::{
+    check_categories, describe_related, format_categories, is_main_category, parse_categories,
+    related_categories, ADDITIONAL_CATEGORIES, MAIN_CATEGORIES,
+};
+use crate::desktop_file::{
+    current_locale, file_name_slug, locale_variants, scan_desktop_files, DesktopEntry, LauncherInfo,
+};
+use crate::exec::{
+    check_program, clean_environment, command_candidates, exec_program, format_exec_environment,
+    help_summary, is_valid_variable_name, launch_command, orphan_programs, parse_exec_environment,
+    quote_argument, quote_exec_argument, rank_candidates, similar_programs, split_exec, whatis_summary,
+    CommandCandidate, ExecEnvironment, OrphanProgram, ProgramCheck, COMMON_VARIABLES,
+};
+use crate::icon_theme::{
+    import_icon, installed_themes, is_svg, orphaned_user_icons, pixel_size, strip_icon_extension,
+    update_icon_cache, user_hicolor_dir, IconResolver, OrphanedIcon, FALLBACK_THEME,
+};
+use crate::menu::{
+    create_directory_file, desktop_id_owner, explain_menu_visibility, load_menu_tree, CheckStatus, MenuEdits,
+    MenuItem, MenuNode, MenuTree,
+};
+use crate::wrappers::{format_wrappers, load_wrappers, parse_wrappers, wrappers_path, ActiveWrapper, WrapperPreset};
+use crate::mime_apps::{
+    default_assignments, effective_defaults, mimeapps_list_paths, removed_associations, resolve_mime_type,
+    scheme_handlers, set_association_removed, set_default_application, set_default_applications,
+    user_mimeapps_list_path, MimeAppsTarget, MimeHierarchy, SchemeHandlers, Verdict,
+};
+use crate::mime_database::{
+    looks_like_text, mime_database_paths, read_file_head, MagicDatabase,
+    MAGIC_OVERRIDES_GLOB_PRIORITY,
+};
+use crate::monogram::{install_monogram, monogram_color, monogram_initials, render_monogram};
+use crate::profiles::{is_valid_profile_name, list_profiles, profiles_dir, DefaultsProfile};
 
 #[derive(Clone)]
 struct MimeChoice {
@@ -35,6 +66,8 @@ impl MainWindow {
             .default_height(600)
             .build();
 
+        load_app_css();
+
         let main_box = GtkBox::new(Orientation::Vertical, 0);
         window.set_child(Some(&main_box));
 
@@ -55,11 +88,53 @@ impl MainWindow {
             .build();
         left_box.append(&search_entry);
 
-        // New Entry button
+        // New Entry button and tools menu
+        let actions_box = GtkBox::new(Orientation::Horizontal, 5);
+        actions_box.set_margin_top(5);
+        actions_box.set_margin_bottom(5);
+
         let new_entry_button = Button::with_label("+ New Entry");
-        new_entry_button.set_margin_top(5);
-        new_entry_button.set_margin_bottom(5);
-        left_box.append(&new_entry_button);
+        new_entry_button.set_hexpand(true);
+        actions_box.append(&new_entry_button);
+
+        let tools_box = GtkBox::new(Orientation::Vertical, 2);
+        let tools_popover = gtk4::Popover::new();
+        tools_popover.set_child(Some(&tools_box));
+        let tools_menu_button = gtk4::MenuButton::builder()
+            .label("Tools")
+            .popover(&tools_popover)
+            .build();
+        actions_box.append(&tools_menu_button);
+
+        let resolver_button = Button::with_label("Which app opens this file?");
+        resolver_button.add_css_class("flat");
+        tools_box.append(&resolver_button);
+
+        let defaults_button = Button::with_label("Default applications");
+        defaults_button.add_css_class("flat");
+        tools_box.append(&defaults_button);
+
+        let matrix_button = Button::with_label("Associations matrix");
+        matrix_button.add_css_class("flat");
+        tools_box.append(&matrix_button);
+
+        let profiles_button = Button::with_label("Default application profiles");
+        profiles_button.add_css_class("flat");
+        tools_box.append(&profiles_button);
+
+        let orphaned_icons_button = Button::with_label("Orphaned icons");
+        orphaned_icons_button.add_css_class("flat");
+        tools_box.append(&orphaned_icons_button);
+
+        let orphan_programs_button = Button::with_label("Orphan programs");
+        orphan_programs_button.add_css_class("flat");
+        tools_box.append(&orphan_programs_button);
+
+        let menu_explainer_button = Button::with_label("Why isn't an app in my menu?");
+        menu_explainer_button.add_css_class("flat");
+        tools_box.append(&menu_explainer_button);
+
+        left_box.append(&actions_box);
 
         let scrolled = ScrolledWindow::builder()
             .hexpand(true)
@@ -69,7 +144,23 @@ impl MainWindow {
         let list_box = ListBox::new();
         list_box.set_selection_mode(gtk4::SelectionMode::Single);
         scrolled.set_child(Some(&list_box));
-        left_box.append(&scrolled);
+
+        // Alternate view: the submenu tree as the XDG menu files lay it out
+        let menu_tree_box = GtkBox::new(Orientation::Vertical, 2);
+        let menu_scrolled = ScrolledWindow::builder()
+            .hexpand(true)
+            .vexpand(true)
+            .child(&menu_tree_box)
+            .build();
+
+        let left_stack = gtk4::Stack::new();
+        left_stack.add_titled(&scrolled, Some("list"), "List");
+        left_stack.add_titled(&menu_scrolled, Some("menu"), "Menu");
+        let left_stack_switcher = gtk4::StackSwitcher::new();
+        left_stack_switcher.set_stack(Some(&left_stack));
+        left_stack_switcher.set_halign(gtk4::Align::Center);
+        left_box.append(&left_stack_switcher);
+        left_box.append(&left_stack);
 
         paned.set_start_child(Some(&left_box));
         paned.set_resize_start_child(true);
@@ -109,10 +200,31 @@ impl MainWindow {
         let exec_browse_button = Button::with_label("Browse...");
         exec_input_box.append(&exec_entry);
         exec_input_box.append(&exec_browse_button);
+        let exec_status_label = Label::new(None);
+        exec_status_label.set_halign(gtk4::Align::Start);
+        exec_status_label.set_wrap(true);
+        exec_status_label.set_selectable(true);
+        exec_status_label.add_css_class("dim-label");
+        let exec_environment = ExecEnvironmentEditor::new(&exec_entry);
         exec_box.append(&exec_label);
         exec_box.append(&exec_input_box);
+        exec_box.append(&exec_status_label);
+        exec_box.append(&exec_environment.container);
+        let exec_wrappers = ExecWrapperEditor::new(&exec_entry);
+        exec_box.append(&exec_wrappers.container);
         editor_box.append(&exec_box);
 
+        // TryExec field
+        let try_exec_box = GtkBox::new(Orientation::Vertical, 5);
+        let try_exec_label = Label::new(Some("Required program (TryExec):"));
+        try_exec_label.set_halign(gtk4::Align::Start);
+        let try_exec_entry = Entry::builder()
+            .placeholder_text("Optional; the entry is hidden when this program is missing")
+            .build();
+        try_exec_box.append(&try_exec_label);
+        try_exec_box.append(&try_exec_entry);
+        editor_box.append(&try_exec_box);
+
         // Icon field with preview
         let icon_box = GtkBox::new(Orientation::Vertical, 5);
         let icon_label = Label::new(Some("Icon:"));
@@ -131,11 +243,36 @@ impl MainWindow {
         let icon_input_box = GtkBox::new(Orientation::Horizontal, 5);
         let icon_entry = Entry::new();
         icon_entry.set_hexpand(true);
+        let icon_pick_button = Button::with_label("Theme Icons...");
         let icon_browse_button = Button::with_label("Browse...");
+        let icon_import_button = Button::with_label("Import...");
+        let icon_generate_button = Button::with_label("Generate");
+        let icon_sizes_button = Button::with_label("Sizes...");
+        icon_sizes_button.set_tooltip_text(Some("Preview every launcher size at scale 1 and 2"));
+        icon_import_button.set_tooltip_text(Some(
+            "Copy an image into your hicolor icon theme and use it by name",
+        ));
+        icon_generate_button.set_tooltip_text(Some(
+            "Create a monogram icon from the entry name in your hicolor icon theme",
+        ));
         icon_input_box.append(&icon_entry);
+        icon_input_box.append(&icon_pick_button);
         icon_input_box.append(&icon_browse_button);
         icon_input_container.append(&icon_input_box);
 
+        let icon_actions_box = GtkBox::new(Orientation::Horizontal, 5);
+        icon_actions_box.append(&icon_import_button);
+        icon_actions_box.append(&icon_generate_button);
+        icon_actions_box.append(&icon_sizes_button);
+        icon_input_container.append(&icon_actions_box);
+
+        let icon_status_label = Label::new(None);
+        icon_status_label.set_halign(gtk4::Align::Start);
+        icon_status_label.set_wrap(true);
+        icon_status_label.set_selectable(true);
+        icon_status_label.add_css_class("dim-label");
+        icon_input_container.append(&icon_status_label);
+
         icon_main_box.append(&icon_input_container);
 
         icon_box.append(&icon_label);
@@ -155,9 +292,20 @@ impl MainWindow {
         let categories_box = GtkBox::new(Orientation::Vertical, 5);
         let categories_label = Label::new(Some("Categories:"));
         categories_label.set_halign(gtk4::Align::Start);
+        let categories_input_box = GtkBox::new(Orientation::Horizontal, 5);
         let categories_entry = Entry::new();
+        categories_entry.set_hexpand(true);
+        let categories_choose_button = Button::with_label("Choose...");
+        categories_input_box.append(&categories_entry);
+        categories_input_box.append(&categories_choose_button);
+        let categories_status_label = Label::new(None);
+        categories_status_label.set_halign(gtk4::Align::Start);
+        categories_status_label.set_wrap(true);
+        categories_status_label.add_css_class("warning");
+        categories_status_label.set_visible(false);
         categories_box.append(&categories_label);
-        categories_box.append(&categories_entry);
+        categories_box.append(&categories_input_box);
+        categories_box.append(&categories_status_label);
         editor_box.append(&categories_box);
 
         // File association management
@@ -225,6 +373,15 @@ impl MainWindow {
         button_box.append(&save_button);
         button_box.append(&delete_button);
         button_box.append(&refresh_button);
+        let menu_visibility_button = Button::with_label("Menu Visibility...");
+        menu_visibility_button.set_tooltip_text(Some("Explain whether and where this entry appears in the menu"));
+        button_box.append(&menu_visibility_button);
+        let launch_button = Button::with_label("Launch...");
+        launch_button.set_tooltip_text(Some("Run the command as it is in the editor and watch its output"));
+        button_box.append(&launch_button);
+        let launcher_preview_button = Button::with_label("Preview...");
+        launcher_preview_button.set_tooltip_text(Some("Show how app grids, menus and docks display this entry"));
+        button_box.append(&launcher_preview_button);
         editor_box.append(&button_box);
 
         right_box.append(&editor_box);
@@ -233,23 +390,35 @@ impl MainWindow {
         // Store current selection
         let current_entry: Rc<RefCell<Option<DesktopEntry>>> = Rc::new(RefCell::new(None));
         let current_row_widget: Rc<RefCell<Option<gtk4::Widget>>> = Rc::new(RefCell::new(None));
+        let staged_icon: Rc<RefCell<Option<StagedIcon>>> = Rc::new(RefCell::new(None));
         let mime_types_state: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(Vec::new()));
         let mime_extensions_state: Rc<RefCell<HashMap<String, String>>> =
             Rc::new(RefCell::new(HashMap::new()));
         let known_mime_choices = Rc::new(load_known_extensions());
         let known_mime_map: Rc<RefCell<HashMap<String, String>>> =
             Rc::new(RefCell::new(build_mime_extension_map(&known_mime_choices)));
+        let magic_database = Rc::new(MagicDatabase::load());
+        // Programs on PATH, in ~/.local/bin and from flatpak, for the Command checks and completion
+        let program_candidates = Rc::new(RefCell::new(command_candidates()));
+        let icon_resolver = Rc::new(IconResolver::new(&current_icon_theme_name()));
 
         // Load desktop files
         let entries = scan_desktop_files();
         let all_entries = Rc::new(RefCell::new(entries.clone()));
+        // Kept alive by its signal handlers on the Command field
+        ExecCompletion::new(
+            &exec_entry,
+            exec_wrappers.presets.clone(),
+            all_entries.clone(),
+            program_candidates.clone(),
+        );
 
         // Map to track which entry corresponds to each row widget
         let row_entry_map: Rc<RefCell<HashMap<gtk4::Widget, DesktopEntry>>> = Rc::new(RefCell::new(HashMap::new()));
 
         // Populate list
         for entry in &entries {
-            let row = create_list_row(&entry.name, &entry.comment, &entry.icon);
+            let row = create_list_row(&entry.name, &entry.comment, &entry.icon, &icon_resolver);
             let widget = row.clone().upcast::<gtk4::Widget>();
             row_entry_map.borrow_mut().insert(widget, entry.clone());
             list_box.append(&row);
@@ -260,20 +429,28 @@ impl MainWindow {
         let details_label_clone = details_label.clone();
         let name_entry_clone = name_entry.clone();
         let exec_entry_clone = exec_entry.clone();
+        let try_exec_entry_clone = try_exec_entry.clone();
         let icon_entry_clone = icon_entry.clone();
         let icon_preview_clone = icon_preview.clone();
+        let icon_status_label_clone = icon_status_label.clone();
+        let icon_resolver_clone = icon_resolver.clone();
         let comment_entry_clone = comment_entry.clone();
         let categories_entry_clone = categories_entry.clone();
         let terminal_check_clone = terminal_check.clone();
+        let exec_wrappers_clone = exec_wrappers.clone();
         let path_display_clone = path_display.clone();
         let current_entry_clone = current_entry.clone();
         let current_row_widget_clone = current_row_widget.clone();
         let row_entry_map_clone = row_entry_map.clone();
         let name_entry_perm = name_entry.clone();
         let exec_entry_perm = exec_entry.clone();
+        let try_exec_entry_perm = try_exec_entry.clone();
+        let exec_environment_perm = exec_environment.container.clone();
+        let exec_wrappers_perm = exec_wrappers.container.clone();
         let icon_entry_perm = icon_entry.clone();
         let comment_entry_perm = comment_entry.clone();
         let categories_entry_perm = categories_entry.clone();
+        let categories_choose_button_perm = categories_choose_button.clone();
         let terminal_check_perm = terminal_check.clone();
         let save_button_perm = save_button.clone();
         let delete_button_perm = delete_button.clone();
@@ -285,6 +462,7 @@ impl MainWindow {
         let mime_extensions_state_clone = mime_extensions_state.clone();
         let add_mime_button_perm = add_mime_button.clone();
         let known_mime_map_clone = known_mime_map.clone();
+        let staged_icon_clone = staged_icon.clone();
 
         list_box.connect_row_selected(move |_, row| {
             if let Some(row) = row {
@@ -294,16 +472,24 @@ impl MainWindow {
                     details_label_clone.set_visible(false);
                     editor_box_clone.set_visible(true);
 
+                    staged_icon_clone.borrow_mut().take();
                     name_entry_clone.set_text(&entry.name);
                     exec_entry_clone.set_text(&entry.exec);
+                    try_exec_entry_clone.set_text(&entry.try_exec);
                     icon_entry_clone.set_text(&entry.icon);
                     comment_entry_clone.set_text(&entry.comment);
                     categories_entry_clone.set_text(&entry.categories);
                     terminal_check_clone.set_active(entry.terminal);
+                    exec_wrappers_clone.gpu_check.set_active(entry.prefers_non_default_gpu);
                     path_display_clone.set_text(&entry.path.display().to_string());
 
                     // Update icon preview
-                    update_icon_preview(&icon_preview_clone, &entry.icon);
+                    update_icon_preview(
+                        &icon_preview_clone,
+                        &icon_status_label_clone,
+                        &icon_resolver_clone,
+                        &entry.icon,
+                    );
 
                     {
                         let mut state = mime_types_state_clone.borrow_mut();
@@ -318,6 +504,7 @@ impl MainWindow {
                         }
                     }
                     clear_list_box(&mime_list_clone);
+                    set_mime_list_owner(&mime_list_clone, entry, !can_write_file(&entry.path));
                     let existing_mimes: Vec<String> = mime_types_state_clone.borrow().clone();
                     for mime in existing_mimes {
                         let extension_owned = if let Some(ext) =
@@ -343,9 +530,13 @@ impl MainWindow {
                     let can_write = can_write_file(&entry.path);
                     name_entry_perm.set_sensitive(can_write);
                     exec_entry_perm.set_sensitive(can_write);
+                    try_exec_entry_perm.set_sensitive(can_write);
+                    exec_environment_perm.set_sensitive(can_write);
+                    exec_wrappers_perm.set_sensitive(can_write);
                     icon_entry_perm.set_sensitive(can_write);
                     comment_entry_perm.set_sensitive(can_write);
                     categories_entry_perm.set_sensitive(can_write);
+                    categories_choose_button_perm.set_sensitive(can_write);
                     terminal_check_perm.set_sensitive(can_write);
                     save_button_perm.set_sensitive(can_write);
                     delete_button_perm.set_sensitive(can_write);
@@ -368,21 +559,45 @@ impl MainWindow {
         let current_row_widget_clone = current_row_widget.clone();
         let name_entry_clone = name_entry.clone();
         let exec_entry_clone = exec_entry.clone();
+        let try_exec_entry_clone = try_exec_entry.clone();
         let icon_entry_clone = icon_entry.clone();
         let comment_entry_clone = comment_entry.clone();
         let categories_entry_clone = categories_entry.clone();
         let terminal_check_clone = terminal_check.clone();
+        let exec_wrappers_clone = exec_wrappers.clone();
         let mime_types_state_clone = mime_types_state.clone();
         let mime_extensions_state_clone = mime_extensions_state.clone();
+        let icon_resolver_clone = icon_resolver.clone();
+        let icon_status_label_clone = icon_status_label.clone();
+        let staged_icon_clone = staged_icon.clone();
 
         save_button.connect_clicked(move |_| {
             if let Some(ref mut entry) = *current_entry_clone.borrow_mut() {
+                // A staged icon is written to the user theme only now, and only if the entry still uses it
+                let staged = staged_icon_clone.borrow_mut().take();
+                if let Some(staged) = staged.filter(|staged| staged.icon_name == icon_entry_clone.text().as_str()) {
+                    match staged.install() {
+                        Ok(written) => {
+                            println!("Installed icon '{}' ({} files)", staged.icon_name, written.len());
+                            icon_resolver_clone.clear_cache();
+                        }
+                        Err(e) => {
+                            eprintln!("Error installing icon '{}': {}", staged.icon_name, e);
+                            icon_status_label_clone.set_text(&format!("Icon installation failed: {}", e));
+                            *staged_icon_clone.borrow_mut() = Some(staged);
+                            return;
+                        }
+                    }
+                }
+
                 entry.name = name_entry_clone.text().to_string();
                 entry.exec = exec_entry_clone.text().to_string();
+                entry.try_exec = try_exec_entry_clone.text().trim().to_string();
                 entry.icon = icon_entry_clone.text().to_string();
                 entry.comment = comment_entry_clone.text().to_string();
                 entry.categories = categories_entry_clone.text().to_string();
                 entry.terminal = terminal_check_clone.is_active();
+                entry.prefers_non_default_gpu = exec_wrappers_clone.gpu_check.is_active();
                 entry.mime_types = mime_types_state_clone.borrow().clone();
                 entry.mime_extensions = mime_extensions_state_clone.borrow().clone();
 
@@ -390,7 +605,7 @@ impl MainWindow {
                     Ok(_) => {
                         println!("Changes saved successfully!");
                         // Update the icon in the list after saving
-                        update_row_icon(&current_row_widget_clone, &entry.icon);
+                        update_row_icon(&current_row_widget_clone, &icon_resolver_clone, &entry.icon);
                     }
                     Err(e) => {
                         eprintln!("Error saving file: {}", e);
@@ -399,26 +614,64 @@ impl MainWindow {
             }
         });
 
+        // The menu tree is evaluated on first use and again after a refresh
+        let menu_tree_loaded = Rc::new(RefCell::new(false));
+        let menu_tree_box_clone = menu_tree_box.clone();
+        let list_box_clone = list_box.clone();
+        let row_entry_map_clone = row_entry_map.clone();
+        let icon_resolver_clone = icon_resolver.clone();
+        let menu_tree_loaded_clone = menu_tree_loaded.clone();
+        let rebuild_menu_tree: Rc<dyn Fn()> = Rc::new(move || {
+            *menu_tree_loaded_clone.borrow_mut() = true;
+            populate_menu_tree(
+                &menu_tree_box_clone,
+                &list_box_clone,
+                row_entry_map_clone.clone(),
+                icon_resolver_clone.clone(),
+            );
+        });
+
+        let menu_tree_loaded_clone = menu_tree_loaded.clone();
+        let rebuild_menu_tree_clone = rebuild_menu_tree.clone();
+        left_stack.connect_visible_child_name_notify(move |stack| {
+            if stack.visible_child_name().as_deref() == Some("menu") && !*menu_tree_loaded_clone.borrow() {
+                rebuild_menu_tree_clone();
+            }
+        });
+
         // Refresh button handler
         let list_box_clone = list_box.clone();
         let all_entries_clone = all_entries.clone();
         let row_entry_map_clone = row_entry_map.clone();
+        let icon_resolver_clone = icon_resolver.clone();
+        let left_stack_clone = left_stack.clone();
+        let menu_tree_loaded_clone = menu_tree_loaded.clone();
+        let rebuild_menu_tree_clone = rebuild_menu_tree.clone();
+        let program_candidates_clone = program_candidates.clone();
         refresh_button.connect_clicked(move |_| {
             while let Some(child) = list_box_clone.first_child() {
                 list_box_clone.remove(&child);
             }
 
             row_entry_map_clone.borrow_mut().clear();
+            icon_resolver_clone.clear_cache();
+            *program_candidates_clone.borrow_mut() = command_candidates();
 
             let entries = scan_desktop_files();
             *all_entries_clone.borrow_mut() = entries.clone();
 
             for entry in &entries {
-                let row = create_list_row(&entry.name, &entry.comment, &entry.icon);
+                let row = create_list_row(&entry.name, &entry.comment, &entry.icon, &icon_resolver_clone);
                 let widget = row.clone().upcast::<gtk4::Widget>();
                 row_entry_map_clone.borrow_mut().insert(widget, entry.clone());
                 list_box_clone.append(&row);
             }
+
+            if left_stack_clone.visible_child_name().as_deref() == Some("menu") {
+                rebuild_menu_tree_clone();
+            } else {
+                *menu_tree_loaded_clone.borrow_mut() = false;
+            }
         });
 
         // Search functionality - optimized to hide/show instead of recreating widgets
@@ -450,10 +703,12 @@ impl MainWindow {
         let details_label_clone = details_label.clone();
         let name_entry_clone = name_entry.clone();
         let exec_entry_clone = exec_entry.clone();
+        let try_exec_entry_clone = try_exec_entry.clone();
         let icon_entry_clone = icon_entry.clone();
         let comment_entry_clone = comment_entry.clone();
         let categories_entry_clone = categories_entry.clone();
         let terminal_check_clone = terminal_check.clone();
+        let exec_wrappers_clone = exec_wrappers.clone();
         let path_display_clone = path_display.clone();
         let current_entry_clone = current_entry.clone();
         let list_box_clone = list_box.clone();
@@ -461,30 +716,11 @@ impl MainWindow {
         let mime_types_state_clone = mime_types_state.clone();
         let mime_extensions_state_clone = mime_extensions_state.clone();
         let add_mime_button_clone = add_mime_button.clone();
+        let staged_icon_clone = staged_icon.clone();
 
-        new_entry_button.connect_clicked(move |_| {
-            // Create a new desktop entry
-            let home = std::env::var("HOME").unwrap_or_else(|_| String::from("/tmp"));
-            let timestamp = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
-            let filename = format!("new-application-{}.desktop", timestamp);
-            let path = std::path::PathBuf::from(format!("{}/.local/share/applications/{}", home, filename));
-
-            let new_entry = crate::desktop_file::DesktopEntry {
-                path: path.clone(),
-                name: String::from("New Application"),
-                exec: String::from(""),
-                icon: String::from("application-x-executable"),
-                comment: String::from(""),
-                terminal: false,
-                categories: String::from(""),
-                entry_type: String::from("Application"),
-                mime_types: Vec::new(),
-                mime_extensions: HashMap::new(),
-            };
-
+        // Fills the editor with an unsaved entry, also used by "Create launcher"
+        let start_new_entry: Rc<dyn Fn(DesktopEntry)> = Rc::new(move |new_entry| {
+            staged_icon_clone.borrow_mut().take();
             // Clear list selection
             list_box_clone.unselect_all();
 
@@ -494,10 +730,12 @@ impl MainWindow {
 
             name_entry_clone.set_text(&new_entry.name);
             exec_entry_clone.set_text(&new_entry.exec);
+            try_exec_entry_clone.set_text(&new_entry.try_exec);
             icon_entry_clone.set_text(&new_entry.icon);
             comment_entry_clone.set_text(&new_entry.comment);
             categories_entry_clone.set_text(&new_entry.categories);
             terminal_check_clone.set_active(new_entry.terminal);
+            exec_wrappers_clone.gpu_check.set_active(new_entry.prefers_non_default_gpu);
             path_display_clone.set_text(&new_entry.path.display().to_string());
 
             {
@@ -507,6 +745,7 @@ impl MainWindow {
                 mime_extensions_state_clone.borrow_mut().clear();
             }
             clear_list_box(&mime_list_clone);
+            set_mime_list_owner(&mime_list_clone, &new_entry, false);
             add_mime_button_clone.set_sensitive(true);
 
             *current_entry_clone.borrow_mut() = Some(new_entry);
@@ -514,6 +753,20 @@ impl MainWindow {
             println!("New entry created. Fill in the details and click Save Changes.");
         });
 
+        let start_new_entry_clone = start_new_entry.clone();
+        new_entry_button.connect_clicked(move |_| {
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            start_new_entry_clone(new_desktop_entry(
+                &format!("new-application-{}", timestamp),
+                "New Application",
+                "",
+                "application-x-executable",
+            ));
+        });
+
         // File association button handler
         let window_clone = window.clone();
         let mime_list_clone = mime_list.clone();
@@ -522,11 +775,15 @@ impl MainWindow {
         let current_entry_clone = current_entry.clone();
         let known_mime_choices_clone = known_mime_choices.clone();
         let known_mime_map_clone_2 = known_mime_map.clone();
+        let magic_database_clone = magic_database.clone();
+        let all_entries_clone = all_entries.clone();
         add_mime_button.connect_clicked(move |_| {
             show_mime_selection_dialog(
                 &window_clone,
                 known_mime_choices_clone.clone(),
                 known_mime_map_clone_2.clone(),
+                magic_database_clone.clone(),
+                all_entries_clone.clone(),
                 mime_types_state_clone.clone(),
                 mime_extensions_state_clone.clone(),
                 &mime_list_clone,
@@ -534,6 +791,127 @@ impl MainWindow {
             );
         });
 
+        // Open-with resolver tool
+        let window_clone = window.clone();
+        let all_entries_clone = all_entries.clone();
+        let magic_database_clone = magic_database.clone();
+        let tools_popover_clone = tools_popover.clone();
+        resolver_button.connect_clicked(move |_| {
+            tools_popover_clone.popdown();
+            show_open_with_resolver(
+                &window_clone,
+                all_entries_clone.clone(),
+                magic_database_clone.clone(),
+            );
+        });
+
+        // Default applications overview
+        let window_clone = window.clone();
+        let all_entries_clone = all_entries.clone();
+        let tools_popover_clone = tools_popover.clone();
+        defaults_button.connect_clicked(move |_| {
+            tools_popover_clone.popdown();
+            show_default_applications(&window_clone, all_entries_clone.clone());
+        });
+
+        // Associations matrix
+        let window_clone = window.clone();
+        let all_entries_clone = all_entries.clone();
+        let row_entry_map_clone = row_entry_map.clone();
+        let tools_popover_clone = tools_popover.clone();
+        matrix_button.connect_clicked(move |_| {
+            tools_popover_clone.popdown();
+            show_associations_matrix(
+                &window_clone,
+                all_entries_clone.clone(),
+                row_entry_map_clone.clone(),
+            );
+        });
+
+        // Default application profiles
+        let window_clone = window.clone();
+        let all_entries_clone = all_entries.clone();
+        let tools_popover_clone = tools_popover.clone();
+        profiles_button.connect_clicked(move |_| {
+            tools_popover_clone.popdown();
+            show_default_profiles(&window_clone, all_entries_clone.clone());
+        });
+
+        // Orphaned icons report
+        let window_clone = window.clone();
+        let all_entries_clone = all_entries.clone();
+        let tools_popover_clone = tools_popover.clone();
+        orphaned_icons_button.connect_clicked(move |_| {
+            tools_popover_clone.popdown();
+            show_orphaned_icons(&window_clone, all_entries_clone.clone());
+        });
+
+        // Programs without a launcher, each of which can prefill a new entry
+        let window_clone = window.clone();
+        let all_entries_clone = all_entries.clone();
+        let icon_resolver_clone = icon_resolver.clone();
+        let tools_popover_clone = tools_popover.clone();
+        orphan_programs_button.connect_clicked(move |_| {
+            tools_popover_clone.popdown();
+            show_orphan_programs(
+                &window_clone,
+                all_entries_clone.clone(),
+                icon_resolver_clone.clone(),
+                start_new_entry.clone(),
+            );
+        });
+
+        // Test launch, also from the editor fields so a typo shows up before saving
+        let window_clone = window.clone();
+        let current_entry_clone = current_entry.clone();
+        let name_entry_clone = name_entry.clone();
+        let exec_entry_clone = exec_entry.clone();
+        let icon_entry_clone = icon_entry.clone();
+        let terminal_check_clone = terminal_check.clone();
+        launch_button.connect_clicked(move |_| {
+            let Some(mut entry) = current_entry_clone.borrow().clone() else {
+                return;
+            };
+            entry.name = name_entry_clone.text().to_string();
+            entry.exec = exec_entry_clone.text().to_string();
+            entry.icon = icon_entry_clone.text().to_string();
+            entry.terminal = terminal_check_clone.is_active();
+            show_test_launch(&window_clone, entry);
+        });
+
+        // Launcher mock-ups use the editor fields, so unsaved changes are previewed too
+        let window_clone = window.clone();
+        let current_entry_clone = current_entry.clone();
+        let name_entry_clone = name_entry.clone();
+        let comment_entry_clone = comment_entry.clone();
+        let icon_entry_clone = icon_entry.clone();
+        let icon_resolver_clone = icon_resolver.clone();
+        launcher_preview_button.connect_clicked(move |_| {
+            let Some(path) = current_entry_clone.borrow().as_ref().map(|entry| entry.path.clone()) else {
+                return;
+            };
+            let mut info = LauncherInfo::from_file(&path).unwrap_or_default();
+            info.name.default = name_entry_clone.text().to_string();
+            info.comment.default = comment_entry_clone.text().to_string();
+            info.icon = icon_entry_clone.text().to_string();
+            show_launcher_preview(&window_clone, info, icon_resolver_clone.clone());
+        });
+
+        // Menu visibility explainer, for any file or for the entry being edited
+        let window_clone = window.clone();
+        let tools_popover_clone = tools_popover.clone();
+        menu_explainer_button.connect_clicked(move |_| {
+            tools_popover_clone.popdown();
+            show_menu_explainer(&window_clone, None);
+        });
+
+        let window_clone = window.clone();
+        let current_entry_clone = current_entry.clone();
+        menu_visibility_button.connect_clicked(move |_| {
+            let path = current_entry_clone.borrow().as_ref().map(|entry| entry.path.clone());
+            show_menu_explainer(&window_clone, path);
+        });
+
         // Command/Exec browse button handler
         let exec_entry_clone = exec_entry.clone();
         let window_clone = window.clone();
@@ -560,6 +938,14 @@ impl MainWindow {
             dialog.show();
         });
 
+        // Themed icon picker handler
+        let icon_entry_clone = icon_entry.clone();
+        let icon_resolver_clone = icon_resolver.clone();
+        let window_clone = window.clone();
+        icon_pick_button.connect_clicked(move |_| {
+            show_icon_picker(&window_clone, icon_entry_clone.clone(), icon_resolver_clone.clone());
+        });
+
         // Icon browse button handler
         let icon_entry_clone = icon_entry.clone();
         let window_clone = window.clone();
@@ -571,17 +957,7 @@ impl MainWindow {
                 &[("Cancel", ResponseType::Cancel), ("Open", ResponseType::Accept)],
             );
 
-            // Add image file filter
-            let filter = FileFilter::new();
-            filter.set_name(Some("Image Files"));
-            filter.add_mime_type("image/png");
-            filter.add_mime_type("image/jpeg");
-            filter.add_mime_type("image/svg+xml");
-            filter.add_pattern("*.png");
-            filter.add_pattern("*.jpg");
-            filter.add_pattern("*.jpeg");
-            filter.add_pattern("*.svg");
-            dialog.add_filter(&filter);
+            dialog.add_filter(&image_file_filter());
 
             let icon_entry_clone2 = icon_entry_clone.clone();
             dialog.connect_response(move |dialog, response| {
@@ -599,11 +975,134 @@ impl MainWindow {
             dialog.show();
         });
 
+        // Icon import button handler
+        let icon_entry_clone = icon_entry.clone();
+        let icon_status_label_clone = icon_status_label.clone();
+        let icon_resolver_clone = icon_resolver.clone();
+        let current_entry_clone = current_entry.clone();
+        let staged_icon_clone = staged_icon.clone();
+        let window_clone = window.clone();
+        icon_import_button.connect_clicked(move |_| {
+            let Some(icon_name) = current_entry_clone.borrow().as_ref().map(custom_icon_name) else {
+                icon_status_label_clone.set_text("Select or create an entry before importing an icon");
+                return;
+            };
+
+            let dialog = FileChooserDialog::new(
+                Some("Import Icon"),
+                Some(&window_clone),
+                FileChooserAction::Open,
+                &[("Cancel", ResponseType::Cancel), ("Import", ResponseType::Accept)],
+            );
+            dialog.add_filter(&image_file_filter());
+
+            let icon_entry_clone2 = icon_entry_clone.clone();
+            let icon_status_label_clone2 = icon_status_label_clone.clone();
+            let icon_resolver_clone2 = icon_resolver_clone.clone();
+            let staged_icon_clone2 = staged_icon_clone.clone();
+            let window_clone2 = window_clone.clone();
+            dialog.connect_response(move |dialog, response| {
+                if response == ResponseType::Accept {
+                    if let Some(path) = dialog.file().and_then(|file| file.path()) {
+                        if pixel_size(&path).is_none() {
+                            icon_status_label_clone2.set_text("Import failed: Unsupported image format");
+                        } else {
+                            let staged = StagedIcon {
+                                icon_name: icon_name.clone(),
+                                source: StagedIconSource::Import(path),
+                            };
+                            stage_icon(&window_clone2, staged, &staged_icon_clone2, &icon_entry_clone2, &icon_resolver_clone2);
+                        }
+                    }
+                }
+                dialog.close();
+            });
+
+            dialog.show();
+        });
+
+        // Monogram icon generator handler
+        let icon_entry_clone = icon_entry.clone();
+        let icon_status_label_clone = icon_status_label.clone();
+        let icon_resolver_clone = icon_resolver.clone();
+        let current_entry_clone = current_entry.clone();
+        let name_entry_clone = name_entry.clone();
+        let staged_icon_clone = staged_icon.clone();
+        let window_clone = window.clone();
+        icon_generate_button.connect_clicked(move |_| {
+            let Some(icon_name) = current_entry_clone.borrow().as_ref().map(custom_icon_name) else {
+                icon_status_label_clone.set_text("Select or create an entry before generating an icon");
+                return;
+            };
+
+            let name = name_entry_clone.text().trim().to_string();
+            let staged = StagedIcon {
+                source: StagedIconSource::Monogram(if name.is_empty() { icon_name.clone() } else { name }),
+                icon_name,
+            };
+            stage_icon(&window_clone, staged, &staged_icon_clone, &icon_entry_clone, &icon_resolver_clone);
+        });
+
+        // Icon size preview handler
+        let icon_entry_clone = icon_entry.clone();
+        let icon_resolver_clone = icon_resolver.clone();
+        let window_clone = window.clone();
+        icon_sizes_button.connect_clicked(move |_| {
+            let icon_name = icon_entry_clone.text().to_string();
+            if !icon_name.is_empty() {
+                show_icon_size_preview(&window_clone, &icon_name, icon_resolver_clone.clone());
+            }
+        });
+
+        // Categories picker and validation
+        let categories_entry_clone = categories_entry.clone();
+        let window_clone = window.clone();
+        categories_choose_button.connect_clicked(move |_| {
+            show_categories_picker(&window_clone, categories_entry_clone.clone());
+        });
+
+        let categories_status_label_clone = categories_status_label.clone();
+        categories_entry.connect_changed(move |entry| {
+            update_categories_status(&categories_status_label_clone, &entry.text());
+        });
+
+        // Command and TryExec resolution status
+        let update_exec: Rc<dyn Fn()> = {
+            let exec_entry = exec_entry.clone();
+            let try_exec_entry = try_exec_entry.clone();
+            let exec_status_label = exec_status_label.clone();
+            let program_candidates = program_candidates.clone();
+            Rc::new(move || {
+                update_exec_status(
+                    &exec_status_label,
+                    &exec_entry.text(),
+                    &try_exec_entry.text(),
+                    &program_candidates.borrow(),
+                );
+            })
+        };
+        let update_exec_clone = update_exec.clone();
+        exec_entry.connect_changed(move |_| update_exec_clone());
+        try_exec_entry.connect_changed(move |_| update_exec());
+
         // Icon entry change handler to update preview in real-time
         let icon_preview_clone = icon_preview.clone();
+        let icon_status_label_clone = icon_status_label.clone();
+        let icon_resolver_clone = icon_resolver.clone();
+        let staged_icon_clone = staged_icon.clone();
         icon_entry.connect_changed(move |entry| {
             let icon_name = entry.text().to_string();
-            update_icon_preview(&icon_preview_clone, &icon_name);
+            match staged_icon_clone.borrow().as_ref() {
+                Some(staged) if staged.icon_name == icon_name => {
+                    staged.show_preview(&icon_preview_clone, &icon_status_label_clone)
+                }
+                _ => update_icon_preview(
+                    &icon_preview_clone,
+                    &icon_status_label_clone,
+                    &icon_resolver_clone,
+                    &icon_name,
+                ),
+            }
         });
 
         // Delete button handler
@@ -674,33 +1173,159 @@ impl MainWindow {
     }
 }
 
-fn update_icon_preview(icon_preview: &Image, icon_name: &str) {
-    if !icon_name.is_empty() {
-        if std::path::Path::new(icon_name).exists() {
-            icon_preview.set_from_file(Some(icon_name));
-        } else {
-            icon_preview.set_icon_name(Some(icon_name));
+fn current_icon_theme_name() -> String {
+    gtk4::Settings::default()
+        .and_then(|settings| settings.gtk_icon_theme_name())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| String::from(FALLBACK_THEME))
+}
+
+enum StagedIconSource {
+    Import(std::path::PathBuf),
+    // The name the initials and colour are taken from
+    Monogram(String),
+}
+
+// An imported or generated icon that is written to the user theme only when the entry is saved
+struct StagedIcon {
+    icon_name: String,
+    source: StagedIconSource,
+}
+
+impl StagedIcon {
+    fn install(&self) -> Result<Vec<std::path::PathBuf>, Box<dyn std::error::Error>> {
+        match &self.source {
+            StagedIconSource::Import(path) => import_icon(path, &self.icon_name),
+            StagedIconSource::Monogram(name) => install_monogram(name, &self.icon_name),
         }
-    } else {
+    }
+
+    fn show_preview(&self, icon_preview: &Image, icon_status: &Label) {
+        match &self.source {
+            StagedIconSource::Import(path) => {
+                icon_preview.set_from_file(Some(path));
+                icon_status.set_text(&format!(
+                    "{} will be installed as icon '{}' when the entry is saved",
+                    path.display(),
+                    self.icon_name
+                ));
+            }
+            StagedIconSource::Monogram(name) => {
+                let scale = icon_preview.scale_factor().max(1) as u32;
+                match render_monogram(&monogram_initials(name), monogram_color(name), 64 * scale) {
+                    Ok(pixbuf) => icon_preview.set_paintable(Some(&gtk4::gdk::Texture::for_pixbuf(&pixbuf))),
+                    Err(_) => icon_preview.set_icon_name(Some("image-missing")),
+                }
+                icon_status.set_text(&format!(
+                    "A monogram will be installed as icon '{}' when the entry is saved",
+                    self.icon_name
+                ));
+            }
+        }
+    }
+}
+
+// Imported and generated icons get a name of their own, so they never replace the theme
+// icon that every other launcher of the same application shows
+fn custom_icon_name(entry: &DesktopEntry) -> String {
+    let slug = file_name_slug(entry.desktop_id().trim_end_matches(".desktop"));
+    format!("{}-custom", if slug.is_empty() { "application" } else { &slug })
+}
+
+// Puts the staged icon into the Icon field, asking first when the name already resolves
+fn stage_icon(
+    parent: &ApplicationWindow,
+    staged: StagedIcon,
+    staged_icon: &Rc<RefCell<Option<StagedIcon>>>,
+    icon_entry: &Entry,
+    resolver: &IconResolver,
+) {
+    let staged_icon = staged_icon.clone();
+    let icon_entry = icon_entry.clone();
+    let apply = move |staged: StagedIcon| {
+        let icon_name = staged.icon_name.clone();
+        *staged_icon.borrow_mut() = Some(staged);
+        // Re-set the text so the preview switches to the staged icon even when the name is unchanged
+        icon_entry.set_text("");
+        icon_entry.set_text(&icon_name);
+    };
+
+    let existing = resolver.lookup(&staged.icon_name, 48, 1).file;
+    let Some(existing) = existing else {
+        apply(staged);
+        return;
+    };
+
+    let dialog = gtk4::Dialog::builder()
+        .transient_for(parent)
+        .modal(true)
+        .title("Replace Icon?")
+        .default_width(420)
+        .build();
+    dialog.add_button("Cancel", ResponseType::Cancel);
+    dialog.add_button("Replace", ResponseType::Accept);
+
+    let content = dialog.content_area();
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    let message = Label::new(Some(&format!(
+        "An icon named '{}' already exists ({}). Saving the entry will replace it for every launcher that uses it.",
+        staged.icon_name,
+        existing.display()
+    )));
+    message.set_wrap(true);
+    message.set_xalign(0.0);
+    content.append(&message);
+
+    let staged = RefCell::new(Some(staged));
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Accept {
+            if let Some(staged) = staged.borrow_mut().take() {
+                apply(staged);
+            }
+        }
+        dialog.close();
+    });
+    dialog.present();
+}
+
+fn update_icon_preview(
+    icon_preview: &Image,
+    icon_status: &Label,
+    resolver: &IconResolver,
+    icon_name: &str,
+) {
+    if icon_name.is_empty() {
         icon_preview.set_icon_name(Some("application-x-executable"));
+        icon_status.set_text("No icon set; launchers will show a generic icon");
+        return;
+    }
+
+    let scale = icon_preview.scale_factor().max(1) as u32;
+    let lookup = resolver.lookup(icon_name, 64, scale);
+    icon_status.set_text(&lookup.describe());
+    match &lookup.file {
+        Some(file) => icon_preview.set_from_file(Some(file)),
+        None => icon_preview.set_icon_name(Some("image-missing")),
     }
 }
 
-fn update_row_icon(row_widget: &Rc<RefCell<Option<gtk4::Widget>>>, icon_name: &str) {
+fn update_row_icon(
+    row_widget: &Rc<RefCell<Option<gtk4::Widget>>>,
+    resolver: &IconResolver,
+    icon_name: &str,
+) {
     if let Some(ref widget) = *row_widget.borrow() {
         // The widget is a GtkBox (horizontal) with icon as first child
         if let Some(row_box) = widget.downcast_ref::<GtkBox>() {
             if let Some(icon_widget) = row_box.first_child() {
                 if let Some(image) = icon_widget.downcast_ref::<Image>() {
                     // Update the icon
-                    if !icon_name.is_empty() {
-                        if std::path::Path::new(icon_name).exists() {
-                            image.set_from_file(Some(icon_name));
-                        } else {
-                            image.set_icon_name(Some(icon_name));
-                        }
-                    } else {
-                        image.set_icon_name(Some("application-x-executable"));
+                    match resolver.lookup(icon_name, 48, 1).file {
+                        Some(file) if !icon_name.is_empty() => image.set_from_file(Some(file)),
+                        _ => image.set_icon_name(Some("application-x-executable")),
                     }
                 }
             }
@@ -708,602 +1333,4986 @@ fn update_row_icon(row_widget: &Rc<RefCell<Option<gtk4::Widget>>>, icon_name: &s
     }
 }
 
-fn clear_list_box(list: &ListBox) {
-    while let Some(child) = list.first_child() {
-        list.remove(&child);
+const ICON_PREVIEW_SIZES: [u32; 7] = [16, 24, 32, 48, 64, 128, 256];
+
+// Bitmaps smaller than this look blurry once launchers upscale them
+const ICON_TINY_BITMAP_SIZE: u32 = 48;
+
+fn load_app_css() {
+    let provider = gtk4::CssProvider::new();
+    provider.load_from_data(
+        ".preview-light { background-color: #fafafa; color: #242424; }
+         .preview-dark { background-color: #242424; color: #fafafa; }",
+    );
+    if let Some(display) = gtk4::gdk::Display::default() {
+        gtk4::style_context_add_provider_for_display(
+            &display,
+            &provider,
+            gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
     }
 }
 
-fn add_mime_association(
-    mime_type: &str,
-    extension: Option<&str>,
-    state: &Rc<RefCell<Vec<String>>>,
-    extensions_state: &Rc<RefCell<HashMap<String, String>>>,
-    list: &ListBox,
-) -> bool {
-    if state
-        .borrow()
-        .iter()
-        .any(|existing| existing == mime_type)
-    {
-        return false;
-    }
+fn show_icon_size_preview(parent: &ApplicationWindow, icon_name: &str, resolver: Rc<IconResolver>) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .title(format!("Icon Sizes: {}", icon_name))
+        .default_width(900)
+        .default_height(640)
+        .build();
 
-    state.borrow_mut().push(mime_type.to_string());
-    if let Some(ext) = extension {
-        extensions_state
-            .borrow_mut()
-            .insert(mime_type.to_string(), ext.to_string());
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    window.set_child(Some(&content));
+
+    let warning_label = Label::new(None);
+    warning_label.set_halign(gtk4::Align::Start);
+    warning_label.set_wrap(true);
+    warning_label.set_selectable(true);
+    content.append(&warning_label);
+
+    let largest = resolver.lookup(icon_name, 512, 1);
+    match &largest.file {
+        None => warning_label.set_text(&largest.describe()),
+        Some(file) if !is_svg(file) => match pixel_size(file) {
+            Some(size) if size < ICON_TINY_BITMAP_SIZE => {
+                warning_label.add_css_class("warning");
+                warning_label.set_text(&format!(
+                    "Only a {}px bitmap is available ({}); every larger size is upscaled and will look blurry.",
+                    size,
+                    file.display()
+                ));
+            }
+            Some(size) => warning_label.set_text(&format!("Largest bitmap: {}px ({})", size, file.display())),
+            None => warning_label.set_text(&format!("Unreadable image: {}", file.display())),
+        },
+        Some(file) => warning_label.set_text(&format!("Scalable source: {}", file.display())),
     }
 
-    let ext_for_row = extension
-        .map(|value| value.to_string())
-        .or_else(|| extensions_state.borrow().get(mime_type).cloned())
-        .filter(|value| !value.is_empty());
+    let scrolled = ScrolledWindow::builder()
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    let strips = GtkBox::new(Orientation::Vertical, 8);
+    scrolled.set_child(Some(&strips));
+    content.append(&scrolled);
 
-    append_mime_row(
-        list,
-        mime_type,
-        ext_for_row.as_deref(),
-        state.clone(),
-        extensions_state.clone(),
-    );
-    true
+    for scale in [1, 2] {
+        for (background, css_class) in [("Light", "preview-light"), ("Dark", "preview-dark")] {
+            let header = Label::new(Some(&format!("{} background, scale {}", background, scale)));
+            header.set_halign(gtk4::Align::Start);
+            header.add_css_class("heading");
+            strips.append(&header);
+
+            let strip = GtkBox::new(Orientation::Horizontal, 16);
+            strip.add_css_class(css_class);
+            strip.set_margin_bottom(4);
+            for size in ICON_PREVIEW_SIZES {
+                strip.append(&icon_size_tile(&resolver, icon_name, size, scale));
+            }
+            strips.append(&strip);
+        }
+    }
+
+    window.present();
 }
 
-fn row_string_data(row: &gtk4::ListBoxRow, key: &str) -> Option<String> {
-    unsafe { row.data::<String>(key).map(|ptr| ptr.as_ref().clone()) }
+// Renders the icon at size × scale device pixels, shown at the logical size like on a HiDPI
+// screen, labelled with where the pixels came from
+fn icon_size_tile(resolver: &IconResolver, icon_name: &str, size: u32, scale: u32) -> GtkBox {
+    let tile = GtkBox::new(Orientation::Vertical, 4);
+    tile.set_margin_top(8);
+    tile.set_margin_bottom(8);
+    tile.set_margin_start(8);
+    tile.set_valign(gtk4::Align::End);
+
+    let lookup = resolver.lookup(icon_name, size, scale);
+    let pixels = (size * scale) as i32;
+    let image = match lookup
+        .file
+        .as_ref()
+        .and_then(|file| gtk4::gdk_pixbuf::Pixbuf::from_file_at_scale(file, pixels, pixels, true).ok())
+    {
+        Some(pixbuf) => Image::from_paintable(Some(&gtk4::gdk::Texture::for_pixbuf(&pixbuf))),
+        None => Image::from_icon_name("image-missing"),
+    };
+    // GtkImage fits the texture into the pixel size, so a scale-2 tile keeps its logical size
+    image.set_pixel_size(size as i32);
+    tile.append(&image);
+
+    let caption = Label::new(Some(&format!("{}px", size)));
+    tile.append(&caption);
+
+    let source = Label::new(None);
+    source.add_css_class("caption");
+    if lookup.file.is_none() {
+        source.set_text("missing");
+        source.add_css_class("error");
+    } else if lookup.is_native(size, scale) {
+        source.set_text("asset");
+    } else {
+        let from = match (&lookup.directory, &lookup.file) {
+            (Some(directory), _) if directory.scale > 1 => format!("{}px@{}", directory.size, directory.scale),
+            (Some(directory), _) => format!("{}px", directory.size),
+            (None, Some(file)) => pixel_size(file)
+                .map(|size| format!("{}px", size))
+                .unwrap_or_else(|| String::from("?")),
+            (None, None) => String::from("?"),
+        };
+        source.set_text(&format!("scaled from {}", from));
+        source.add_css_class("warning");
+    }
+    if let Some(file) = &lookup.file {
+        source.set_tooltip_text(Some(&file.display().to_string()));
+    }
+    tile.append(&source);
+
+    tile
 }
 
-fn add_mime_from_row(
-    row: &gtk4::ListBoxRow,
-    state: &Rc<RefCell<Vec<String>>>,
-    list: &ListBox,
-    current_entry: &Rc<RefCell<Option<DesktopEntry>>>,
-    known_map: &Rc<RefCell<HashMap<String, String>>>,
-    extensions_state: &Rc<RefCell<HashMap<String, String>>>,
-) {
-    if let Some(mime_value) = row_string_data(row, "mime-type") {
-        let extension_value = row_string_data(row, "extension");
-        let extension_opt = extension_value
-            .as_ref()
-            .and_then(|value| if value.is_empty() { None } else { Some(value.as_str()) });
+fn update_exec_status(status_label: &Label, exec: &str, try_exec: &str, candidates: &[CommandCandidate]) {
+    let mut lines: Vec<String> = Vec::new();
+    let mut problem = false;
 
-        let mut map = known_map.borrow_mut();
-        if let Some(ext_str) = extension_opt {
-            map.entry(mime_value.clone()).or_insert_with(|| ext_str.to_string());
+    // The program after an env prefix, or the shell itself for sh -c wrappers
+    let environment = parse_exec_environment(exec);
+    let program = match &environment.shell {
+        Some(shell) => Ok(Some(shell.program.clone())),
+        None => split_exec(&environment.command).map(|args| args.into_iter().next()),
+    };
+    match program {
+        Err(e) => {
+            lines.push(format!("Command cannot be parsed: {}", e));
+            problem = true;
+        }
+        Ok(program) => {
+            if let Some(program) = &program {
+                match check_program(program) {
+                    ProgramCheck::Found(path) => lines.push(format!("Runs {}", path.display())),
+                    ProgramCheck::NotExecutable(path) => {
+                        lines.push(format!("{} exists but is not executable", path.display()));
+                        problem = true;
+                    }
+                    ProgramCheck::NotFound => {
+                        let mut line = if program.contains('/') {
+                            format!("{} not found", program)
+                        } else {
+                            format!("'{}' not found on PATH", program)
+                        };
+                        let suggestions = similar_programs(program, candidates);
+                        if !suggestions.is_empty() {
+                            line.push_str(&format!(". Did you mean: {}?", suggestions.join(", ")));
+                        }
+                        lines.push(line);
+                        problem = true;
+                    }
+                }
+            }
         }
+    }
 
-        let resolved_extension = extension_opt.or_else(|| map.get(&mime_value).map(|s| s.as_str()));
+    let try_exec = try_exec.trim();
+    if !try_exec.is_empty() {
+        match check_program(try_exec) {
+            ProgramCheck::Found(path) => lines.push(format!("TryExec finds {}", path.display())),
+            _ => {
+                lines.push(format!("TryExec '{}' not found: menus hide this entry", try_exec));
+                problem = true;
+            }
+        }
+    }
 
-        if add_mime_association(
-            &mime_value,
-            resolved_extension,
-            state,
-            extensions_state,
-            list,
-        ) {
-            if let Some(ref mut entry) = *current_entry.borrow_mut() {
-                entry.mime_types = state.borrow().clone();
-                entry.mime_extensions = extensions_state.borrow().clone();
+    status_label.set_text(&lines.join("\n"));
+    status_label.set_visible(!lines.is_empty());
+    if problem {
+        status_label.remove_css_class("dim-label");
+        status_label.add_css_class("error");
+    } else {
+        status_label.remove_css_class("error");
+        status_label.add_css_class("dim-label");
+    }
+}
+
+// Table of the variables set by the Command field's `env VAR=value` or `sh -c` prefix.
+// Edits rewrite the Command field; typing in the Command field reloads the table.
+struct ExecEnvironmentEditor {
+    exec_entry: Entry,
+    container: GtkBox,
+    rows_box: GtkBox,
+    rows: RefCell<Vec<(GtkBox, Entry, Entry)>>,
+    shell_label: Label,
+    status_label: Label,
+    environment: RefCell<ExecEnvironment>,
+    syncing: Cell<bool>,
+}
+
+impl ExecEnvironmentEditor {
+    fn new(exec_entry: &Entry) -> Rc<Self> {
+        let container = GtkBox::new(Orientation::Vertical, 5);
+
+        let header = GtkBox::new(Orientation::Horizontal, 5);
+        let title = Label::new(Some("Environment variables:"));
+        title.set_halign(gtk4::Align::Start);
+        title.set_hexpand(true);
+        let suggestions_box = GtkBox::new(Orientation::Vertical, 2);
+        let suggestions_popover = gtk4::Popover::new();
+        suggestions_popover.set_child(Some(&suggestions_box));
+        let suggestions_button = gtk4::MenuButton::builder()
+            .label("Common...")
+            .popover(&suggestions_popover)
+            .tooltip_text("Add a toolkit variable")
+            .build();
+        let add_button = Button::with_label("Add Variable");
+        header.append(&title);
+        header.append(&suggestions_button);
+        header.append(&add_button);
+
+        let rows_box = GtkBox::new(Orientation::Vertical, 5);
+        let shell_label = Label::new(None);
+        shell_label.set_halign(gtk4::Align::Start);
+        shell_label.add_css_class("dim-label");
+        shell_label.set_visible(false);
+        let status_label = Label::new(None);
+        status_label.set_halign(gtk4::Align::Start);
+        status_label.set_wrap(true);
+        status_label.add_css_class("error");
+        status_label.set_visible(false);
+
+        container.append(&header);
+        container.append(&rows_box);
+        container.append(&shell_label);
+        container.append(&status_label);
+
+        let editor = Rc::new(Self {
+            exec_entry: exec_entry.clone(),
+            container,
+            rows_box,
+            rows: RefCell::new(Vec::new()),
+            shell_label,
+            status_label,
+            environment: RefCell::new(parse_exec_environment("")),
+            syncing: Cell::new(false),
+        });
+
+        for (name, value, description) in COMMON_VARIABLES {
+            let label = Label::new(Some(&format!("{} — {}", name, description)));
+            label.set_xalign(0.0);
+            let button = Button::builder().child(&label).build();
+            button.add_css_class("flat");
+            button.set_tooltip_text(Some(&format!("Adds {}={}", name, value)));
+            let editor_clone = editor.clone();
+            let suggestions_popover_clone = suggestions_popover.clone();
+            button.connect_clicked(move |_| {
+                suggestions_popover_clone.popdown();
+                let existing = editor_clone
+                    .rows
+                    .borrow()
+                    .iter()
+                    .find(|(_, name_entry, _)| name_entry.text().trim() == *name)
+                    .map(|(_, _, value_entry)| value_entry.clone());
+                match existing {
+                    Some(value_entry) => {
+                        value_entry.grab_focus();
+                    }
+                    None => {
+                        editor_clone.add_row(name, value);
+                        editor_clone.write();
+                    }
+                }
+            });
+            suggestions_box.append(&button);
+        }
+
+        let editor_clone = editor.clone();
+        add_button.connect_clicked(move |_| {
+            editor_clone.add_row("", "").grab_focus();
+        });
+
+        let editor_clone = editor.clone();
+        exec_entry.connect_changed(move |_| {
+            if !editor_clone.syncing.get() {
+                editor_clone.load();
             }
+        });
 
-            let display_extension = resolved_extension.unwrap_or("-");
-            println!(
-                "Added file association '{}' ({})",
-                mime_value,
-                display_extension
-            );
-        } else {
-            println!(
-                "File association '{}' already exists",
-                mime_value
-            );
+        editor
+    }
+
+    fn load(self: &Rc<Self>) {
+        let environment = parse_exec_environment(&self.exec_entry.text());
+        for (row, _, _) in self.rows.borrow_mut().drain(..) {
+            self.rows_box.remove(&row);
+        }
+        for (name, value) in &environment.variables {
+            self.add_row(name, value);
+        }
+        match &environment.shell {
+            Some(shell) => {
+                self.shell_label
+                    .set_text(&format!("Variables are set inside the {} -c wrapper", shell.program));
+                self.shell_label.set_visible(true);
+            }
+            None => self.shell_label.set_visible(false),
         }
+        self.status_label.set_visible(false);
+        *self.environment.borrow_mut() = environment;
     }
-}
 
-fn try_add_manual_mime(
-    entry: &Entry,
-    state: &Rc<RefCell<Vec<String>>>,
-    list: &ListBox,
-    current_entry: &Rc<RefCell<Option<DesktopEntry>>>,
-    known_map: &Rc<RefCell<HashMap<String, String>>>,
-    extensions_state: &Rc<RefCell<HashMap<String, String>>>,
-) {
-    let input = entry.text().to_string();
+    fn add_row(self: &Rc<Self>, name: &str, value: &str) -> Entry {
+        let row = GtkBox::new(Orientation::Horizontal, 5);
+        let name_entry = Entry::builder().text(name).placeholder_text("NAME").width_chars(24).build();
+        let value_entry = Entry::builder().text(value).placeholder_text("value").hexpand(true).build();
+        let remove_button = Button::from_icon_name("list-remove-symbolic");
+        remove_button.add_css_class("flat");
+        remove_button.set_tooltip_text(Some("Remove this variable"));
+        row.append(&name_entry);
+        row.append(&Label::new(Some("=")));
+        row.append(&value_entry);
+        row.append(&remove_button);
+        self.rows_box.append(&row);
+
+        let editor_clone = self.clone();
+        name_entry.connect_changed(move |_| editor_clone.write());
+        let editor_clone = self.clone();
+        value_entry.connect_changed(move |_| editor_clone.write());
+        let editor_clone = self.clone();
+        let row_clone = row.clone();
+        remove_button.connect_clicked(move |_| {
+            editor_clone.rows.borrow_mut().retain(|(other, _, _)| other != &row_clone);
+            editor_clone.rows_box.remove(&row_clone);
+            editor_clone.write();
+        });
 
-    match resolve_mime_from_input(&input) {
-        Some((mime, display_hint)) => {
-            let extension_opt = if display_hint.is_empty() {
-                None
+        self.rows.borrow_mut().push((row, name_entry.clone(), value_entry));
+        name_entry
+    }
+
+    // Regenerates the Command field from the table, unless a row needs fixing first
+    fn write(&self) {
+        let mut variables: Vec<(String, String)> = Vec::new();
+        let mut problems: Vec<String> = Vec::new();
+        for (_, name_entry, value_entry) in self.rows.borrow().iter() {
+            let name = name_entry.text().trim().to_string();
+            let value = value_entry.text().to_string();
+            name_entry.remove_css_class("error");
+            if name.is_empty() && value.is_empty() {
+                continue;
+            }
+            let problem = if name.is_empty() {
+                Some(format!("The value '{}' needs a variable name", value))
+            } else if !is_valid_variable_name(&name) {
+                Some(format!(
+                    "'{}' is not a valid variable name: use letters, digits and _, not starting with a digit",
+                    name
+                ))
+            } else if variables.iter().any(|(other, _)| *other == name) {
+                Some(format!("{} is set more than once", name))
             } else {
-                Some(display_hint.as_str())
+                None
             };
+            match problem {
+                Some(problem) => {
+                    name_entry.add_css_class("error");
+                    problems.push(problem);
+                }
+                None => variables.push((name, value)),
+            }
+        }
+
+        self.status_label.set_text(&problems.join("\n"));
+        self.status_label.set_visible(!problems.is_empty());
+        if !problems.is_empty() {
+            return;
+        }
+
+        let exec = {
+            let mut environment = self.environment.borrow_mut();
+            environment.variables = variables;
+            format_exec_environment(&environment)
+        };
+        if exec != self.exec_entry.text() {
+            self.syncing.set(true);
+            self.exec_entry.set_text(&exec);
+            self.syncing.set(false);
+        }
+    }
+}
+
+// Toggles for well-known launch wrappers such as prime-run or nice, read from and written
+// back into the tokens the Command starts with (after any env prefix)
+struct ExecWrapperEditor {
+    exec_entry: Entry,
+    container: gtk4::Expander,
+    gpu_check: CheckButton,
+    presets: Vec<WrapperPreset>,
+    rows_box: GtkBox,
+    rows: Vec<(CheckButton, Option<Entry>)>,
+    status_label: Label,
+    syncing: Cell<bool>,
+}
 
-            let mut map = known_map.borrow_mut();
-            if let Some(ext_str) = extension_opt {
-                map.entry(mime.clone()).or_insert_with(|| ext_str.to_string());
+impl ExecWrapperEditor {
+    fn new(exec_entry: &Entry) -> Rc<Self> {
+        let container = gtk4::Expander::new(Some("Launch wrappers"));
+        let content = GtkBox::new(Orientation::Vertical, 5);
+        content.set_margin_top(5);
+        container.set_child(Some(&content));
+
+        let gpu_check = CheckButton::with_label("Prefer the discrete GPU (PrefersNonDefaultGPU)");
+        gpu_check.set_tooltip_text(Some(
+            "Asks the desktop to launch this app on the non-default GPU, without a wrapper program",
+        ));
+        content.append(&gpu_check);
+
+        let presets = load_wrappers();
+        let rows_box = GtkBox::new(Orientation::Vertical, 5);
+        let mut rows: Vec<(CheckButton, Option<Entry>)> = Vec::new();
+        for preset in &presets {
+            let row = GtkBox::new(Orientation::Horizontal, 5);
+            let check = CheckButton::with_label(&preset.name);
+            check.set_hexpand(true);
+            if !preset.description.is_empty() {
+                check.set_tooltip_text(Some(&preset.description));
             }
-            let resolved_extension = extension_opt
-                .or_else(|| map.get(&mime).map(|value| value.as_str()));
+            row.append(&check);
+
+            let parameter_entry = preset.has_parameter().then(|| {
+                let parameter_entry = Entry::builder()
+                    .text(preset.default_parameter.as_str())
+                    .width_chars(12)
+                    .build();
+                parameter_entry.set_tooltip_text(Some(&preset.description));
+                row.append(&parameter_entry);
+                parameter_entry
+            });
 
-            if add_mime_association(
-                &mime,
-                resolved_extension,
-                state,
-                extensions_state,
-                list,
-            ) {
-                if let Some(ref mut entry_ref) = *current_entry.borrow_mut() {
-                    entry_ref.mime_types = state.borrow().clone();
-                    entry_ref.mime_extensions = extensions_state.borrow().clone();
+            let installed_label = Label::new(None);
+            match preset.installed() {
+                Some(path) => {
+                    installed_label.set_text("installed");
+                    installed_label.set_tooltip_text(Some(&path.display().to_string()));
+                    installed_label.add_css_class("dim-label");
                 }
+                None => {
+                    installed_label.set_text(&format!("{} not installed", preset.program));
+                    installed_label.add_css_class("warning");
+                }
+            }
+            row.append(&installed_label);
 
-                println!(
-                    "Added file association '{}' ({})",
-                    mime,
-                    resolved_extension.unwrap_or("-")
-                );
-            } else {
-                println!(
-                    "File association '{}' already exists",
-                    mime
-                );
+            rows_box.append(&row);
+            rows.push((check, parameter_entry));
+        }
+        content.append(&rows_box);
+
+        let status_label = Label::new(None);
+        status_label.set_halign(gtk4::Align::Start);
+        status_label.set_wrap(true);
+        status_label.set_visible(false);
+        content.append(&status_label);
+
+        let config_label = Label::new(Some(&format!("Add your own wrappers in {}", wrappers_path().display())));
+        config_label.set_halign(gtk4::Align::Start);
+        config_label.set_wrap(true);
+        config_label.set_selectable(true);
+        config_label.add_css_class("dim-label");
+        content.append(&config_label);
+
+        let editor = Rc::new(Self {
+            exec_entry: exec_entry.clone(),
+            container,
+            gpu_check,
+            presets,
+            rows_box,
+            rows,
+            status_label,
+            syncing: Cell::new(false),
+        });
+
+        for (check, parameter_entry) in &editor.rows {
+            let editor_clone = editor.clone();
+            check.connect_toggled(move |_| editor_clone.write());
+            if let Some(parameter_entry) = parameter_entry {
+                let editor_clone = editor.clone();
+                let check_clone = check.clone();
+                parameter_entry.connect_changed(move |_| {
+                    if check_clone.is_active() {
+                        editor_clone.write();
+                    }
+                });
             }
         }
-        None => {
-            if !input.trim().is_empty() {
-                println!(
-                    "Unable to determine MIME type from '{}'",
-                    input.trim()
-                );
+
+        let editor_clone = editor.clone();
+        exec_entry.connect_changed(move |_| {
+            if !editor_clone.syncing.get() {
+                editor_clone.load();
+            }
+        });
+
+        editor
+    }
+
+    fn load(&self) {
+        let environment = parse_exec_environment(&self.exec_entry.text());
+        let wrapped = match &environment.shell {
+            None => parse_wrappers(&environment.command, &self.presets),
+            Some(_) => parse_wrappers("", &self.presets),
+        };
+
+        self.syncing.set(true);
+        for (preset, (check, parameter_entry)) in self.presets.iter().zip(&self.rows) {
+            let active = wrapped.wrappers.iter().find(|wrapper| wrapper.id == preset.id);
+            check.set_active(active.is_some());
+            if let Some(parameter_entry) = parameter_entry {
+                parameter_entry.set_text(active.map_or(&preset.default_parameter, |wrapper| &wrapper.parameter));
+            }
+        }
+        self.syncing.set(false);
+
+        self.rows_box.set_sensitive(environment.shell.is_none());
+        match &environment.shell {
+            Some(shell) => self.show_status(
+                &format!("Wrappers are not detected inside {} -c commands", shell.program),
+                false,
+            ),
+            None => self.status_label.set_visible(false),
+        }
+        if !wrapped.wrappers.is_empty() {
+            self.container.set_expanded(true);
+        }
+    }
+
+    fn show_status(&self, text: &str, error: bool) {
+        self.status_label.set_text(text);
+        self.status_label.set_visible(true);
+        if error {
+            self.status_label.remove_css_class("dim-label");
+            self.status_label.add_css_class("error");
+        } else {
+            self.status_label.remove_css_class("error");
+            self.status_label.add_css_class("dim-label");
+        }
+    }
+
+    // Rewrites the Command so its wrappers match the toggles, keeping the ones already there in place
+    fn write(&self) {
+        if self.syncing.get() {
+            return;
+        }
+        let mut environment = parse_exec_environment(&self.exec_entry.text());
+        if environment.shell.is_some() {
+            return;
+        }
+        let mut wrapped = parse_wrappers(&environment.command, &self.presets);
+
+        let mut missing: Vec<&str> = Vec::new();
+        let enabled: Vec<(&WrapperPreset, String)> = self
+            .presets
+            .iter()
+            .zip(&self.rows)
+            .filter(|(_, (check, _))| check.is_active())
+            .map(|(preset, (_, parameter_entry))| {
+                let parameter = parameter_entry
+                    .as_ref()
+                    .map(|parameter_entry| parameter_entry.text().trim().to_string())
+                    .unwrap_or_default();
+                if preset.has_parameter() && parameter.is_empty() {
+                    missing.push(&preset.name);
+                }
+                (preset, parameter)
+            })
+            .collect();
+        if !missing.is_empty() {
+            self.show_status(&format!("Enter a value for: {}", missing.join(", ")), true);
+            return;
+        }
+        self.status_label.set_visible(false);
+
+        wrapped
+            .wrappers
+            .retain(|wrapper| enabled.iter().any(|(preset, _)| preset.id == wrapper.id));
+        for (preset, parameter) in enabled {
+            match wrapped.wrappers.iter_mut().find(|wrapper| wrapper.id == preset.id) {
+                Some(wrapper) => wrapper.parameter = parameter,
+                None => wrapped.wrappers.push(ActiveWrapper {
+                    id: preset.id.clone(),
+                    program: preset.program.clone(),
+                    parameter,
+                }),
+            }
+        }
+
+        environment.command = format_wrappers(&wrapped, &self.presets);
+        let exec = format_exec_environment(&environment);
+        if exec != self.exec_entry.text() {
+            self.syncing.set(true);
+            self.exec_entry.set_text(&exec);
+            self.syncing.set(false);
+        }
+    }
+}
+
+const EXEC_COMPLETION_LIMIT: usize = 10;
+// Longest `whatis` or `--help` may take to print a description before it is killed
+const EXEC_DESCRIPTION_TIMEOUT_MS: u64 = 1000;
+
+// Suggestions under the Command field for the program being typed, after any env prefix
+// and launch wrappers
+struct ExecCompletion {
+    exec_entry: Entry,
+    popover: gtk4::Popover,
+    list_box: ListBox,
+    description_label: Label,
+    // Runs the selected program with --help, only when asked, since that starts it
+    help_button: Button,
+    presets: Vec<WrapperPreset>,
+    entries: Rc<RefCell<Vec<DesktopEntry>>>,
+    // Shared with the Command checks and reloaded by Refresh
+    candidates: Rc<RefCell<Vec<CommandCandidate>>>,
+    shown: RefCell<Vec<CommandCandidate>>,
+    descriptions: RefCell<HashMap<std::path::PathBuf, Option<String>>>,
+    applying: Cell<bool>,
+}
+
+impl ExecCompletion {
+    fn new(
+        exec_entry: &Entry,
+        presets: Vec<WrapperPreset>,
+        entries: Rc<RefCell<Vec<DesktopEntry>>>,
+        candidates: Rc<RefCell<Vec<CommandCandidate>>>,
+    ) -> Rc<Self> {
+        let popover = gtk4::Popover::new();
+        popover.set_parent(exec_entry);
+        let popover_clone = popover.clone();
+        exec_entry.connect_destroy(move |_| popover_clone.unparent());
+        popover.set_autohide(false);
+        popover.set_has_arrow(false);
+        popover.set_position(gtk4::PositionType::Bottom);
+        popover.set_halign(gtk4::Align::Start);
+
+        let content = GtkBox::new(Orientation::Vertical, 5);
+        let list_box = ListBox::new();
+        list_box.set_selection_mode(gtk4::SelectionMode::Single);
+        list_box.set_can_focus(false);
+        let description_label = Label::new(None);
+        description_label.set_halign(gtk4::Align::Start);
+        description_label.set_wrap(true);
+        description_label.set_max_width_chars(60);
+        description_label.add_css_class("dim-label");
+        description_label.set_visible(false);
+        let help_button = Button::with_label("Describe with --help");
+        help_button.add_css_class("flat");
+        help_button.set_halign(gtk4::Align::Start);
+        help_button.set_focusable(false);
+        help_button.set_focus_on_click(false);
+        help_button.set_tooltip_text(Some("Run the program with --help and show the first line it prints"));
+        help_button.set_visible(false);
+        content.append(&list_box);
+        content.append(&description_label);
+        content.append(&help_button);
+        popover.set_child(Some(&content));
+
+        let completion = Rc::new(Self {
+            exec_entry: exec_entry.clone(),
+            popover,
+            list_box,
+            description_label,
+            help_button,
+            presets,
+            entries,
+            candidates,
+            shown: RefCell::new(Vec::new()),
+            descriptions: RefCell::new(HashMap::new()),
+            applying: Cell::new(false),
+        });
+
+        let completion_clone = completion.clone();
+        completion.list_box.connect_row_activated(move |_, row| {
+            completion_clone.apply(row.index());
+        });
+        let completion_clone = completion.clone();
+        completion.list_box.connect_row_selected(move |_, row| {
+            if let Some(row) = row {
+                completion_clone.describe(row.index());
+            }
+        });
+        let completion_clone = completion.clone();
+        completion.help_button.connect_clicked(move |_| {
+            completion_clone.describe_with_help();
+        });
+
+        let completion_clone = completion.clone();
+        exec_entry.connect_changed(move |_| {
+            if !completion_clone.applying.get() {
+                completion_clone.update();
+            }
+        });
+
+        // Arrow keys and Enter drive the list while focus stays in the entry
+        let key_controller = gtk4::EventControllerKey::new();
+        key_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+        let completion_clone = completion.clone();
+        key_controller.connect_key_pressed(move |_, key, _, _| {
+            let completion = &completion_clone;
+            if !completion.popover.is_visible() {
+                return gtk4::glib::Propagation::Proceed;
+            }
+            let selected = completion.list_box.selected_row().map(|row| row.index());
+            let count = completion.shown.borrow().len() as i32;
+            let target = match key {
+                gtk4::gdk::Key::Down => Some(selected.map_or(0, |index| (index + 1).min(count - 1))),
+                gtk4::gdk::Key::Up => Some(selected.map_or(count - 1, |index| (index - 1).max(0))),
+                gtk4::gdk::Key::Return | gtk4::gdk::Key::KP_Enter => {
+                    let Some(index) = selected else {
+                        return gtk4::glib::Propagation::Proceed;
+                    };
+                    completion.apply(index);
+                    return gtk4::glib::Propagation::Stop;
+                }
+                gtk4::gdk::Key::Escape => {
+                    completion.popover.popdown();
+                    return gtk4::glib::Propagation::Stop;
+                }
+                _ => None,
+            };
+            match target.and_then(|index| completion.list_box.row_at_index(index)) {
+                Some(row) => {
+                    completion.list_box.select_row(Some(&row));
+                    gtk4::glib::Propagation::Stop
+                }
+                None => gtk4::glib::Propagation::Proceed,
             }
+        });
+        exec_entry.add_controller(key_controller);
+
+        let focus_controller = gtk4::EventControllerFocus::new();
+        let completion_clone = completion.clone();
+        focus_controller.connect_leave(move |_| completion_clone.popover.popdown());
+        exec_entry.add_controller(focus_controller);
+
+        completion
+    }
+
+    // The program being typed, when the command has nothing after it yet
+    fn program_query(&self) -> Option<String> {
+        let environment = parse_exec_environment(&self.exec_entry.text());
+        if environment.shell.is_some() {
+            return None;
+        }
+        let wrapped = parse_wrappers(&environment.command, &self.presets);
+        (!wrapped.command.is_empty() && !wrapped.command.contains(char::is_whitespace)).then_some(wrapped.command)
+    }
+
+    fn update(&self) {
+        let Some(query) = self.program_query().filter(|_| self.exec_entry.has_focus()) else {
+            self.popover.popdown();
+            return;
+        };
+        let shown: Vec<CommandCandidate> = {
+            let candidates = self.candidates.borrow();
+            rank_candidates(&query, &candidates, EXEC_COMPLETION_LIMIT)
+                .into_iter()
+                .cloned()
+                .collect()
+        };
+        clear_list_box(&self.list_box);
+        self.description_label.set_visible(false);
+        self.help_button.set_visible(false);
+        if shown.is_empty() || (shown.len() == 1 && shown[0].command == query) {
+            self.shown.borrow_mut().clear();
+            self.popover.popdown();
+            return;
+        }
+
+        for candidate in &shown {
+            let row_box = GtkBox::new(Orientation::Horizontal, 10);
+            row_box.set_margin_start(5);
+            row_box.set_margin_end(5);
+            let name_label = Label::new(Some(&candidate.name));
+            name_label.set_halign(gtk4::Align::Start);
+            name_label.set_hexpand(true);
+            let detail_label = Label::new(Some(&candidate.detail));
+            detail_label.set_ellipsize(gtk4::pango::EllipsizeMode::Start);
+            detail_label.set_max_width_chars(40);
+            detail_label.add_css_class("dim-label");
+            row_box.append(&name_label);
+            row_box.append(&detail_label);
+
+            let row = gtk4::ListBoxRow::new();
+            row.set_child(Some(&row_box));
+            row.set_focusable(false);
+            self.list_box.append(&row);
+        }
+        *self.shown.borrow_mut() = shown;
+        self.popover.set_size_request(self.exec_entry.width(), -1);
+        self.popover.popup();
+    }
+
+    fn apply(&self, index: i32) {
+        let Some(candidate) = usize::try_from(index)
+            .ok()
+            .and_then(|index| self.shown.borrow().get(index).cloned())
+        else {
+            return;
+        };
+        let mut environment = parse_exec_environment(&self.exec_entry.text());
+        let mut wrapped = parse_wrappers(&environment.command, &self.presets);
+        wrapped.command = candidate.command;
+        environment.command = format_wrappers(&wrapped, &self.presets);
+
+        self.applying.set(true);
+        self.exec_entry.set_text(&format_exec_environment(&environment));
+        self.exec_entry.set_position(-1);
+        self.applying.set(false);
+        self.popover.popdown();
+    }
+
+    fn selected_candidate(&self) -> Option<CommandCandidate> {
+        let row = self.list_box.selected_row()?;
+        self.shown.borrow().get(usize::try_from(row.index()).ok()?).cloned()
+    }
+
+    fn show_description(&self, description: Option<&str>) {
+        self.description_label.set_text(description.unwrap_or(""));
+        self.description_label.set_visible(description.is_some());
+        self.help_button.set_visible(description.is_none());
+    }
+
+    // Shows the Comment of an entry that already runs the selected program, or its man
+    // page summary. The program itself is only run through the --help button.
+    fn describe(self: &Rc<Self>, index: i32) {
+        let Some(candidate) = usize::try_from(index)
+            .ok()
+            .and_then(|index| self.shown.borrow().get(index).cloned())
+        else {
+            return;
+        };
+        let Some(path) = candidate.path else {
+            self.description_label.set_text(&candidate.detail);
+            self.description_label.set_visible(true);
+            self.help_button.set_visible(false);
+            return;
+        };
+        if let Some(description) = self.descriptions.borrow().get(&path) {
+            self.show_description(description.as_deref());
+            return;
+        }
+
+        let canonical = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        let comment = self
+            .entries
+            .borrow()
+            .iter()
+            .filter(|entry| !entry.comment.trim().is_empty())
+            .find(|entry| exec_program(&entry.exec).as_ref() == Some(&canonical))
+            .map(|entry| entry.comment.trim().to_string());
+        if let Some(comment) = comment {
+            self.show_description(Some(&comment));
+            self.descriptions.borrow_mut().insert(path, Some(comment));
+            return;
+        }
+
+        self.description_label.set_visible(false);
+        self.help_button.set_visible(false);
+        let completion = self.clone();
+        gtk4::glib::spawn_future_local(async move {
+            let description = command_output(&[std::ffi::OsStr::new("whatis"), std::ffi::OsStr::new(&candidate.name)])
+                .await
+                .as_deref()
+                .and_then(whatis_summary);
+            completion.finish_description(&path, description);
+        });
+    }
+
+    fn describe_with_help(self: &Rc<Self>) {
+        let Some(candidate) = self.selected_candidate() else {
+            return;
+        };
+        let Some(path) = candidate.path else {
+            return;
+        };
+        self.help_button.set_sensitive(false);
+        let completion = self.clone();
+        gtk4::glib::spawn_future_local(async move {
+            let description = command_output(&[path.as_os_str(), std::ffi::OsStr::new("--help")])
+                .await
+                .and_then(|output| help_summary(&output, &candidate.name));
+            completion.help_button.set_sensitive(true);
+            completion.finish_description(&path, description);
+        });
+    }
+
+    fn finish_description(&self, path: &std::path::Path, description: Option<String>) {
+        self.descriptions.borrow_mut().insert(path.to_path_buf(), description.clone());
+        let still_selected = self
+            .selected_candidate()
+            .is_some_and(|selected| selected.path.as_deref() == Some(path));
+        if still_selected {
+            self.show_description(description.as_deref());
         }
     }
+}
+
+// Runs a command with no input and returns what it printed, killing it if it takes too long
+async fn command_output(argv: &[&std::ffi::OsStr]) -> Option<String> {
+    let process = gio::Subprocess::newv(
+        argv,
+        gio::SubprocessFlags::STDOUT_PIPE | gio::SubprocessFlags::STDERR_MERGE,
+    )
+    .ok()?;
+    let timeout: Rc<Cell<Option<gtk4::glib::SourceId>>> = Rc::new(Cell::new(None));
+    let timeout_clone = timeout.clone();
+    let process_clone = process.clone();
+    timeout.set(Some(gtk4::glib::timeout_add_local_once(
+        std::time::Duration::from_millis(EXEC_DESCRIPTION_TIMEOUT_MS),
+        move || {
+            timeout_clone.take();
+            process_clone.force_exit();
+        },
+    )));
+
+    let result = process.communicate_utf8_future(None).await;
+    if let Some(source) = timeout.take() {
+        source.remove();
+    }
+    let (output, _) = result.ok()?;
+    output.map(|output| output.to_string())
+}
+
+// An unsaved Application entry in ~/.local/share/applications named `file_stem`.desktop
+fn new_desktop_entry(file_stem: &str, name: &str, exec: &str, icon: &str) -> DesktopEntry {
+    let home = std::env::var("HOME").unwrap_or_else(|_| String::from("/tmp"));
+    DesktopEntry {
+        path: std::path::PathBuf::from(format!("{}/.local/share/applications/{}.desktop", home, file_stem)),
+        name: name.to_string(),
+        exec: exec.to_string(),
+        try_exec: String::new(),
+        icon: icon.to_string(),
+        comment: String::new(),
+        terminal: false,
+        prefers_non_default_gpu: false,
+        categories: String::new(),
+        working_directory: String::new(),
+        entry_type: String::from("Application"),
+        mime_types: Vec::new(),
+        mime_extensions: HashMap::new(),
+    }
+}
+
+fn update_categories_status(status_label: &Label, value: &str) {
+    let warnings = check_categories(&parse_categories(value));
+    status_label.set_visible(!warnings.is_empty());
+    status_label.set_text(&warnings.join("\n"));
+}
+
+fn show_categories_picker(parent: &ApplicationWindow, categories_entry: Entry) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .title("Choose Categories")
+        .default_width(560)
+        .default_height(620)
+        .build();
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    window.set_child(Some(&content));
+
+    let current = parse_categories(&categories_entry.text());
+
+    // Audio and Video imply AudioVideo, so the more specific one is the selection
+    let main: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(
+        ["Audio", "Video"]
+            .iter()
+            .map(|name| name.to_string())
+            .find(|name| current.contains(name))
+            .or_else(|| current.iter().find(|category| is_main_category(category)).cloned()),
+    ));
+    let additional: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(
+        current
+            .iter()
+            .filter(|category| related_categories(category).is_some())
+            .cloned()
+            .collect(),
+    ));
+    let kept: Vec<String> = current
+        .iter()
+        .filter(|category| !is_main_category(category) && related_categories(category).is_none())
+        .cloned()
+        .collect();
+    let dropped_mains: Vec<String> = current
+        .iter()
+        .filter(|category| is_main_category(category))
+        .filter(|category| {
+            let selected = main.borrow();
+            Some(category.as_str()) != selected.as_deref()
+                && !(category.as_str() == "AudioVideo"
+                    && matches!(selected.as_deref(), Some("Audio") | Some("Video")))
+        })
+        .cloned()
+        .collect();
+
+    let main_box = GtkBox::new(Orientation::Horizontal, 6);
+    main_box.append(&Label::new(Some("Main category:")));
+    let mut main_names = vec!["None"];
+    main_names.extend(MAIN_CATEGORIES);
+    let main_dropdown = gtk4::DropDown::from_strings(&main_names);
+    main_dropdown.set_hexpand(true);
+    if let Some(index) = main
+        .borrow()
+        .as_ref()
+        .and_then(|selected| main_names.iter().position(|name| name == selected))
+    {
+        main_dropdown.set_selected(index as u32);
+    }
+    main_box.append(&main_dropdown);
+    content.append(&main_box);
+
+    if !dropped_mains.is_empty() {
+        let dropped_label = Label::new(Some(&format!(
+            "Only one main category is kept; applying removes {}",
+            dropped_mains.join(", ")
+        )));
+        dropped_label.set_halign(gtk4::Align::Start);
+        dropped_label.set_wrap(true);
+        dropped_label.add_css_class("warning");
+        content.append(&dropped_label);
+    }
+
+    let filter_entry = Entry::builder()
+        .placeholder_text("Filter additional categories")
+        .build();
+    content.append(&filter_entry);
+
+    let scrolled = ScrolledWindow::builder()
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    let additional_list = GtkBox::new(Orientation::Vertical, 2);
+    scrolled.set_child(Some(&additional_list));
+    content.append(&scrolled);
+
+    if !kept.is_empty() {
+        let kept_label = Label::new(Some(&format!("Kept as-is: {}", format_categories(&kept))));
+        kept_label.set_halign(gtk4::Align::Start);
+        kept_label.set_wrap(true);
+        kept_label.add_css_class("dim-label");
+        content.append(&kept_label);
+    }
+
+    let preview_label = Label::new(None);
+    preview_label.set_halign(gtk4::Align::Start);
+    preview_label.set_selectable(true);
+    preview_label.add_css_class("monospace");
+    content.append(&preview_label);
+
+    let warnings_label = Label::new(None);
+    warnings_label.set_halign(gtk4::Align::Start);
+    warnings_label.set_wrap(true);
+    warnings_label.add_css_class("warning");
+    content.append(&warnings_label);
+
+    let footer = GtkBox::new(Orientation::Horizontal, 6);
+    footer.set_halign(gtk4::Align::End);
+    let cancel_button = Button::with_label("Cancel");
+    let apply_button = Button::with_label("Apply");
+    apply_button.add_css_class("suggested-action");
+    footer.append(&cancel_button);
+    footer.append(&apply_button);
+    content.append(&footer);
+
+    let compose = {
+        let main = main.clone();
+        let additional = additional.clone();
+        move || -> Vec<String> {
+            let mut result = Vec::new();
+            if let Some(selected) = main.borrow().as_ref() {
+                if selected == "Audio" || selected == "Video" {
+                    result.push(String::from("AudioVideo"));
+                }
+                result.push(selected.clone());
+            }
+            let selected_additional = additional.borrow();
+            for (name, _) in ADDITIONAL_CATEGORIES {
+                if selected_additional.iter().any(|category| category == name) {
+                    result.push(name.to_string());
+                }
+            }
+            result.extend(kept.iter().cloned());
+            result
+        }
+    };
+
+    let refresh: Rc<dyn Fn()> = {
+        let compose = compose.clone();
+        let preview_label = preview_label.clone();
+        let warnings_label = warnings_label.clone();
+        Rc::new(move || {
+            let categories = compose();
+            preview_label.set_text(&format!("Categories={}", format_categories(&categories)));
+            warnings_label.set_text(&check_categories(&categories).join("\n"));
+        })
+    };
+
+    let mut rows: Vec<(String, GtkBox)> = Vec::new();
+    for (name, related) in ADDITIONAL_CATEGORIES {
+        let row_box = GtkBox::new(Orientation::Horizontal, 8);
+        let check = CheckButton::with_label(name);
+        check.set_active(additional.borrow().iter().any(|category| category == name));
+        row_box.append(&check);
+        if !related.is_empty() {
+            let related_label = Label::new(Some(&format!("needs {}", describe_related(related))));
+            related_label.add_css_class("dim-label");
+            row_box.append(&related_label);
+        }
+
+        let additional_for_check = additional.clone();
+        let refresh_for_check = refresh.clone();
+        let name_for_check = name.to_string();
+        check.connect_toggled(move |check| {
+            {
+                let mut selected = additional_for_check.borrow_mut();
+                selected.retain(|category| category != &name_for_check);
+                if check.is_active() {
+                    selected.push(name_for_check.clone());
+                }
+            }
+            refresh_for_check();
+        });
+
+        additional_list.append(&row_box);
+        rows.push((name.to_lowercase(), row_box));
+    }
+
+    filter_entry.connect_changed(move |entry| {
+        let query = entry.text().to_lowercase();
+        for (name, row_box) in &rows {
+            row_box.set_visible(query.is_empty() || name.contains(&query));
+        }
+    });
+
+    let refresh_for_main = refresh.clone();
+    main_dropdown.connect_selected_notify(move |dropdown| {
+        *main.borrow_mut() = match dropdown.selected() {
+            0 => None,
+            index => MAIN_CATEGORIES.get(index as usize - 1).map(|name| name.to_string()),
+        };
+        refresh_for_main();
+    });
+    refresh();
+
+    let window_for_cancel = window.clone();
+    cancel_button.connect_clicked(move |_| window_for_cancel.close());
+
+    let window_for_apply = window.clone();
+    apply_button.connect_clicked(move |_| {
+        categories_entry.set_text(&format_categories(&compose()));
+        window_for_apply.close();
+    });
+
+    window.present();
+}
+
+fn image_file_filter() -> FileFilter {
+    let filter = FileFilter::new();
+    filter.set_name(Some("Image Files"));
+    filter.add_mime_type("image/png");
+    filter.add_mime_type("image/jpeg");
+    filter.add_mime_type("image/svg+xml");
+    filter.add_pattern("*.png");
+    filter.add_pattern("*.jpg");
+    filter.add_pattern("*.jpeg");
+    filter.add_pattern("*.svg");
+    filter
+}
+
+const ICON_PICKER_MAX_ICONS: usize = 400;
+
+fn show_icon_picker(parent: &ApplicationWindow, icon_entry: Entry, resolver: Rc<IconResolver>) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .title("Choose Icon")
+        .default_width(760)
+        .default_height(600)
+        .build();
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    window.set_child(Some(&content));
+
+    let themes = installed_themes();
+    let theme_names: Vec<String> = themes.iter().map(|theme| theme.name.clone()).collect();
+    let theme_labels: Vec<&str> = themes.iter().map(|theme| theme.display_name.as_str()).collect();
+
+    let filter_box = GtkBox::new(Orientation::Horizontal, 6);
+    let search_entry = Entry::builder()
+        .placeholder_text("Search icon names")
+        .hexpand(true)
+        .build();
+    let context_dropdown = gtk4::DropDown::from_strings(&[]);
+    let theme_dropdown = gtk4::DropDown::from_strings(&theme_labels);
+    theme_dropdown.set_tooltip_text(Some("Theme used for the previews"));
+    if let Some(index) = theme_names.iter().position(|name| name == resolver.theme_name()) {
+        theme_dropdown.set_selected(index as u32);
+    }
+    filter_box.append(&search_entry);
+    filter_box.append(&context_dropdown);
+    filter_box.append(&theme_dropdown);
+    content.append(&filter_box);
+
+    let scrolled = ScrolledWindow::builder()
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    content.append(&scrolled);
+
+    let footer = GtkBox::new(Orientation::Horizontal, 6);
+    let status_label = Label::new(None);
+    status_label.set_halign(gtk4::Align::Start);
+    status_label.set_hexpand(true);
+    status_label.set_wrap(true);
+    status_label.add_css_class("dim-label");
+    let use_button = Button::with_label("Use Icon");
+    use_button.add_css_class("suggested-action");
+    use_button.set_sensitive(false);
+    footer.append(&status_label);
+    footer.append(&use_button);
+    content.append(&footer);
+
+    // The preview resolver follows the theme dropdown; the chosen icon name works in any theme
+    let preview_resolver: Rc<RefCell<Rc<IconResolver>>> = Rc::new(RefCell::new(resolver));
+    let icons: Rc<RefCell<BTreeMap<String, BTreeSet<String>>>> =
+        Rc::new(RefCell::new(preview_resolver.borrow().available_icons()));
+    let contexts: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(Vec::new()));
+    let selected_icon: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+    // Set while the context model is replaced; the caller rebuilds the grid once afterwards
+    let resetting_contexts = Rc::new(Cell::new(false));
+
+    let reset_contexts: Rc<dyn Fn()> = {
+        let icons = icons.clone();
+        let contexts = contexts.clone();
+        let context_dropdown = context_dropdown.clone();
+        let resetting_contexts = resetting_contexts.clone();
+        Rc::new(move || {
+            let previous = contexts
+                .borrow()
+                .get(context_dropdown.selected() as usize)
+                .cloned();
+            let mut names = vec![String::from("All contexts")];
+            names.extend(icons.borrow().keys().cloned());
+            let index = previous
+                .and_then(|previous| names.iter().position(|name| *name == previous))
+                .unwrap_or(0);
+            let labels: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+            let model = gtk4::StringList::new(&labels);
+            *contexts.borrow_mut() = names;
+
+            resetting_contexts.set(true);
+            context_dropdown.set_model(Some(&model));
+            context_dropdown.set_selected(index as u32);
+            resetting_contexts.set(false);
+        })
+    };
+    reset_contexts();
+
+    let rebuild: Rc<dyn Fn()> = {
+        let scrolled = scrolled.clone();
+        let search_entry = search_entry.clone();
+        let context_dropdown = context_dropdown.clone();
+        let status_label = status_label.clone();
+        let use_button = use_button.clone();
+        let icons = icons.clone();
+        let contexts = contexts.clone();
+        let preview_resolver = preview_resolver.clone();
+        let selected_icon = selected_icon.clone();
+        let icon_entry = icon_entry.clone();
+        let window = window.clone();
+        Rc::new(move || {
+            let query = search_entry.text().to_lowercase();
+            let context_filter = match context_dropdown.selected() {
+                0 => None,
+                index => contexts.borrow().get(index as usize).cloned(),
+            };
+            let resolver = preview_resolver.borrow().clone();
+
+            let sections = GtkBox::new(Orientation::Vertical, 6);
+            let mut matched = 0;
+            let mut shown = 0;
+
+            for (context, names) in icons.borrow().iter() {
+                if context_filter.as_ref().is_some_and(|filter| filter != context) {
+                    continue;
+                }
+                let matching: Vec<&String> = names
+                    .iter()
+                    .filter(|name| query.is_empty() || name.to_lowercase().contains(&query))
+                    .collect();
+                if matching.is_empty() {
+                    continue;
+                }
+                matched += matching.len();
+
+                let remaining = ICON_PICKER_MAX_ICONS.saturating_sub(shown);
+                if remaining == 0 {
+                    continue;
+                }
+
+                let header = Label::new(Some(&format!("{} ({})", context, matching.len())));
+                header.set_halign(gtk4::Align::Start);
+                header.add_css_class("heading");
+                sections.append(&header);
+
+                let flow = gtk4::FlowBox::new();
+                flow.set_selection_mode(gtk4::SelectionMode::Single);
+                flow.set_homogeneous(true);
+                flow.set_max_children_per_line(8);
+
+                for name in matching.into_iter().take(remaining) {
+                    let tile = GtkBox::new(Orientation::Vertical, 4);
+                    let image = Image::new();
+                    image.set_pixel_size(48);
+                    match resolver.lookup(name, 48, 1).file {
+                        Some(file) => image.set_from_file(Some(file)),
+                        None => image.set_icon_name(Some("image-missing")),
+                    }
+                    let label = Label::new(Some(name));
+                    label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+                    label.set_max_width_chars(14);
+                    tile.append(&image);
+                    tile.append(&label);
+
+                    let child = gtk4::FlowBoxChild::new();
+                    child.set_child(Some(&tile));
+                    child.set_tooltip_text(Some(name));
+                    unsafe {
+                        child.set_data("icon-name", name.clone());
+                    }
+                    flow.insert(&child, -1);
+                    shown += 1;
+                }
+
+                let selected_for_flow = selected_icon.clone();
+                let status_for_flow = status_label.clone();
+                let use_for_flow = use_button.clone();
+                let sections_for_flow = sections.clone();
+                let resolver_for_flow = resolver.clone();
+                flow.connect_child_activated(move |flow, child| {
+                    // Only one icon can be selected across all context sections
+                    let mut section = sections_for_flow.first_child();
+                    while let Some(widget) = section {
+                        if let Some(other) = widget.downcast_ref::<gtk4::FlowBox>() {
+                            if other != flow {
+                                other.unselect_all();
+                            }
+                        }
+                        section = widget.next_sibling();
+                    }
+
+                    let Some(name) = row_string_data(child, "icon-name") else {
+                        return;
+                    };
+                    status_for_flow.set_text(&format!(
+                        "{}: {}",
+                        name,
+                        resolver_for_flow.lookup(&name, 48, 1).describe()
+                    ));
+                    *selected_for_flow.borrow_mut() = Some(name);
+                    use_for_flow.set_sensitive(true);
+                });
+
+                let icon_entry_for_flow = icon_entry.clone();
+                let window_for_flow = window.clone();
+                let gesture = gtk4::GestureClick::new();
+                let flow_for_gesture = flow.clone();
+                gesture.connect_pressed(move |_, presses, x, y| {
+                    if presses != 2 {
+                        return;
+                    }
+                    let Some(child) = flow_for_gesture.child_at_pos(x as i32, y as i32) else {
+                        return;
+                    };
+                    if let Some(name) = row_string_data(&child, "icon-name") {
+                        icon_entry_for_flow.set_text(&name);
+                        window_for_flow.close();
+                    }
+                });
+                flow.add_controller(gesture);
+
+                sections.append(&flow);
+            }
+
+            if matched == 0 {
+                let empty_label = Label::new(Some("No icons match."));
+                empty_label.add_css_class("dim-label");
+                sections.append(&empty_label);
+            }
+
+            *selected_icon.borrow_mut() = None;
+            use_button.set_sensitive(false);
+            status_label.set_text(&if shown < matched {
+                format!(
+                    "Showing {} of {} icons from {} and its parent themes; refine the search to see more",
+                    shown,
+                    matched,
+                    resolver.theme_name()
+                )
+            } else {
+                format!("{} icons from {} and its parent themes", matched, resolver.theme_name())
+            });
+            scrolled.set_child(Some(&sections));
+        })
+    };
+    rebuild();
+
+    let rebuild_for_search = rebuild.clone();
+    search_entry.connect_changed(move |_| rebuild_for_search());
+
+    let rebuild_for_context = rebuild.clone();
+    context_dropdown.connect_selected_notify(move |_| {
+        if !resetting_contexts.get() {
+            rebuild_for_context();
+        }
+    });
+
+    let preview_resolver_for_theme = preview_resolver.clone();
+    let icons_for_theme = icons.clone();
+    let rebuild_for_theme = rebuild.clone();
+    theme_dropdown.connect_selected_notify(move |dropdown| {
+        let Some(theme_name) = theme_names.get(dropdown.selected() as usize) else {
+            return;
+        };
+        let resolver = Rc::new(IconResolver::new(theme_name));
+        *icons_for_theme.borrow_mut() = resolver.available_icons();
+        *preview_resolver_for_theme.borrow_mut() = resolver;
+        reset_contexts();
+        rebuild_for_theme();
+    });
+
+    let window_for_use = window.clone();
+    use_button.connect_clicked(move |_| {
+        if let Some(name) = selected_icon.borrow().as_ref() {
+            icon_entry.set_text(name);
+        }
+        window_for_use.close();
+    });
+
+    window.present();
+}
+
+fn clear_list_box(list: &ListBox) {
+    while let Some(child) = list.first_child() {
+        list.remove(&child);
+    }
+}
+
+fn add_mime_association(
+    mime_type: &str,
+    extension: Option<&str>,
+    state: &Rc<RefCell<Vec<String>>>,
+    extensions_state: &Rc<RefCell<HashMap<String, String>>>,
+    list: &ListBox,
+) -> bool {
+    if state
+        .borrow()
+        .iter()
+        .any(|existing| existing == mime_type)
+    {
+        return false;
+    }
+
+    state.borrow_mut().push(mime_type.to_string());
+    if let Some(ext) = extension {
+        extensions_state
+            .borrow_mut()
+            .insert(mime_type.to_string(), ext.to_string());
+    }
+
+    let ext_for_row = extension
+        .map(|value| value.to_string())
+        .or_else(|| extensions_state.borrow().get(mime_type).cloned())
+        .filter(|value| !value.is_empty());
+
+    append_mime_row(
+        list,
+        mime_type,
+        ext_for_row.as_deref(),
+        state.clone(),
+        extensions_state.clone(),
+    );
+    true
+}
+
+fn row_string_data(row: &impl IsA<gtk4::glib::Object>, key: &str) -> Option<String> {
+    unsafe { row.data::<String>(key).map(|ptr| ptr.as_ref().clone()) }
+}
+
+fn add_mime_from_row(
+    row: &gtk4::ListBoxRow,
+    state: &Rc<RefCell<Vec<String>>>,
+    list: &ListBox,
+    current_entry: &Rc<RefCell<Option<DesktopEntry>>>,
+    known_map: &Rc<RefCell<HashMap<String, String>>>,
+    extensions_state: &Rc<RefCell<HashMap<String, String>>>,
+) {
+    if let Some(mime_value) = row_string_data(row, "mime-type") {
+        let extension_value = row_string_data(row, "extension");
+        let extension_opt = extension_value
+            .as_ref()
+            .and_then(|value| if value.is_empty() { None } else { Some(value.as_str()) });
+
+        let mut map = known_map.borrow_mut();
+        if let Some(ext_str) = extension_opt {
+            map.entry(mime_value.clone()).or_insert_with(|| ext_str.to_string());
+        }
+
+        let resolved_extension = extension_opt.or_else(|| map.get(&mime_value).map(|s| s.as_str()));
+
+        if add_mime_association(
+            &mime_value,
+            resolved_extension,
+            state,
+            extensions_state,
+            list,
+        ) {
+            if let Some(ref mut entry) = *current_entry.borrow_mut() {
+                entry.mime_types = state.borrow().clone();
+                entry.mime_extensions = extensions_state.borrow().clone();
+            }
+
+            let display_extension = resolved_extension.unwrap_or("-");
+            println!(
+                "Added file association '{}' ({})",
+                mime_value,
+                display_extension
+            );
+        } else {
+            println!(
+                "File association '{}' already exists",
+                mime_value
+            );
+        }
+    }
+}
+
+fn try_add_manual_mime(
+    entry: &Entry,
+    state: &Rc<RefCell<Vec<String>>>,
+    list: &ListBox,
+    current_entry: &Rc<RefCell<Option<DesktopEntry>>>,
+    known_map: &Rc<RefCell<HashMap<String, String>>>,
+    extensions_state: &Rc<RefCell<HashMap<String, String>>>,
+) {
+    let input = entry.text().to_string();
+
+    match resolve_mime_from_input(&input) {
+        Some((mime, display_hint)) => {
+            add_resolved_mime(
+                &mime,
+                &display_hint,
+                state,
+                list,
+                current_entry,
+                known_map,
+                extensions_state,
+            );
+        }
+        None => {
+            if !input.trim().is_empty() {
+                println!(
+                    "Unable to determine MIME type from '{}'",
+                    input.trim()
+                );
+            }
+        }
+    }
+
+    entry.set_text("");
+}
+
+fn add_resolved_mime(
+    mime: &str,
+    display_hint: &str,
+    state: &Rc<RefCell<Vec<String>>>,
+    list: &ListBox,
+    current_entry: &Rc<RefCell<Option<DesktopEntry>>>,
+    known_map: &Rc<RefCell<HashMap<String, String>>>,
+    extensions_state: &Rc<RefCell<HashMap<String, String>>>,
+) {
+    let extension_opt = if display_hint.is_empty() {
+        None
+    } else {
+        Some(display_hint)
+    };
+
+    let mut map = known_map.borrow_mut();
+    if let Some(ext_str) = extension_opt {
+        map.entry(mime.to_string()).or_insert_with(|| ext_str.to_string());
+    }
+    let resolved_extension = extension_opt
+        .or_else(|| map.get(mime).map(|value| value.as_str()));
+
+    if add_mime_association(
+        mime,
+        resolved_extension,
+        state,
+        extensions_state,
+        list,
+    ) {
+        if let Some(ref mut entry_ref) = *current_entry.borrow_mut() {
+            entry_ref.mime_types = state.borrow().clone();
+            entry_ref.mime_extensions = extensions_state.borrow().clone();
+        }
+
+        println!(
+            "Added file association '{}' ({})",
+            mime,
+            resolved_extension.unwrap_or("-")
+        );
+    } else {
+        println!(
+            "File association '{}' already exists",
+            mime
+        );
+    }
+}
+
+fn set_mime_list_owner(list: &ListBox, entry: &DesktopEntry, read_only: bool) {
+    let desktop_id = entry.desktop_id();
+    unsafe {
+        // Hiding writes to mimeapps.list right away, which makes no sense for a file not saved yet
+        list.set_data("saved", entry.path.exists());
+        list.set_data("hidden-mime-types", removed_associations(&desktop_id));
+        list.set_data("desktop-id", desktop_id);
+        list.set_data("read-only", read_only);
+    }
+}
+
+fn append_mime_row(
+    list: &ListBox,
+    mime_type: &str,
+    extension: Option<&str>,
+    state: Rc<RefCell<Vec<String>>>,
+    extensions_state: Rc<RefCell<HashMap<String, String>>>,
+) {
+    let desktop_id = unsafe { list.data::<String>("desktop-id").map(|ptr| ptr.as_ref().clone()) }
+        .unwrap_or_default();
+    let read_only = unsafe { list.data::<bool>("read-only").map(|ptr| *ptr.as_ref()) }
+        .unwrap_or(false);
+    let saved = unsafe { list.data::<bool>("saved").map(|ptr| *ptr.as_ref()) }
+        .unwrap_or(false);
+    let hidden = unsafe {
+        list.data::<Vec<String>>("hidden-mime-types")
+            .map(|ptr| ptr.as_ref().iter().any(|hidden| hidden == mime_type))
+    }
+    .unwrap_or(false);
+
+    let row = gtk4::ListBoxRow::new();
+    row.set_selectable(false);
+    row.set_activatable(false);
+
+    let row_box = GtkBox::new(Orientation::Horizontal, 5);
+
+    let extension_owned = extension.unwrap_or("").to_string();
+    let scheme_display = mime_type
+        .strip_prefix("x-scheme-handler/")
+        .map(|scheme| format!("{}:", scheme));
+    let ext_display = if !extension_owned.is_empty() {
+        extension_owned.as_str()
+    } else {
+        scheme_display.as_deref().unwrap_or("—")
+    };
+
+    let ext_label = Label::new(Some(ext_display));
+    ext_label.add_css_class("monospace");
+    ext_label.set_width_chars(6);
+    ext_label.set_halign(gtk4::Align::Start);
+
+    let label = Label::new(Some(mime_type));
+    label.set_halign(gtk4::Align::Start);
+    label.set_hexpand(true);
+    label.set_wrap(true);
+    set_mime_label_hidden(&label, mime_type, hidden);
+
+    let hide_button = Button::with_label("Hide");
+    hide_button.add_css_class("flat");
+    hide_button.set_tooltip_text(Some("Don't offer this application for this type"));
+    hide_button.set_visible(!hidden);
+
+    let restore_button = Button::with_label("Restore");
+    restore_button.add_css_class("flat");
+    restore_button.set_tooltip_text(Some("Offer this application for this type again"));
+    restore_button.set_visible(hidden);
+    if !saved {
+        for button in [&hide_button, &restore_button] {
+            button.set_sensitive(false);
+            button.set_tooltip_text(Some("Save the entry first"));
+        }
+    }
+
+    let remove_button = Button::with_label("Remove");
+    remove_button.add_css_class("flat");
+    remove_button.set_halign(gtk4::Align::End);
+    remove_button.set_sensitive(!read_only);
+
+    row_box.append(&ext_label);
+    row_box.append(&label);
+    row_box.append(&hide_button);
+    row_box.append(&restore_button);
+    row_box.append(&remove_button);
+    row.set_child(Some(&row_box));
+    list.append(&row);
+
+    unsafe {
+        row.set_data("mime-type", mime_type.to_string());
+        row.set_data("extension", extension_owned.clone());
+    }
+
+    for (button, removed) in [(&hide_button, true), (&restore_button, false)] {
+        let mime_value = mime_type.to_string();
+        let desktop_id = desktop_id.clone();
+        let label = label.clone();
+        let hide_button = hide_button.clone();
+        let restore_button = restore_button.clone();
+        let list_weak = Downgrade::downgrade(list);
+        button.connect_clicked(move |_| {
+            let path = user_mimeapps_list_path();
+            match set_association_removed(&path, &mime_value, &desktop_id, removed) {
+                Ok(_) => {
+                    // Another mimeapps.list can still decide the type, so show what the files say now.
+                    // Rows added later read the hidden types from the list, so refresh them too.
+                    let hidden_types = removed_associations(&desktop_id);
+                    let hidden = hidden_types.contains(&mime_value);
+                    if let Some(list) = list_weak.upgrade() {
+                        unsafe {
+                            list.set_data("hidden-mime-types", hidden_types);
+                        }
+                    }
+                    set_mime_label_hidden(&label, &mime_value, hidden);
+                    hide_button.set_visible(!hidden);
+                    restore_button.set_visible(hidden);
+                    println!(
+                        "{} {} for '{}' in {}",
+                        if removed { "Hid" } else { "Restored" },
+                        desktop_id,
+                        mime_value,
+                        path
+                    );
+                    if hidden != removed {
+                        eprintln!(
+                            "Another mimeapps.list in the lookup order still {} {} for '{}'",
+                            if hidden { "removes" } else { "adds" },
+                            desktop_id,
+                            mime_value
+                        );
+                    }
+                }
+                Err(e) => eprintln!("Error writing {}: {}", path, e),
+            }
+        });
+    }
+
+    let mime_value = mime_type.to_string();
+    let state_clone = state.clone();
+    let extensions_state_clone = extensions_state.clone();
+    let list_weak = Downgrade::downgrade(&list);
+    let row_weak = Downgrade::downgrade(&row);
+
+    remove_button.connect_clicked(move |_| {
+        state_clone
+            .borrow_mut()
+            .retain(|existing| existing != &mime_value);
+        extensions_state_clone.borrow_mut().remove(&mime_value);
+
+        if let (Some(list_box), Some(row_widget)) = (list_weak.upgrade(), row_weak.upgrade()) {
+            list_box.remove(&row_widget);
+        }
+    });
+}
+
+fn set_mime_label_hidden(label: &Label, mime_type: &str, hidden: bool) {
+    let escaped = gtk4::glib::markup_escape_text(mime_type);
+    if hidden {
+        label.set_markup(&format!("<s>{}</s>", escaped));
+        label.add_css_class("dim-label");
+    } else {
+        label.set_markup(&escaped);
+        label.remove_css_class("dim-label");
+    }
+}
+
+fn resolve_mime_from_input(input: &str) -> Option<(String, String)> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    if trimmed.contains('/') {
+        return Some((trimmed.to_string(), String::new()));
+    }
+
+    let sanitized = trimmed.trim_start_matches('.');
+    if sanitized.is_empty() {
+        return None;
+    }
+
+    let filename = format!("dummy.{}", sanitized);
+    let path = std::path::Path::new(&filename);
+    let (content_type, uncertain) = gio::content_type_guess(Some(path), &[]);
+
+    if let Some(mime) = gio::content_type_get_mime_type(content_type.as_str()) {
+        return Some((mime.to_string(), format!(".{}", sanitized)));
+    }
+
+    if !uncertain {
+        return Some((content_type.to_string(), format!(".{}", sanitized)));
+    }
+
+    Some((
+        format!("application/x-{}", sanitized.to_lowercase()),
+        format!(".{}", sanitized),
+    ))
+}
+
+fn resolve_mime_from_sample(path: &std::path::Path, magic: &MagicDatabase) -> Option<(String, String)> {
+    if path.is_dir() {
+        return Some((String::from("inode/directory"), String::new()));
+    }
+
+    let extension_hint = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| format!(".{}", ext))
+        .unwrap_or_default();
+
+    let glob_mime = if extension_hint.is_empty() {
+        None
+    } else {
+        let (content_type, uncertain) = gio::content_type_guess(Some(path), &[]);
+        if uncertain {
+            None
+        } else {
+            gio::content_type_get_mime_type(content_type.as_str()).map(|mime| mime.to_string())
+        }
+    };
+
+    let magic_mime = magic.sniff_file(path);
+
+    let mime = match (glob_mime, magic_mime) {
+        (Some(glob), Some((sniffed, priority))) => {
+            if priority >= MAGIC_OVERRIDES_GLOB_PRIORITY {
+                sniffed
+            } else {
+                glob
+            }
+        }
+        (Some(glob), None) => glob,
+        (None, Some((sniffed, _))) => sniffed,
+        (None, None) => {
+            let head = read_file_head(path, 128).ok()?;
+            if looks_like_text(&head) {
+                String::from("text/plain")
+            } else {
+                String::from("application/octet-stream")
+            }
+        }
+    };
+
+    Some((mime, extension_hint))
+}
+
+fn build_mime_extension_map(choices: &[MimeChoice]) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    for choice in choices {
+        map.entry(choice.mime_type.clone())
+            .or_insert_with(|| choice.extension.clone());
+    }
+    map
+}
+
+fn load_known_extensions() -> Vec<MimeChoice> {
+    let mut map: HashMap<String, (String, u32)> = HashMap::new();
+
+    for path in mime_database_paths("globs2") {
+        if let Ok(file) = File::open(&path) {
+            let reader = BufReader::new(file);
+            for line in reader.lines().map_while(Result::ok) {
+                let trimmed = line.trim();
+                if trimmed.is_empty() || trimmed.starts_with('#') {
+                    continue;
+                }
+
+                let mut parts = trimmed.splitn(3, ':');
+                let weight_str = parts.next().unwrap_or("0");
+                let mime = parts.next().unwrap_or("").trim();
+                let pattern = parts.next().unwrap_or("").trim();
+
+                if mime.is_empty() || pattern.is_empty() {
+                    continue;
+                }
+
+                if let Some(extension) = extract_extension_from_pattern(pattern) {
+                    let weight = weight_str.parse::<u32>().unwrap_or(0);
+                    let entry = map
+                        .entry(extension.clone())
+                        .or_insert_with(|| (mime.to_string(), weight));
+
+                    if weight >= entry.1 {
+                        *entry = (mime.to_string(), weight);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut choices = Vec::new();
+    for (extension, (mime, _weight)) in map {
+        let description = mime_description(&mime);
+        choices.push(MimeChoice {
+            extension: format!(".{}", extension),
+            mime_type: mime,
+            description,
+        });
+    }
+
+    choices.sort_by(|a, b| a.extension.cmp(&b.extension));
+    choices
+}
+
+fn extract_extension_from_pattern(pattern: &str) -> Option<String> {
+    if !pattern.starts_with("*.") {
+        return None;
+    }
+
+    let ext = &pattern[2..];
+    if ext.is_empty() {
+        return None;
+    }
+
+    if ext.chars().any(|c| matches!(c, '*' | '?' | '[' | ']' | '!')) {
+        return None;
+    }
+
+    Some(ext.to_lowercase())
+}
+
+fn mime_description(mime: &str) -> String {
+    if let Some(content_type) = gio::content_type_from_mime_type(mime) {
+        let description = gio::content_type_get_description(content_type.as_str());
+        let value = description.to_string();
+        if !value.is_empty() {
+            return value;
+        }
+    }
+
+    mime.to_string()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn show_mime_selection_dialog(
+    parent: &ApplicationWindow,
+    known_choices: Rc<Vec<MimeChoice>>,
+    known_map: Rc<RefCell<HashMap<String, String>>>,
+    magic: Rc<MagicDatabase>,
+    all_entries: Rc<RefCell<Vec<DesktopEntry>>>,
+    mime_state: Rc<RefCell<Vec<String>>>,
+    extension_state: Rc<RefCell<HashMap<String, String>>>,
+    mime_list: &ListBox,
+    current_entry: Rc<RefCell<Option<DesktopEntry>>>,
+) {
+    let dialog = gtk4::Dialog::builder()
+        .transient_for(parent)
+        .modal(true)
+        .title("Add File Association")
+        .default_width(520)
+        .default_height(480)
+        .build();
+
+    dialog.add_button("Close", ResponseType::Close);
+
+    let content = dialog.content_area();
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+
+    let notebook = gtk4::Notebook::new();
+    notebook.set_vexpand(true);
+    content.append(&notebook);
+
+    let files_page = GtkBox::new(Orientation::Vertical, 8);
+    files_page.set_margin_top(8);
+    notebook.append_page(&files_page, Some(&Label::new(Some("File Types"))));
+
+    let protocols_page = build_protocols_page(
+        &dialog,
+        all_entries,
+        mime_state.clone(),
+        extension_state.clone(),
+        mime_list,
+        current_entry.clone(),
+        known_map.clone(),
+    );
+    notebook.append_page(&protocols_page, Some(&Label::new(Some("Protocols"))));
+
+    let filter_entry = Entry::builder()
+        .placeholder_text("Search by extension, description, or MIME type")
+        .build();
+    files_page.append(&filter_entry);
+
+    let scrolled = ScrolledWindow::builder()
+        .min_content_height(260)
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+
+    let list_box = ListBox::new();
+    list_box.set_selection_mode(gtk4::SelectionMode::Browse);
+    scrolled.set_child(Some(&list_box));
+    files_page.append(&scrolled);
+
+    if known_choices.is_empty() {
+        let empty_label = Label::new(Some("No known file types available. Use manual input below."));
+        empty_label.set_halign(gtk4::Align::Start);
+        empty_label.add_css_class("dim-label");
+        files_page.append(&empty_label);
+    }
+
+    let select_box = GtkBox::new(Orientation::Horizontal, 6);
+    select_box.set_hexpand(true);
+
+    let hint_label = Label::new(Some("Double-click a row or use \"Add Selected\"."));
+    hint_label.set_halign(gtk4::Align::Start);
+    hint_label.set_hexpand(true);
+    hint_label.add_css_class("dim-label");
+    select_box.append(&hint_label);
+
+    let add_selected_button = Button::with_label("Add Selected");
+    add_selected_button.set_sensitive(false);
+    add_selected_button.set_halign(gtk4::Align::End);
+    select_box.append(&add_selected_button);
+
+    files_page.append(&select_box);
+
+    let manual_box = GtkBox::new(Orientation::Horizontal, 6);
+    manual_box.set_hexpand(true);
+
+    let manual_entry = Entry::builder()
+        .placeholder_text("Enter extension (e.g. .txt) or MIME type")
+        .build();
+    manual_entry.set_hexpand(true);
+    manual_box.append(&manual_entry);
+
+    let manual_add_button = Button::with_label("Add From Text");
+    manual_box.append(&manual_add_button);
+
+    let sample_button = Button::with_label("Pick Sample File...");
+    sample_button.set_tooltip_text(Some("Detect the MIME type from a file's name and contents"));
+    manual_box.append(&sample_button);
+
+    files_page.append(&manual_box);
+
+    for choice in known_choices.iter() {
+        let row = gtk4::ListBoxRow::new();
+        row.set_activatable(true);
+        row.set_selectable(true);
+
+        unsafe {
+            row.set_data("mime-type", choice.mime_type.clone());
+            row.set_data("extension", choice.extension.clone());
+            row.set_data("description", choice.description.clone());
+        }
+
+        let row_box = GtkBox::new(Orientation::Horizontal, 10);
+        row_box.set_hexpand(true);
+
+        let ext_label = Label::new(Some(&choice.extension));
+        ext_label.add_css_class("monospace");
+        ext_label.set_width_chars(8);
+        ext_label.set_halign(gtk4::Align::Start);
+
+        let description_label = Label::new(Some(&choice.description));
+        description_label.set_halign(gtk4::Align::Start);
+        description_label.set_hexpand(true);
+        description_label.set_wrap(true);
+
+        let mime_label = Label::new(Some(choice.mime_type.as_str()));
+        mime_label.add_css_class("dim-label");
+        mime_label.set_halign(gtk4::Align::End);
+
+        row_box.append(&ext_label);
+        row_box.append(&description_label);
+        row_box.append(&mime_label);
+
+        row.set_child(Some(&row_box));
+        list_box.append(&row);
+    }
+
+    let list_box_for_filter = list_box.clone();
+    filter_entry.connect_changed(move |entry| {
+        let query = entry.text().to_lowercase();
+        let mut child = list_box_for_filter.first_child();
+        while let Some(widget) = child {
+            if let Some(row) = widget.downcast_ref::<gtk4::ListBoxRow>() {
+                let extension = row_string_data(row, "extension")
+                    .unwrap_or_default()
+                    .to_lowercase();
+                let description = row_string_data(row, "description")
+                    .unwrap_or_default()
+                    .to_lowercase();
+                let mime = row_string_data(row, "mime-type")
+                    .unwrap_or_default()
+                    .to_lowercase();
+
+                let matches = query.is_empty()
+                    || extension.contains(&query)
+                    || description.contains(&query)
+                    || mime.contains(&query);
+                row.set_visible(matches);
+            }
+            child = widget.next_sibling();
+        }
+    });
+
+    let add_selected_button_clone = add_selected_button.clone();
+    list_box.connect_row_selected(move |_, row| {
+        add_selected_button_clone.set_sensitive(row.is_some());
+    });
+
+    let state_for_activation = mime_state.clone();
+    let list_for_activation = mime_list.clone();
+    let entry_for_activation = current_entry.clone();
+    let map_for_activation = known_map.clone();
+    let extensions_for_activation = extension_state.clone();
+    list_box.connect_row_activated(move |_, row| {
+        add_mime_from_row(
+            row,
+            &state_for_activation,
+            &list_for_activation,
+            &entry_for_activation,
+            &map_for_activation,
+            &extensions_for_activation,
+        );
+    });
+
+    let list_for_button = list_box.clone();
+    let state_for_button = mime_state.clone();
+    let mime_list_for_button = mime_list.clone();
+    let entry_for_button = current_entry.clone();
+    let map_for_button = known_map.clone();
+    let extensions_for_button = extension_state.clone();
+    add_selected_button.connect_clicked(move |_| {
+        if let Some(row) = list_for_button.selected_row() {
+            add_mime_from_row(
+                &row,
+                &state_for_button,
+                &mime_list_for_button,
+                &entry_for_button,
+                &map_for_button,
+                &extensions_for_button,
+            );
+        }
+    });
+
+    let state_for_manual = mime_state.clone();
+    let mime_list_for_manual = mime_list.clone();
+    let entry_for_manual = current_entry.clone();
+    let map_for_manual = known_map.clone();
+    let extensions_for_manual = extension_state.clone();
+    let manual_entry_button = manual_entry.clone();
+    manual_add_button.connect_clicked(move |_| {
+        try_add_manual_mime(
+            &manual_entry_button,
+            &state_for_manual,
+            &mime_list_for_manual,
+            &entry_for_manual,
+            &map_for_manual,
+            &extensions_for_manual,
+        );
+    });
+
+    let state_for_entry = mime_state.clone();
+    let mime_list_for_entry = mime_list.clone();
+    let entry_for_entry = current_entry.clone();
+    let map_for_entry = known_map.clone();
+    let extensions_for_entry = extension_state.clone();
+    manual_entry.connect_activate(move |entry| {
+        try_add_manual_mime(
+            entry,
+            &state_for_entry,
+            &mime_list_for_entry,
+            &entry_for_entry,
+            &map_for_entry,
+            &extensions_for_entry,
+        );
+    });
+
+    let dialog_for_sample = dialog.clone();
+    let state_for_sample = mime_state.clone();
+    let mime_list_for_sample = mime_list.clone();
+    let entry_for_sample = current_entry.clone();
+    let map_for_sample = known_map.clone();
+    let extensions_for_sample = extension_state.clone();
+    sample_button.connect_clicked(move |_| {
+        let chooser = FileChooserDialog::new(
+            Some("Select Sample File"),
+            Some(&dialog_for_sample),
+            FileChooserAction::Open,
+            &[("Cancel", ResponseType::Cancel), ("Open", ResponseType::Accept)],
+        );
+
+        let magic_clone = magic.clone();
+        let state_clone = state_for_sample.clone();
+        let mime_list_clone = mime_list_for_sample.clone();
+        let entry_clone = entry_for_sample.clone();
+        let map_clone = map_for_sample.clone();
+        let extensions_clone = extensions_for_sample.clone();
+        chooser.connect_response(move |chooser, response| {
+            if response == ResponseType::Accept {
+                if let Some(path) = chooser.file().and_then(|file| file.path()) {
+                    match resolve_mime_from_sample(&path, &magic_clone) {
+                        Some((mime, display_hint)) => {
+                            add_resolved_mime(
+                                &mime,
+                                &display_hint,
+                                &state_clone,
+                                &mime_list_clone,
+                                &entry_clone,
+                                &map_clone,
+                                &extensions_clone,
+                            );
+                        }
+                        None => {
+                            println!(
+                                "Unable to determine MIME type of '{}'",
+                                path.display()
+                            );
+                        }
+                    }
+                }
+            }
+            chooser.close();
+        });
+
+        chooser.show();
+    });
+
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show();
+}
+
+fn build_protocols_page(
+    dialog: &gtk4::Dialog,
+    all_entries: Rc<RefCell<Vec<DesktopEntry>>>,
+    mime_state: Rc<RefCell<Vec<String>>>,
+    extension_state: Rc<RefCell<HashMap<String, String>>>,
+    mime_list: &ListBox,
+    current_entry: Rc<RefCell<Option<DesktopEntry>>>,
+    known_map: Rc<RefCell<HashMap<String, String>>>,
+) -> GtkBox {
+    let page = GtkBox::new(Orientation::Vertical, 8);
+    page.set_margin_top(8);
+
+    let filter_entry = Entry::builder()
+        .placeholder_text("Search by scheme or application")
+        .build();
+    page.append(&filter_entry);
+
+    let scrolled = ScrolledWindow::builder()
+        .min_content_height(200)
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    let schemes_list = ListBox::new();
+    schemes_list.set_selection_mode(gtk4::SelectionMode::Browse);
+    scrolled.set_child(Some(&schemes_list));
+    page.append(&scrolled);
+
+    let default_box = GtkBox::new(Orientation::Horizontal, 6);
+    let default_label = Label::new(Some("Default handler:"));
+    let handler_dropdown = gtk4::DropDown::from_strings(&[]);
+    handler_dropdown.set_hexpand(true);
+    let set_default_button = Button::with_label("Set Default");
+    set_default_button.set_sensitive(false);
+    default_box.append(&default_label);
+    default_box.append(&handler_dropdown);
+    default_box.append(&set_default_button);
+    page.append(&default_box);
+
+    let (target_box, target_dropdown, targets) = mimeapps_target_box("Write default to:");
+    page.append(&target_box);
+
+    let test_box = GtkBox::new(Orientation::Horizontal, 6);
+    let test_entry = Entry::builder()
+        .placeholder_text("Sample URI (e.g. mailto:someone@example.com)")
+        .hexpand(true)
+        .build();
+    let test_button = Button::with_label("Test Open");
+    test_box.append(&test_entry);
+    test_box.append(&test_button);
+    page.append(&test_box);
+
+    let add_box = GtkBox::new(Orientation::Horizontal, 6);
+    let scheme_entry = Entry::builder()
+        .placeholder_text("Enter scheme (e.g. zoommtg)")
+        .hexpand(true)
+        .build();
+    let add_selected_button = Button::with_label("Add Selected");
+    add_selected_button.set_sensitive(false);
+    let add_scheme_button = Button::with_label("Add Scheme");
+    add_box.append(&scheme_entry);
+    add_box.append(&add_selected_button);
+    add_box.append(&add_scheme_button);
+    page.append(&add_box);
+
+    let scheme_data: Rc<RefCell<Vec<SchemeHandlers>>> = Rc::new(RefCell::new(Vec::new()));
+
+    let populate: Rc<dyn Fn()> = {
+        let schemes_list = schemes_list.clone();
+        let scheme_data = scheme_data.clone();
+        let all_entries = all_entries.clone();
+        Rc::new(move || {
+            clear_list_box(&schemes_list);
+            let entries = all_entries.borrow();
+            let handlers = scheme_handlers(&entries);
+            let display_name = |id: &str| {
+                entries
+                    .iter()
+                    .find(|entry| entry.desktop_id() == id)
+                    .map(|entry| entry.name.clone())
+                    .unwrap_or_else(|| id.to_string())
+            };
+
+            for info in &handlers {
+                let row = gtk4::ListBoxRow::new();
+                let names: Vec<String> = info.handlers.iter().map(|id| display_name(id)).collect();
+                unsafe {
+                    row.set_data("scheme", info.scheme.clone());
+                    row.set_data("description", names.join(" ").to_lowercase());
+                }
+
+                let row_box = GtkBox::new(Orientation::Horizontal, 10);
+                let scheme_label = Label::new(Some(&format!("{}:", info.scheme)));
+                scheme_label.add_css_class("monospace");
+                scheme_label.set_width_chars(14);
+                scheme_label.set_xalign(0.0);
+
+                let handlers_label = Label::new(Some(&names.join(", ")));
+                handlers_label.set_halign(gtk4::Align::Start);
+                handlers_label.set_hexpand(true);
+                handlers_label.set_wrap(true);
+
+                let default_text = info
+                    .default
+                    .as_deref()
+                    .map(|id| format!("default: {}", display_name(id)))
+                    .unwrap_or_else(|| String::from("no default"));
+                let default_value = Label::new(Some(&default_text));
+                default_value.add_css_class("dim-label");
+                default_value.set_halign(gtk4::Align::End);
+                let source_text = info
+                    .default_source
+                    .as_deref()
+                    .map(|source| format!("Set in {}", source))
+                    .unwrap_or_else(|| String::from("Not set in any mimeapps.list; first handler is used"));
+                default_value.set_tooltip_text(Some(&source_text));
+
+                row_box.append(&scheme_label);
+                row_box.append(&handlers_label);
+                row_box.append(&default_value);
+                row.set_child(Some(&row_box));
+                schemes_list.append(&row);
+            }
+
+            *scheme_data.borrow_mut() = handlers;
+        })
+    };
+    populate();
+
+    let schemes_for_filter = schemes_list.clone();
+    filter_entry.connect_changed(move |entry| {
+        let query = entry.text().to_lowercase();
+        let mut child = schemes_for_filter.first_child();
+        while let Some(widget) = child {
+            if let Some(row) = widget.downcast_ref::<gtk4::ListBoxRow>() {
+                let scheme = row_string_data(row, "scheme").unwrap_or_default();
+                let handlers = row_string_data(row, "description").unwrap_or_default();
+                row.set_visible(query.is_empty() || scheme.contains(&query) || handlers.contains(&query));
+            }
+            child = widget.next_sibling();
+        }
+    });
+
+    let data_for_selection = scheme_data.clone();
+    let dropdown_for_selection = handler_dropdown.clone();
+    let test_entry_for_selection = test_entry.clone();
+    let set_default_for_selection = set_default_button.clone();
+    let add_selected_for_selection = add_selected_button.clone();
+    schemes_list.connect_row_selected(move |_, row| {
+        set_default_for_selection.set_sensitive(row.is_some());
+        add_selected_for_selection.set_sensitive(row.is_some());
+
+        let Some(scheme) = row.and_then(|row| row_string_data(row, "scheme")) else {
+            dropdown_for_selection.set_model(None::<&gio::ListModel>);
+            return;
+        };
+
+        let data = data_for_selection.borrow();
+        if let Some(info) = data.iter().find(|info| info.scheme == scheme) {
+            let ids: Vec<&str> = info.handlers.iter().map(|id| id.as_str()).collect();
+            dropdown_for_selection.set_model(Some(&gtk4::StringList::new(&ids)));
+            if let Some(position) = info
+                .default
+                .as_ref()
+                .and_then(|default| info.handlers.iter().position(|id| id == default))
+            {
+                dropdown_for_selection.set_selected(position as u32);
+            }
+        }
+        test_entry_for_selection.set_text(&format!("{}:", scheme));
+    });
+
+    let schemes_for_default = schemes_list.clone();
+    let dropdown_for_default = handler_dropdown.clone();
+    let target_for_default = target_dropdown.clone();
+    let populate_for_default = populate.clone();
+    set_default_button.connect_clicked(move |_| {
+        let Some(scheme) = schemes_for_default
+            .selected_row()
+            .and_then(|row| row_string_data(&row, "scheme"))
+        else {
+            return;
+        };
+        let Some(desktop_id) = dropdown_for_default
+            .selected_item()
+            .and_downcast::<gtk4::StringObject>()
+            .map(|item| item.string().to_string())
+        else {
+            return;
+        };
+
+        let mime_type = format!("x-scheme-handler/{}", scheme);
+        let Some(path) = targets
+            .get(target_for_default.selected() as usize)
+            .and_then(|target| target.path())
+        else {
+            return;
+        };
+        match set_default_application(&path, &mime_type, &desktop_id) {
+            Ok(_) => {
+                println!("Set {} as default handler for {}: in {}", desktop_id, scheme, path);
+                populate_for_default();
+            }
+            Err(e) => eprintln!("Error writing {}: {}", path, e),
+        }
+    });
+
+    let dialog_for_test = dialog.clone();
+    let test_entry_for_button = test_entry.clone();
+    test_button.connect_clicked(move |_| {
+        let uri = test_entry_for_button.text().trim().to_string();
+        if uri.is_empty() {
+            return;
+        }
+        let context = WidgetExt::display(&dialog_for_test).app_launch_context();
+        if let Err(e) = gio::AppInfo::launch_default_for_uri(&uri, Some(&context)) {
+            eprintln!("Error opening {}: {}", uri, e);
+        }
+    });
+
+    let schemes_for_add = schemes_list.clone();
+    let state_for_add = mime_state.clone();
+    let mime_list_for_add = mime_list.clone();
+    let entry_for_add = current_entry.clone();
+    let map_for_add = known_map.clone();
+    let extensions_for_add = extension_state.clone();
+    add_selected_button.connect_clicked(move |_| {
+        if let Some(scheme) = schemes_for_add
+            .selected_row()
+            .and_then(|row| row_string_data(&row, "scheme"))
+        {
+            add_resolved_mime(
+                &format!("x-scheme-handler/{}", scheme),
+                "",
+                &state_for_add,
+                &mime_list_for_add,
+                &entry_for_add,
+                &map_for_add,
+                &extensions_for_add,
+            );
+        }
+    });
+
+    let scheme_entry_for_add = scheme_entry.clone();
+    let mime_list_for_manual = mime_list.clone();
+    add_scheme_button.connect_clicked(move |_| {
+        let input = scheme_entry_for_add.text().to_string();
+        let scheme = input
+            .trim()
+            .trim_start_matches("x-scheme-handler/")
+            .trim_end_matches(':')
+            .to_lowercase();
+        let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+        if !valid {
+            println!("'{}' is not a valid URI scheme", input.trim());
+            return;
+        }
+
+        add_resolved_mime(
+            &format!("x-scheme-handler/{}", scheme),
+            "",
+            &mime_state,
+            &mime_list_for_manual,
+            &current_entry,
+            &known_map,
+            &extension_state,
+        );
+        scheme_entry_for_add.set_text("");
+    });
+
+    page
+}
+
+// Asks GIO (access(2) underneath) without opening or creating anything, so file monitors on
+// the directory stay quiet. A file that does not exist yet needs a writable directory.
+fn can_write_path(path: &std::path::Path) -> bool {
+    let existing = if path.exists() { Some(path) } else { path.parent() };
+    existing.is_some_and(|path| {
+        gio::File::for_path(path)
+            .query_info("access::can-write", gio::FileQueryInfoFlags::NONE, gio::Cancellable::NONE)
+            .is_ok_and(|info| info.boolean("access::can-write"))
+    })
+}
+
+fn mimeapps_target_dropdown() -> (gtk4::DropDown, Vec<MimeAppsTarget>) {
+    let targets: Vec<MimeAppsTarget> = [
+        MimeAppsTarget::Desktop,
+        MimeAppsTarget::User,
+        MimeAppsTarget::SystemDesktop,
+        MimeAppsTarget::System,
+    ]
+    .into_iter()
+    .filter(|target| match target.path() {
+        Some(path) => !target.is_system() || can_write_path(std::path::Path::new(&path)),
+        None => false,
+    })
+    .collect();
+
+    let labels: Vec<String> = targets.iter().map(|target| target.label()).collect();
+    let label_refs: Vec<&str> = labels.iter().map(|label| label.as_str()).collect();
+    let dropdown = gtk4::DropDown::from_strings(&label_refs);
+    if let Some(position) = targets.iter().position(|target| *target == MimeAppsTarget::User) {
+        dropdown.set_selected(position as u32);
+    }
+    (dropdown, targets)
+}
+
+// The labelled target row used wherever defaults are written, so every dialog offers the
+// same files in the same way
+fn mimeapps_target_box(label: &str) -> (GtkBox, gtk4::DropDown, Vec<MimeAppsTarget>) {
+    let target_box = GtkBox::new(Orientation::Horizontal, 6);
+    let (target_dropdown, targets) = mimeapps_target_dropdown();
+    target_dropdown.set_hexpand(true);
+    target_box.append(&Label::new(Some(label)));
+    target_box.append(&target_dropdown);
+    (target_box, target_dropdown, targets)
+}
+
+fn show_default_applications(parent: &ApplicationWindow, entries: Rc<RefCell<Vec<DesktopEntry>>>) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .title("Default Applications")
+        .default_width(760)
+        .default_height(560)
+        .build();
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    window.set_child(Some(&content));
+
+    let chain_label = Label::new(Some("Lookup order (first match wins):"));
+    chain_label.set_halign(gtk4::Align::Start);
+    content.append(&chain_label);
+
+    for path in mimeapps_list_paths() {
+        let present = std::path::Path::new(&path).exists();
+        let label = Label::new(Some(&format!(
+            "{}  {}",
+            if present { "●" } else { "○" },
+            path
+        )));
+        label.set_halign(gtk4::Align::Start);
+        label.add_css_class("monospace");
+        if !present {
+            label.add_css_class("dim-label");
+        }
+        content.append(&label);
+    }
+
+    let filter_entry = Entry::builder()
+        .placeholder_text("Search by MIME type or application")
+        .build();
+    content.append(&filter_entry);
+
+    let scrolled = ScrolledWindow::builder()
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    let list = ListBox::new();
+    list.set_selection_mode(gtk4::SelectionMode::None);
+    scrolled.set_child(Some(&list));
+    content.append(&scrolled);
+
+    let entries_ref = entries.borrow();
+    for assignment in default_assignments() {
+        let names: Vec<String> = assignment
+            .desktop_ids
+            .iter()
+            .map(|id| {
+                entries_ref
+                    .iter()
+                    .find(|entry| &entry.desktop_id() == id)
+                    .map(|entry| format!("{} ({})", entry.name, id))
+                    .unwrap_or_else(|| format!("{} (not installed)", id))
+            })
+            .collect();
+
+        let row = gtk4::ListBoxRow::new();
+        unsafe {
+            row.set_data("description", format!("{} {}", assignment.mime_type, names.join(" ")).to_lowercase());
+        }
+
+        let row_box = GtkBox::new(Orientation::Vertical, 2);
+        row_box.set_margin_top(4);
+        row_box.set_margin_bottom(4);
+
+        let headline = Label::new(Some(&format!("{} → {}", assignment.mime_type, names.join(", "))));
+        headline.set_halign(gtk4::Align::Start);
+        headline.set_wrap(true);
+
+        let source_text = match &assignment.overridden_by {
+            Some(winner) => format!("{} (overridden by {})", assignment.source, winner),
+            None => assignment.source.clone(),
+        };
+        let source_label = Label::new(Some(&source_text));
+        source_label.set_halign(gtk4::Align::Start);
+        source_label.set_selectable(true);
+        source_label.add_css_class("monospace");
+        source_label.add_css_class("dim-label");
+
+        if assignment.overridden_by.is_some() {
+            headline.add_css_class("dim-label");
+        }
+
+        row_box.append(&headline);
+        row_box.append(&source_label);
+        row.set_child(Some(&row_box));
+        list.append(&row);
+    }
+
+    let list_for_filter = list.clone();
+    filter_entry.connect_changed(move |entry| {
+        let query = entry.text().to_lowercase();
+        let mut child = list_for_filter.first_child();
+        while let Some(widget) = child {
+            if let Some(row) = widget.downcast_ref::<gtk4::ListBoxRow>() {
+                let description = row_string_data(row, "description").unwrap_or_default();
+                row.set_visible(query.is_empty() || description.contains(&query));
+            }
+            child = widget.next_sibling();
+        }
+    });
+
+    window.present();
+}
+
+const MATRIX_MAX_COLUMNS: usize = 40;
+
+// A writable entry and the MIME types staged for it in the matrix
+struct MatrixRow {
+    entry: DesktopEntry,
+    staged: Vec<String>,
+}
+
+fn show_associations_matrix(
+    parent: &ApplicationWindow,
+    all_entries: Rc<RefCell<Vec<DesktopEntry>>>,
+    row_entry_map: Rc<RefCell<HashMap<gtk4::Widget, DesktopEntry>>>,
+) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .title("Associations Matrix")
+        .default_width(960)
+        .default_height(640)
+        .build();
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    window.set_child(Some(&content));
+
+    let filter_box = GtkBox::new(Orientation::Horizontal, 6);
+    let filter_entry = Entry::builder()
+        .placeholder_text("Filter MIME types (e.g. image/ or text)")
+        .hexpand(true)
+        .build();
+    let column_entry = Entry::builder()
+        .placeholder_text("Add MIME type column")
+        .build();
+    let add_column_button = Button::with_label("Add Column");
+    filter_box.append(&filter_entry);
+    filter_box.append(&column_entry);
+    filter_box.append(&add_column_button);
+    content.append(&filter_box);
+
+    let scrolled = ScrolledWindow::builder()
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    content.append(&scrolled);
+
+    let footer = GtkBox::new(Orientation::Horizontal, 6);
+    let status_label = Label::new(None);
+    status_label.set_halign(gtk4::Align::Start);
+    status_label.set_hexpand(true);
+    status_label.add_css_class("dim-label");
+    let (target_box, target_dropdown, targets) = mimeapps_target_box("Write defaults to:");
+    let apply_button = Button::with_label("Apply Changes");
+    apply_button.add_css_class("suggested-action");
+    footer.append(&status_label);
+    footer.append(&apply_button);
+    content.append(&target_box);
+    content.append(&footer);
+
+    let rows: Rc<RefCell<Vec<MatrixRow>>> = Rc::new(RefCell::new(
+        all_entries
+            .borrow()
+            .iter()
+            .filter(|entry| can_write_file(&entry.path))
+            .map(|entry| MatrixRow {
+                entry: entry.clone(),
+                staged: entry.mime_types.clone(),
+            })
+            .collect(),
+    ));
+
+    let current_defaults: Rc<RefCell<HashMap<String, String>>> =
+        Rc::new(RefCell::new(effective_defaults().into_iter().collect()));
+    let staged_defaults: Rc<RefCell<HashMap<String, String>>> = Rc::new(RefCell::new(HashMap::new()));
+
+    let columns: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new({
+        let mut columns: Vec<String> = rows
+            .borrow()
+            .iter()
+            .flat_map(|row| row.entry.mime_types.iter().cloned())
+            .collect();
+        columns.sort();
+        columns.dedup();
+        columns
+    }));
+
+    let update_status: Rc<dyn Fn()> = {
+        let rows = rows.clone();
+        let staged_defaults = staged_defaults.clone();
+        let status_label = status_label.clone();
+        let apply_button = apply_button.clone();
+        Rc::new(move || {
+            let changed_entries = rows
+                .borrow()
+                .iter()
+                .filter(|row| row.entry.mime_types != row.staged)
+                .count();
+            let changed_defaults = staged_defaults.borrow().len();
+            status_label.set_text(&format!(
+                "{} entries and {} defaults changed",
+                changed_entries, changed_defaults
+            ));
+            apply_button.set_sensitive(changed_entries + changed_defaults > 0);
+        })
+    };
+
+    let rebuild: Rc<dyn Fn()> = {
+        let scrolled = scrolled.clone();
+        let filter_entry = filter_entry.clone();
+        let rows = rows.clone();
+        let columns = columns.clone();
+        let current_defaults = current_defaults.clone();
+        let staged_defaults = staged_defaults.clone();
+        let update_status = update_status.clone();
+        Rc::new(move || {
+            let query = filter_entry.text().to_lowercase();
+            let visible: Vec<String> = columns
+                .borrow()
+                .iter()
+                .filter(|mime| query.is_empty() || mime.to_lowercase().contains(&query))
+                .take(MATRIX_MAX_COLUMNS)
+                .cloned()
+                .collect();
+
+            let grid = gtk4::Grid::new();
+            grid.set_row_spacing(4);
+            grid.set_column_spacing(12);
+
+            if rows.borrow().is_empty() {
+                let empty_label = Label::new(Some("No writable desktop entries found."));
+                empty_label.add_css_class("dim-label");
+                grid.attach(&empty_label, 0, 0, 1, 1);
+            }
+
+            for (column, mime) in visible.iter().enumerate() {
+                let header = Label::new(Some(mime));
+                header.set_ellipsize(gtk4::pango::EllipsizeMode::Start);
+                header.set_max_width_chars(12);
+                header.set_valign(gtk4::Align::End);
+                header.set_tooltip_text(Some(mime));
+                grid.attach(&header, column as i32 + 1, 0, 1, 1);
+            }
+
+            let mut radio_groups: Vec<Option<CheckButton>> = vec![None; visible.len()];
+
+            for (row_index, row) in rows.borrow().iter().enumerate() {
+                let entry = &row.entry;
+                let name_label = Label::new(Some(&entry.name));
+                name_label.set_halign(gtk4::Align::Start);
+                name_label.set_tooltip_text(Some(&entry.path.display().to_string()));
+                grid.attach(&name_label, 0, row_index as i32 + 1, 1, 1);
+
+                let desktop_id = entry.desktop_id();
+
+                for (column, mime) in visible.iter().enumerate() {
+                    let cell = GtkBox::new(Orientation::Horizontal, 2);
+
+                    let member_check = CheckButton::new();
+                    member_check.set_active(row.staged.contains(mime));
+                    member_check.set_tooltip_text(Some(&format!("{} handles {}", entry.name, mime)));
+
+                    let default_radio = CheckButton::new();
+                    if let Some(first) = &radio_groups[column] {
+                        default_radio.set_group(Some(first));
+                    } else {
+                        radio_groups[column] = Some(default_radio.clone());
+                    }
+                    let effective_default = staged_defaults
+                        .borrow()
+                        .get(mime)
+                        .cloned()
+                        .or_else(|| current_defaults.borrow().get(mime).cloned());
+                    default_radio.set_active(effective_default.as_deref() == Some(desktop_id.as_str()));
+                    default_radio.set_tooltip_text(Some(&format!("Default for {}", mime)));
+                    // Only an application that handles the type can become its default
+                    default_radio.set_sensitive(row.staged.contains(mime));
+
+                    let rows_for_check = rows.clone();
+                    let staged_for_check = staged_defaults.clone();
+                    let mime_for_check = mime.clone();
+                    let id_for_check = desktop_id.clone();
+                    let radio_for_check = default_radio.clone();
+                    let status_for_check = update_status.clone();
+                    member_check.connect_toggled(move |check| {
+                        if let Some(row) = rows_for_check.borrow_mut().get_mut(row_index) {
+                            row.staged.retain(|existing| existing != &mime_for_check);
+                            if check.is_active() {
+                                row.staged.push(mime_for_check.clone());
+                            }
+                        }
+                        radio_for_check.set_sensitive(check.is_active());
+                        if !check.is_active() {
+                            let mut staged = staged_for_check.borrow_mut();
+                            if staged.get(&mime_for_check) == Some(&id_for_check) {
+                                staged.remove(&mime_for_check);
+                            }
+                            drop(staged);
+                            radio_for_check.set_active(false);
+                        }
+                        status_for_check();
+                    });
+
+                    let staged_for_radio = staged_defaults.clone();
+                    let current_for_radio = current_defaults.clone();
+                    let mime_for_radio = mime.clone();
+                    let id_for_radio = desktop_id.clone();
+                    let status_for_radio = update_status.clone();
+                    default_radio.connect_toggled(move |radio| {
+                        if !radio.is_active() {
+                            return;
+                        }
+                        let mut staged = staged_for_radio.borrow_mut();
+                        if current_for_radio.borrow().get(&mime_for_radio) == Some(&id_for_radio) {
+                            staged.remove(&mime_for_radio);
+                        } else {
+                            staged.insert(mime_for_radio.clone(), id_for_radio.clone());
+                        }
+                        drop(staged);
+                        status_for_radio();
+                    });
+
+                    cell.append(&member_check);
+                    cell.append(&default_radio);
+                    grid.attach(&cell, column as i32 + 1, row_index as i32 + 1, 1, 1);
+                }
+            }
+
+            scrolled.set_child(Some(&grid));
+        })
+    };
+    rebuild();
+    update_status();
+
+    let rebuild_for_filter = rebuild.clone();
+    filter_entry.connect_changed(move |_| rebuild_for_filter());
+
+    let columns_for_add = columns.clone();
+    let rebuild_for_add = rebuild.clone();
+    let column_entry_for_add = column_entry.clone();
+    let add_column = move || {
+        let Some((mime, _)) = resolve_mime_from_input(&column_entry_for_add.text()) else {
+            return;
+        };
+        {
+            let mut columns = columns_for_add.borrow_mut();
+            if !columns.contains(&mime) {
+                columns.insert(0, mime);
+            }
+        }
+        column_entry_for_add.set_text("");
+        rebuild_for_add();
+    };
+    let add_column = Rc::new(add_column);
+    let add_column_for_button = add_column.clone();
+    add_column_button.connect_clicked(move |_| add_column_for_button());
+    column_entry.connect_activate(move |_| add_column());
+
+    apply_button.connect_clicked(move |_| {
+        let mut saved = 0;
+        for row in rows.borrow_mut().iter_mut() {
+            if row.entry.mime_types == row.staged {
+                continue;
+            }
+            let mut updated = row.entry.clone();
+            updated.mime_types = row.staged.clone();
+            match updated.save_mime_types() {
+                Ok(_) => {
+                    saved += 1;
+                    replace_cached_entry(&all_entries, &row_entry_map, &updated);
+                    row.entry = updated;
+                }
+                Err(e) => eprintln!("Error saving {}: {}", row.entry.path.display(), e),
+            }
+        }
+
+        let path = targets
+            .get(target_dropdown.selected() as usize)
+            .and_then(|target| target.path());
+        let mut defaults_written = 0;
+        if let Some(path) = path {
+            let defaults: Vec<(String, String)> = staged_defaults
+                .borrow()
+                .iter()
+                .map(|(mime, desktop_id)| (mime.clone(), desktop_id.clone()))
+                .collect();
+            // One write for the whole batch; on failure everything stays staged for a retry
+            if !defaults.is_empty() {
+                match set_default_applications(&path, &defaults) {
+                    Ok(_) => {
+                        defaults_written = defaults.len();
+                        current_defaults.borrow_mut().extend(staged_defaults.borrow_mut().drain());
+                    }
+                    Err(e) => eprintln!("Error writing {}: {}", path, e),
+                }
+            }
+        }
+
+        println!(
+            "Saved {} entries and {} default applications",
+            saved, defaults_written
+        );
+        update_status();
+    });
+
+    window.present();
+}
+
+fn replace_cached_entry(
+    all_entries: &Rc<RefCell<Vec<DesktopEntry>>>,
+    row_entry_map: &Rc<RefCell<HashMap<gtk4::Widget, DesktopEntry>>>,
+    updated: &DesktopEntry,
+) {
+    for entry in all_entries.borrow_mut().iter_mut() {
+        if entry.path == updated.path {
+            *entry = updated.clone();
+        }
+    }
+    for entry in row_entry_map.borrow_mut().values_mut() {
+        if entry.path == updated.path {
+            *entry = updated.clone();
+        }
+    }
+}
+
+fn show_default_profiles(parent: &ApplicationWindow, entries: Rc<RefCell<Vec<DesktopEntry>>>) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .title("Default Application Profiles")
+        .default_width(720)
+        .default_height(600)
+        .build();
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    window.set_child(Some(&content));
+
+    // Profiles capture and replace the defaults of this one file
+    let (target_box, target_dropdown, targets) = mimeapps_target_box("Defaults file:");
+    content.append(&target_box);
+    let target_path = move || -> Option<String> {
+        targets
+            .get(target_dropdown.selected() as usize)
+            .and_then(|target| target.path())
+    };
+
+    let save_box = GtkBox::new(Orientation::Horizontal, 6);
+    let name_entry = Entry::builder()
+        .placeholder_text("Profile name (e.g. work, presentation)")
+        .hexpand(true)
+        .build();
+    let save_button = Button::with_label("Save Current Defaults");
+    save_box.append(&name_entry);
+    save_box.append(&save_button);
+    content.append(&save_box);
+
+    let profiles_label = Label::new(Some(&format!("Profiles in {}:", profiles_dir().display())));
+    profiles_label.set_halign(gtk4::Align::Start);
+    profiles_label.set_wrap(true);
+    content.append(&profiles_label);
+
+    let profiles_scrolled = ScrolledWindow::builder()
+        .min_content_height(140)
+        .hexpand(true)
+        .build();
+    let profiles_list = ListBox::new();
+    profiles_list.set_selection_mode(gtk4::SelectionMode::Browse);
+    profiles_scrolled.set_child(Some(&profiles_list));
+    content.append(&profiles_scrolled);
+
+    let switch_box = GtkBox::new(Orientation::Horizontal, 6);
+    switch_box.set_halign(gtk4::Align::End);
+    let switch_button = Button::with_label("Switch To Selected");
+    switch_button.add_css_class("suggested-action");
+    let delete_button = Button::with_label("Delete");
+    delete_button.add_css_class("destructive-action");
+    switch_box.append(&switch_button);
+    switch_box.append(&delete_button);
+    content.append(&switch_box);
+
+    let compare_box = GtkBox::new(Orientation::Horizontal, 6);
+    let left_dropdown = gtk4::DropDown::from_strings(&[]);
+    let right_dropdown = gtk4::DropDown::from_strings(&[]);
+    left_dropdown.set_hexpand(true);
+    right_dropdown.set_hexpand(true);
+    let compare_button = Button::with_label("Compare");
+    compare_box.append(&left_dropdown);
+    compare_box.append(&Label::new(Some("vs")));
+    compare_box.append(&right_dropdown);
+    compare_box.append(&compare_button);
+    content.append(&compare_box);
+
+    let diff_scrolled = ScrolledWindow::builder()
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    let diff_list = ListBox::new();
+    diff_list.set_selection_mode(gtk4::SelectionMode::None);
+    diff_scrolled.set_child(Some(&diff_list));
+    content.append(&diff_scrolled);
+
+    // Index 0 of both comparison dropdowns is the live configuration
+    let profiles: Rc<RefCell<Vec<DefaultsProfile>>> = Rc::new(RefCell::new(Vec::new()));
+
+    let reload: Rc<dyn Fn()> = {
+        let profiles = profiles.clone();
+        let profiles_list = profiles_list.clone();
+        let left_dropdown = left_dropdown.clone();
+        let right_dropdown = right_dropdown.clone();
+        Rc::new(move || {
+            clear_list_box(&profiles_list);
+            let loaded = list_profiles();
+
+            for profile in &loaded {
+                let label = Label::new(Some(&format!(
+                    "{} — {} defaults",
+                    profile.name,
+                    profile.defaults.len()
+                )));
+                label.set_halign(gtk4::Align::Start);
+                label.set_tooltip_text(Some(&profile.path().display().to_string()));
+                profiles_list.append(&label);
+            }
+
+            let mut names = vec![String::from("Current defaults")];
+            names.extend(loaded.iter().map(|profile| profile.name.clone()));
+            let name_refs: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+            left_dropdown.set_model(Some(&gtk4::StringList::new(&name_refs)));
+            right_dropdown.set_model(Some(&gtk4::StringList::new(&name_refs)));
+            if names.len() > 1 {
+                right_dropdown.set_selected(1);
+            }
+
+            *profiles.borrow_mut() = loaded;
+        })
+    };
+    reload();
+
+    let selected_profile = {
+        let profiles = profiles.clone();
+        let profiles_list = profiles_list.clone();
+        move || -> Option<DefaultsProfile> {
+            let index = profiles_list.selected_row()?.index();
+            profiles.borrow().get(index as usize).cloned()
+        }
+    };
+
+    let reload_for_save = reload.clone();
+    let target_path_for_save = target_path.clone();
+    save_button.connect_clicked(move |_| {
+        let name = name_entry.text().trim().to_string();
+        if !is_valid_profile_name(&name) {
+            println!("'{}' is not a valid profile name", name);
+            return;
+        }
+
+        let Some(path) = target_path_for_save() else {
+            return;
+        };
+        let profile = DefaultsProfile::capture(&name, &path);
+        match profile.save() {
+            Ok(_) => {
+                println!("Saved {} defaults to {}", profile.defaults.len(), profile.path().display());
+                name_entry.set_text("");
+                reload_for_save();
+            }
+            Err(e) => eprintln!("Error saving profile: {}", e),
+        }
+    });
+
+    let selected_for_switch = selected_profile.clone();
+    let target_path_for_switch = target_path.clone();
+    switch_button.connect_clicked(move |_| {
+        let Some(profile) = selected_for_switch() else {
+            return;
+        };
+        let Some(path) = target_path_for_switch() else {
+            return;
+        };
+
+        match profile.apply(&path) {
+            Ok(_) => println!("Switched to profile '{}' ({})", profile.name, path),
+            Err(e) => eprintln!("Error writing {}: {}", path, e),
+        }
+    });
+
+    let reload_for_delete = reload.clone();
+    delete_button.connect_clicked(move |_| {
+        let Some(profile) = selected_profile() else {
+            return;
+        };
+        match std::fs::remove_file(profile.path()) {
+            Ok(_) => {
+                println!("Deleted profile '{}'", profile.name);
+                reload_for_delete();
+            }
+            Err(e) => eprintln!("Error deleting profile: {}", e),
+        }
+    });
+
+    compare_button.connect_clicked(move |_| {
+        clear_list_box(&diff_list);
+
+        let profile_at = |index: u32| -> Option<DefaultsProfile> {
+            if index == 0 {
+                target_path().map(|path| DefaultsProfile::capture("Current defaults", &path))
+            } else {
+                profiles.borrow().get(index as usize - 1).cloned()
+            }
+        };
+        let (Some(left), Some(right)) = (
+            profile_at(left_dropdown.selected()),
+            profile_at(right_dropdown.selected()),
+        ) else {
+            return;
+        };
+
+        let entries_ref = entries.borrow();
+        let display_name = |id: &Option<String>| match id {
+            Some(id) => entries_ref
+                .iter()
+                .find(|entry| &entry.desktop_id() == id)
+                .map(|entry| entry.name.clone())
+                .unwrap_or_else(|| id.clone()),
+            None => String::from("(not set)"),
+        };
+
+        let differences = left.diff(&right);
+        if differences.is_empty() {
+            let label = Label::new(Some("The profiles set the same defaults."));
+            label.add_css_class("dim-label");
+            diff_list.append(&label);
+        }
+
+        for difference in differences {
+            let row_box = GtkBox::new(Orientation::Horizontal, 10);
+            let mime_label = Label::new(Some(&difference.mime_type));
+            mime_label.add_css_class("monospace");
+            mime_label.set_width_chars(28);
+            mime_label.set_xalign(0.0);
+            mime_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+
+            let left_label = Label::new(Some(&display_name(&difference.left)));
+            left_label.set_hexpand(true);
+            left_label.set_xalign(0.0);
+            let right_label = Label::new(Some(&display_name(&difference.right)));
+            right_label.set_hexpand(true);
+            right_label.set_xalign(0.0);
+
+            row_box.append(&mime_label);
+            row_box.append(&left_label);
+            row_box.append(&right_label);
+            diff_list.append(&row_box);
+        }
+    });
+
+    window.present();
+}
+
+fn resolve_mime_for_target(input: &str, magic: &MagicDatabase) -> Option<String> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    if let Some((scheme, _)) = trimmed.split_once(':') {
+        let is_scheme = scheme.len() > 1
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+        if is_scheme {
+            if scheme.eq_ignore_ascii_case("file") {
+                let path = gio::File::for_uri(trimmed).path()?;
+                return resolve_mime_for_target(&path.display().to_string(), magic);
+            }
+            return Some(format!("x-scheme-handler/{}", scheme.to_lowercase()));
+        }
+    }
+
+    let path = std::path::Path::new(trimmed);
+    if path.exists() {
+        return resolve_mime_from_sample(path, magic).map(|(mime, _)| mime);
+    }
+
+    if trimmed.starts_with('/') || trimmed.starts_with('~') {
+        let extension = path.extension()?.to_str()?;
+        return resolve_mime_from_input(extension).map(|(mime, _)| mime);
+    }
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(extension) if !trimmed.contains('/') => {
+            resolve_mime_from_input(extension).map(|(mime, _)| mime)
+        }
+        _ => resolve_mime_from_input(trimmed).map(|(mime, _)| mime),
+    }
+}
+
+fn show_orphaned_icons(parent: &ApplicationWindow, entries: Rc<RefCell<Vec<DesktopEntry>>>) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .title("Orphaned Icons")
+        .default_width(760)
+        .default_height(600)
+        .build();
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    window.set_child(Some(&content));
+
+    let summary_label = Label::new(None);
+    summary_label.set_halign(gtk4::Align::Start);
+    summary_label.set_wrap(true);
+    content.append(&summary_label);
+
+    let scrolled = ScrolledWindow::builder()
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    let orphan_list = ListBox::new();
+    orphan_list.set_selection_mode(gtk4::SelectionMode::None);
+    scrolled.set_child(Some(&orphan_list));
+    content.append(&scrolled);
+
+    let footer = GtkBox::new(Orientation::Horizontal, 6);
+    let select_all_button = Button::with_label("Select All");
+    let rescan_button = Button::with_label("Rescan");
+    let spacer = GtkBox::new(Orientation::Horizontal, 0);
+    spacer.set_hexpand(true);
+    let trash_button = Button::with_label("Move Selected to Trash");
+    trash_button.add_css_class("destructive-action");
+    footer.append(&select_all_button);
+    footer.append(&rescan_button);
+    footer.append(&spacer);
+    footer.append(&trash_button);
+    content.append(&footer);
+
+    let checks: Rc<RefCell<Vec<(CheckButton, OrphanedIcon)>>> = Rc::new(RefCell::new(Vec::new()));
+
+    let rescan: Rc<dyn Fn()> = {
+        let orphan_list = orphan_list.clone();
+        let summary_label = summary_label.clone();
+        let checks = checks.clone();
+        Rc::new(move || {
+            clear_list_box(&orphan_list);
+            let orphans = orphaned_user_icons(&entries.borrow());
+            let total: u64 = orphans.iter().map(|orphan| orphan.size_bytes).sum();
+            summary_label.set_text(&if orphans.is_empty() {
+                String::from("Every icon in your user icon folders is used by a desktop entry.")
+            } else {
+                format!(
+                    "{} icon files ({}) are not referenced by any desktop entry:",
+                    orphans.len(),
+                    gtk4::glib::format_size(total)
+                )
+            });
+
+            let mut new_checks = Vec::new();
+            for orphan in orphans {
+                let row_box = GtkBox::new(Orientation::Horizontal, 10);
+                let check = CheckButton::new();
+                let image = Image::from_file(&orphan.path);
+                image.set_pixel_size(32);
+                let path_label = Label::new(Some(&orphan.path.display().to_string()));
+                path_label.set_hexpand(true);
+                path_label.set_xalign(0.0);
+                path_label.set_ellipsize(gtk4::pango::EllipsizeMode::Start);
+                path_label.set_tooltip_text(Some(&orphan.path.display().to_string()));
+                let size_label = Label::new(Some(&gtk4::glib::format_size(orphan.size_bytes)));
+                size_label.add_css_class("dim-label");
+
+                row_box.append(&check);
+                row_box.append(&image);
+                row_box.append(&path_label);
+                row_box.append(&size_label);
+                orphan_list.append(&row_box);
+                new_checks.push((check, orphan));
+            }
+            *checks.borrow_mut() = new_checks;
+        })
+    };
+    rescan();
+
+    let checks_for_select = checks.clone();
+    select_all_button.connect_clicked(move |_| {
+        for (check, _) in checks_for_select.borrow().iter() {
+            check.set_active(true);
+        }
+    });
+
+    let rescan_for_button = rescan.clone();
+    rescan_button.connect_clicked(move |_| rescan_for_button());
+
+    trash_button.connect_clicked(move |_| {
+        let mut trashed = 0;
+        let mut touched_hicolor = false;
+        for (check, orphan) in checks.borrow().iter().filter(|(check, _)| check.is_active()) {
+            match gio::File::for_path(&orphan.path).trash(gio::Cancellable::NONE) {
+                Ok(_) => {
+                    trashed += 1;
+                    touched_hicolor |= orphan.path.starts_with(user_hicolor_dir());
+                    check.set_active(false);
+                }
+                Err(e) => eprintln!("Error moving {} to trash: {}", orphan.path.display(), e),
+            }
+        }
+        if touched_hicolor {
+            update_icon_cache(&user_hicolor_dir());
+        }
+        println!("Moved {} icon files to trash", trashed);
+        rescan();
+    });
+
+    window.present();
+}
+
+fn show_test_launch(parent: &ApplicationWindow, entry: DesktopEntry) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .title(format!("Test Launch: {}", entry.name))
+        .default_width(760)
+        .default_height(520)
+        .build();
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    window.set_child(Some(&content));
+
+    let targets_entry = Entry::builder()
+        .placeholder_text("Files or URLs passed for %f, %F, %u and %U (optional)")
+        .hexpand(true)
+        .build();
+    content.append(&targets_entry);
+
+    let command_label = Label::new(None);
+    command_label.set_halign(gtk4::Align::Start);
+    command_label.set_selectable(true);
+    command_label.set_wrap(true);
+    command_label.add_css_class("monospace");
+    content.append(&command_label);
+
+    let working_directory = if entry.working_directory.is_empty() {
+        std::env::var("HOME").unwrap_or_else(|_| String::from("/"))
+    } else {
+        entry.working_directory.clone()
+    };
+    let directory_label = Label::new(Some(&format!(
+        "Working directory: {}{}",
+        working_directory,
+        if entry.working_directory.is_empty() { " (no Path= set)" } else { "" }
+    )));
+    directory_label.set_halign(gtk4::Align::Start);
+    directory_label.add_css_class("dim-label");
+    content.append(&directory_label);
+
+    let controls = GtkBox::new(Orientation::Horizontal, 6);
+    let clean_check = CheckButton::with_label("Clean environment");
+    clean_check.set_tooltip_text(Some(
+        "Only pass the session variables (display, D-Bus, locale, HOME) and a default PATH",
+    ));
+    clean_check.set_hexpand(true);
+    let run_button = Button::with_label("Launch");
+    run_button.add_css_class("suggested-action");
+    let stop_button = Button::with_label("Stop");
+    stop_button.set_sensitive(false);
+    controls.append(&clean_check);
+    controls.append(&run_button);
+    controls.append(&stop_button);
+    content.append(&controls);
+
+    let status_label = Label::new(None);
+    status_label.set_halign(gtk4::Align::Start);
+    status_label.set_selectable(true);
+    content.append(&status_label);
+
+    let log_view = gtk4::TextView::new();
+    log_view.set_editable(false);
+    log_view.set_monospace(true);
+    log_view.set_wrap_mode(gtk4::WrapMode::WordChar);
+    let log_buffer = log_view.buffer();
+    log_buffer.create_tag(Some("stderr"), &[("foreground", &"#c01c28")]);
+    log_buffer.create_tag(Some("info"), &[("style", &gtk4::pango::Style::Italic)]);
+    let log_scrolled = ScrolledWindow::builder()
+        .hexpand(true)
+        .vexpand(true)
+        .child(&log_view)
+        .build();
+    content.append(&log_scrolled);
+
+    let entry = Rc::new(entry);
+    let running: Rc<RefCell<Option<gio::Subprocess>>> = Rc::new(RefCell::new(None));
+
+    let update_command: Rc<dyn Fn()> = {
+        let entry = entry.clone();
+        let targets_entry = targets_entry.clone();
+        let command_label = command_label.clone();
+        let run_button = run_button.clone();
+        let running = running.clone();
+        Rc::new(move || match launch_command(&entry, &targets_entry.text()) {
+            Ok(command) => {
+                let quoted: Vec<String> = command.iter().map(|arg| quote_argument(arg)).collect();
+                command_label.set_text(&quoted.join(" "));
+                command_label.remove_css_class("error");
+                // Editing the targets while the program runs must not allow a second launch
+                run_button.set_sensitive(running.borrow().is_none());
+            }
+            Err(e) => {
+                command_label.set_text(&e);
+                command_label.add_css_class("error");
+                run_button.set_sensitive(false);
+            }
+        })
+    };
+    let update_clone = update_command.clone();
+    targets_entry.connect_changed(move |_| update_clone());
+    update_command();
+
+    let running_clone = running.clone();
+    stop_button.connect_clicked(move |_| {
+        if let Some(process) = running_clone.borrow().as_ref() {
+            process.force_exit();
+        }
+    });
+
+    let stop_button_clone = stop_button.clone();
+    let run_button_clone = run_button.clone();
+    run_button.connect_clicked(move |_| {
+        let Ok(command) = launch_command(&entry, &targets_entry.text()) else {
+            return;
+        };
+        log_buffer.set_text("");
+
+        let launcher = gio::SubprocessLauncher::new(
+            gio::SubprocessFlags::STDOUT_PIPE | gio::SubprocessFlags::STDERR_PIPE,
+        );
+        launcher.set_cwd(&working_directory);
+        if clean_check.is_active() {
+            let environment = clean_environment();
+            let environment_refs: Vec<&std::path::Path> =
+                environment.iter().map(std::path::Path::new).collect();
+            launcher.set_environ(&environment_refs);
+        }
+        if entry.terminal {
+            append_launch_log(&log_view, "Running in a terminal; the program's own output appears there.\n", Some("info"));
+        }
+
+        let argv: Vec<&std::ffi::OsStr> = command.iter().map(std::ffi::OsStr::new).collect();
+        let process = match launcher.spawn(&argv) {
+            Ok(process) => process,
+            Err(e) => {
+                status_label.set_text(&format!("Failed to start: {}", e.message()));
+                status_label.add_css_class("error");
+                return;
+            }
+        };
+        status_label.set_text("Running...");
+        status_label.remove_css_class("error");
+        run_button_clone.set_sensitive(false);
+        stop_button_clone.set_sensitive(true);
+        *running.borrow_mut() = Some(process.clone());
+
+        for (pipe, tag) in [(process.stdout_pipe(), None), (process.stderr_pipe(), Some("stderr"))] {
+            let Some(pipe) = pipe else {
+                continue;
+            };
+            let log_view = log_view.clone();
+            gtk4::glib::spawn_future_local(async move {
+                // Chunks can end inside a UTF-8 sequence, so incomplete bytes wait for the next one
+                let mut pending: Vec<u8> = Vec::new();
+                while let Ok(bytes) = pipe.read_bytes_future(4096, gtk4::glib::Priority::DEFAULT).await {
+                    if bytes.is_empty() {
+                        break;
+                    }
+                    pending.extend_from_slice(&bytes);
+                    let complete = match std::str::from_utf8(&pending) {
+                        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                        _ => pending.len(),
+                    };
+                    let text: Vec<u8> = pending.drain(..complete).collect();
+                    append_launch_log(&log_view, &String::from_utf8_lossy(&text), tag);
+                }
+                if !pending.is_empty() {
+                    append_launch_log(&log_view, &String::from_utf8_lossy(&pending), tag);
+                }
+            });
+        }
+
+        let started = std::time::Instant::now();
+        let status_label = status_label.clone();
+        let running = running.clone();
+        let stop_button = stop_button_clone.clone();
+        let log_view = log_view.clone();
+        let update_command = update_command.clone();
+        process.wait_async(gio::Cancellable::NONE, move |result| {
+            let elapsed = started.elapsed().as_secs_f64();
+            let outcome = match (result, running.borrow().as_ref()) {
+                (Err(e), _) => format!("Waiting for the process failed: {}", e.message()),
+                (Ok(()), Some(process)) if process.has_exited() => {
+                    format!("Exited with code {} after {:.2} s", process.exit_status(), elapsed)
+                }
+                (Ok(()), Some(process)) if process.has_signaled() => {
+                    format!("Killed by signal {} after {:.2} s", process.term_sig(), elapsed)
+                }
+                (Ok(()), _) => format!("Finished after {:.2} s", elapsed),
+            };
+            append_launch_log(&log_view, &format!("{}\n", outcome), Some("info"));
+            status_label.set_text(&outcome);
+            *running.borrow_mut() = None;
+            stop_button.set_sensitive(false);
+            update_command();
+        });
+    });
+
+    window.present();
+}
+
+// A theme icon named after the program if there is one, else an image shipped with it
+fn orphan_launcher_icon(orphan: &OrphanProgram, resolver: &IconResolver) -> String {
+    let file_name = orphan
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let themed = [file_name, orphan.name.to_lowercase().replace(' ', "-")]
+        .into_iter()
+        .find(|name| !name.is_empty() && resolver.icon_exists(name));
+    themed
+        .or_else(|| orphan.icon_file.as_ref().map(|file| file.display().to_string()))
+        .unwrap_or_else(|| String::from("application-x-executable"))
+}
+
+fn show_orphan_programs(
+    parent: &ApplicationWindow,
+    entries: Rc<RefCell<Vec<DesktopEntry>>>,
+    resolver: Rc<IconResolver>,
+    start_new_entry: Rc<dyn Fn(DesktopEntry)>,
+) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .title("Orphan Programs")
+        .default_width(760)
+        .default_height(600)
+        .build();
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    window.set_child(Some(&content));
+
+    let summary_label = Label::new(None);
+    summary_label.set_halign(gtk4::Align::Start);
+    summary_label.set_wrap(true);
+    content.append(&summary_label);
+
+    let scrolled = ScrolledWindow::builder()
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    let orphan_list = ListBox::new();
+    orphan_list.set_selection_mode(gtk4::SelectionMode::None);
+    scrolled.set_child(Some(&orphan_list));
+    content.append(&scrolled);
 
-    entry.set_text("");
-}
+    let footer = GtkBox::new(Orientation::Horizontal, 6);
+    let rescan_button = Button::with_label("Rescan");
+    footer.append(&rescan_button);
+    content.append(&footer);
+
+    let rescan: Rc<dyn Fn()> = {
+        let window = window.clone();
+        let orphan_list = orphan_list.clone();
+        let summary_label = summary_label.clone();
+        Rc::new(move || {
+            clear_list_box(&orphan_list);
+            let orphans = orphan_programs(&entries.borrow());
+            summary_label.set_text(&if orphans.is_empty() {
+                String::from(
+                    "Every program in ~/.local/bin, /opt/*/bin and /usr/local/bin, and every AppImage found, has a launcher.",
+                )
+            } else {
+                format!("{} programs are not run by any desktop entry:", orphans.len())
+            });
 
-fn append_mime_row(
-    list: &ListBox,
-    mime_type: &str,
-    extension: Option<&str>,
-    state: Rc<RefCell<Vec<String>>>,
-    extensions_state: Rc<RefCell<HashMap<String, String>>>,
-) {
-    let row = gtk4::ListBoxRow::new();
-    row.set_selectable(false);
-    row.set_activatable(false);
+            for orphan in orphans {
+                let icon = orphan_launcher_icon(&orphan, &resolver);
+                let row_box = GtkBox::new(Orientation::Horizontal, 10);
+                let image = resolved_icon_image(&icon, &resolver);
+                image.set_pixel_size(32);
+
+                let text_box = GtkBox::new(Orientation::Vertical, 2);
+                text_box.set_hexpand(true);
+                let name_label = Label::new(Some(&orphan.name));
+                name_label.set_xalign(0.0);
+                let path_label = Label::new(Some(&orphan.path.display().to_string()));
+                path_label.set_xalign(0.0);
+                path_label.set_ellipsize(gtk4::pango::EllipsizeMode::Start);
+                path_label.set_tooltip_text(Some(&orphan.path.display().to_string()));
+                path_label.add_css_class("dim-label");
+                text_box.append(&name_label);
+                text_box.append(&path_label);
+                if !orphan.executable {
+                    let warning_label = Label::new(Some("Not executable: mark it executable before launching"));
+                    warning_label.set_xalign(0.0);
+                    warning_label.add_css_class("warning");
+                    text_box.append(&warning_label);
+                }
 
-    let row_box = GtkBox::new(Orientation::Horizontal, 5);
+                row_box.append(&image);
+                row_box.append(&text_box);
+                if orphan.appimage {
+                    let kind_label = Label::new(Some("AppImage"));
+                    kind_label.add_css_class("dim-label");
+                    row_box.append(&kind_label);
+                }
 
-    let extension_owned = extension.unwrap_or("").to_string();
-    let ext_display = if extension_owned.is_empty() {
-        "—"
-    } else {
-        extension_owned.as_str()
+                let create_button = Button::with_label("Create Launcher");
+                create_button.set_valign(gtk4::Align::Center);
+                let window = window.clone();
+                let start_new_entry = start_new_entry.clone();
+                create_button.connect_clicked(move |_| {
+                    let slug = file_name_slug(&orphan.name);
+                    let base = if slug.is_empty() { "new-application" } else { &slug };
+                    let exec = quote_exec_argument(&orphan.path.to_string_lossy());
+                    let mut entry = new_desktop_entry(base, &orphan.name, &exec, &icon);
+                    let mut counter = 2;
+                    while entry.path.exists() {
+                        entry = new_desktop_entry(&format!("{}-{}", base, counter), &orphan.name, &exec, &icon);
+                        counter += 1;
+                    }
+                    start_new_entry(entry);
+                    window.close();
+                });
+                row_box.append(&create_button);
+                orphan_list.append(&row_box);
+            }
+        })
     };
+    rescan();
 
-    let ext_label = Label::new(Some(ext_display));
-    ext_label.add_css_class("monospace");
-    ext_label.set_width_chars(6);
-    ext_label.set_halign(gtk4::Align::Start);
+    rescan_button.connect_clicked(move |_| rescan());
 
-    let label = Label::new(Some(mime_type));
-    label.set_halign(gtk4::Align::Start);
-    label.set_hexpand(true);
-    label.set_wrap(true);
+    window.present();
+}
 
-    let remove_button = Button::with_label("Remove");
-    remove_button.add_css_class("flat");
-    remove_button.set_halign(gtk4::Align::End);
+fn append_launch_log(log_view: &gtk4::TextView, text: &str, tag: Option<&str>) {
+    let buffer = log_view.buffer();
+    let mut end = buffer.end_iter();
+    match tag {
+        Some(tag) => buffer.insert_with_tags_by_name(&mut end, text, &[tag]),
+        None => buffer.insert(&mut end, text),
+    }
+    let mark = buffer.create_mark(None, &buffer.end_iter(), false);
+    log_view.scroll_mark_onscreen(&mark);
+    buffer.delete_mark(&mark);
+}
 
-    row_box.append(&ext_label);
-    row_box.append(&label);
-    row_box.append(&remove_button);
-    row.set_child(Some(&row_box));
-    list.append(&row);
+// Rough widths launchers give their labels before truncating, in characters
+const LAUNCHER_GRID_NAME_CHARS: usize = 16;
+const LAUNCHER_MENU_SUBTITLE_CHARS: usize = 42;
+const LAUNCHER_TOOLTIP_CHARS: i32 = 40;
 
-    unsafe {
-        row.set_data("mime-type", mime_type.to_string());
-        row.set_data("extension", extension_owned.clone());
+fn show_launcher_preview(parent: &ApplicationWindow, info: LauncherInfo, resolver: Rc<IconResolver>) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .title("Launcher Preview")
+        .default_width(720)
+        .default_height(560)
+        .build();
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    window.set_child(Some(&content));
+
+    // The environment's locale first, then the untranslated values, then each translation in the file.
+    // A C or POSIX environment already shows the untranslated values.
+    let environment_locale = current_locale();
+    let mut locales = vec![environment_locale.clone()];
+    if !locale_variants(&environment_locale).is_empty() {
+        locales.push(String::from("C"));
+    }
+    for locale in info.locales() {
+        if !locales.contains(&locale) {
+            locales.push(locale);
+        }
     }
+    let locale_labels: Vec<String> = locales
+        .iter()
+        .map(|locale| {
+            if locale == &environment_locale {
+                format!("{} (current)", locale)
+            } else if locale == "C" {
+                String::from("Untranslated")
+            } else {
+                locale.clone()
+            }
+        })
+        .collect();
+    let locale_refs: Vec<&str> = locale_labels.iter().map(|label| label.as_str()).collect();
 
-    let mime_value = mime_type.to_string();
-    let state_clone = state.clone();
-    let extensions_state_clone = extensions_state.clone();
-    let list_weak = Downgrade::downgrade(&list);
-    let row_weak = Downgrade::downgrade(&row);
+    let locale_box = GtkBox::new(Orientation::Horizontal, 6);
+    locale_box.append(&Label::new(Some("Locale:")));
+    let locale_dropdown = gtk4::DropDown::from_strings(&locale_refs);
+    locale_box.append(&locale_dropdown);
+    content.append(&locale_box);
 
-    remove_button.connect_clicked(move |_| {
-        state_clone
-            .borrow_mut()
-            .retain(|existing| existing != &mime_value);
-        extensions_state_clone.borrow_mut().remove(&mime_value);
+    let scrolled = ScrolledWindow::builder()
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    let mockups_box = GtkBox::new(Orientation::Vertical, 12);
+    scrolled.set_child(Some(&mockups_box));
+    content.append(&scrolled);
 
-        if let (Some(list_box), Some(row_widget)) = (list_weak.upgrade(), row_weak.upgrade()) {
-            list_box.remove(&row_widget);
-        }
-    });
+    let render: Rc<dyn Fn()> = {
+        let locale_dropdown = locale_dropdown.clone();
+        Rc::new(move || {
+            while let Some(child) = mockups_box.first_child() {
+                mockups_box.remove(&child);
+            }
+            let locale = locales
+                .get(locale_dropdown.selected() as usize)
+                .cloned()
+                .unwrap_or_else(|| String::from("C"));
+            render_launcher_mockups(&mockups_box, &info, &locale, &resolver);
+        })
+    };
+
+    let render_clone = render.clone();
+    locale_dropdown.connect_selected_notify(move |_| render_clone());
+    render();
+
+    window.present();
 }
 
-fn resolve_mime_from_input(input: &str) -> Option<(String, String)> {
-    let trimmed = input.trim();
-    if trimmed.is_empty() {
-        return None;
+fn launcher_section(container: &GtkBox, title: &str) -> GtkBox {
+    let heading = Label::new(Some(title));
+    heading.set_halign(gtk4::Align::Start);
+    heading.add_css_class("heading");
+    container.append(&heading);
+
+    let section = GtkBox::new(Orientation::Horizontal, 12);
+    section.set_margin_start(12);
+    container.append(&section);
+    section
+}
+
+fn render_launcher_mockups(container: &GtkBox, info: &LauncherInfo, locale: &str, resolver: &IconResolver) {
+    let (name, name_locale) = info.name.resolve(locale);
+    let (generic_name, generic_locale) = info.generic_name.resolve(locale);
+    let (comment, comment_locale) = info.comment.resolve(locale);
+    let translating = !locale_variants(locale).is_empty();
+    let mut warnings: Vec<String> = Vec::new();
+
+    for (key, value, matched) in [
+        ("Name", &info.name, &name_locale),
+        ("GenericName", &info.generic_name, &generic_locale),
+        ("Comment", &info.comment, &comment_locale),
+    ] {
+        if translating && !value.default.is_empty() && matched.is_none() {
+            warnings.push(format!("{} has no translation for {}; the untranslated text is shown", key, locale));
+        }
     }
 
-    if trimmed.contains('/') {
-        return Some((trimmed.to_string(), String::new()));
+    // GNOME-style app grid tile: large icon, one ellipsized line of Name
+    let grid_section = launcher_section(container, "App grid tile");
+    let tile = GtkBox::new(Orientation::Vertical, 6);
+    tile.set_size_request(128, -1);
+    tile.add_css_class("card");
+    tile.set_margin_top(4);
+    tile.set_margin_bottom(4);
+    let tile_icon = resolved_icon_image(&info.icon, resolver);
+    tile_icon.set_pixel_size(96);
+    tile_icon.set_margin_top(8);
+    tile.append(&tile_icon);
+    let tile_label = Label::new(Some(&name));
+    tile_label.set_max_width_chars(LAUNCHER_GRID_NAME_CHARS as i32);
+    tile_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+    tile_label.set_margin_bottom(8);
+    tile.append(&tile_label);
+    grid_section.append(&tile);
+    if name.chars().count() > LAUNCHER_GRID_NAME_CHARS {
+        warnings.push(format!(
+            "The name is {} characters; app grids cut it after about {}",
+            name.chars().count(),
+            LAUNCHER_GRID_NAME_CHARS
+        ));
     }
 
-    let sanitized = trimmed.trim_start_matches('.');
-    if sanitized.is_empty() {
-        return None;
+    // KDE/XFCE-style menu row: Name with GenericName, or Comment, underneath
+    let menu_section = launcher_section(container, "Menu row");
+    let subtitle = if generic_name.is_empty() { comment.clone() } else { generic_name.clone() };
+    let row = create_list_row(&name, &subtitle, &info.icon, resolver);
+    row.set_halign(gtk4::Align::Start);
+    // The subtitle is the last label of the text column
+    if let Some(subtitle_label) = row
+        .last_child()
+        .and_then(|text_box| text_box.last_child())
+        .and_then(|label| label.downcast::<Label>().ok())
+    {
+        subtitle_label.set_max_width_chars(LAUNCHER_MENU_SUBTITLE_CHARS as i32);
+    }
+    menu_section.append(&row);
+    if subtitle.chars().count() > LAUNCHER_MENU_SUBTITLE_CHARS {
+        warnings.push(format!(
+            "The menu description is {} characters; menus show about {}",
+            subtitle.chars().count(),
+            LAUNCHER_MENU_SUBTITLE_CHARS
+        ));
     }
 
-    let filename = format!("dummy.{}", sanitized);
-    let path = std::path::Path::new(&filename);
-    let (content_type, uncertain) = gio::content_type_guess(Some(path), &[]);
+    // Dock: tooltip on hover and the Desktop Actions of the right-click menu
+    let dock_section = launcher_section(container, "Dock tooltip and right-click menu");
+    let dock_item = GtkBox::new(Orientation::Vertical, 4);
+    let dock_icon = resolved_icon_image(&info.icon, resolver);
+    dock_icon.set_pixel_size(48);
+    dock_item.append(&dock_icon);
+    let tooltip = GtkBox::new(Orientation::Vertical, 2);
+    tooltip.add_css_class("card");
+    let tooltip_name = Label::new(Some(&name));
+    tooltip_name.add_css_class("heading");
+    tooltip_name.set_margin_start(8);
+    tooltip_name.set_margin_end(8);
+    tooltip_name.set_margin_top(6);
+    tooltip.append(&tooltip_name);
+    let tooltip_comment = Label::new(Some(&comment));
+    tooltip_comment.set_wrap(true);
+    tooltip_comment.set_max_width_chars(LAUNCHER_TOOLTIP_CHARS);
+    tooltip_comment.set_margin_start(8);
+    tooltip_comment.set_margin_end(8);
+    tooltip_comment.set_margin_bottom(6);
+    tooltip_comment.add_css_class("dim-label");
+    tooltip_comment.set_visible(!comment.is_empty());
+    tooltip.append(&tooltip_comment);
+    dock_item.append(&tooltip);
+    dock_section.append(&dock_item);
+
+    let actions_menu = GtkBox::new(Orientation::Vertical, 2);
+    actions_menu.add_css_class("card");
+    actions_menu.set_valign(gtk4::Align::Start);
+    for action in &info.actions {
+        if action.missing_group {
+            warnings.push(format!(
+                "Actions lists '{}' but there is no [Desktop Action {}] group",
+                action.id, action.id
+            ));
+            continue;
+        }
+        let (action_name, action_locale) = action.name.resolve(locale);
+        if action_name.is_empty() {
+            warnings.push(format!("Desktop Action '{}' has no Name, so it is not shown", action.id));
+            continue;
+        }
+        if translating && action_locale.is_none() {
+            warnings.push(format!(
+                "Desktop Action '{}' has no translated Name for {}",
+                action.id, locale
+            ));
+        }
 
-    if let Some(mime) = gio::content_type_get_mime_type(content_type.as_str()) {
-        return Some((mime.to_string(), format!(".{}", sanitized)));
+        let action_row = GtkBox::new(Orientation::Horizontal, 8);
+        action_row.set_margin_start(8);
+        action_row.set_margin_end(8);
+        action_row.set_margin_top(4);
+        action_row.set_margin_bottom(4);
+        if !action.icon.is_empty() {
+            let action_icon = resolved_icon_image(&action.icon, resolver);
+            action_icon.set_pixel_size(16);
+            action_row.append(&action_icon);
+        }
+        action_row.append(&Label::new(Some(&action_name)));
+        actions_menu.append(&action_row);
     }
-
-    if !uncertain {
-        return Some((content_type.to_string(), format!(".{}", sanitized)));
+    if actions_menu.first_child().is_none() {
+        let none_label = Label::new(Some("No Desktop Actions; only the dock's own items are shown"));
+        none_label.add_css_class("dim-label");
+        none_label.set_margin_start(8);
+        none_label.set_margin_end(8);
+        none_label.set_margin_top(6);
+        none_label.set_margin_bottom(6);
+        actions_menu.append(&none_label);
     }
+    dock_section.append(&actions_menu);
 
-    Some((
-        format!("application/x-{}", sanitized.to_lowercase()),
-        format!(".{}", sanitized),
-    ))
-}
+    if !info.icon.is_empty() && !resolver.icon_exists(&info.icon) {
+        warnings.push(format!("Icon '{}' was not found in the current icon theme", info.icon));
+    }
 
-fn build_mime_extension_map(choices: &[MimeChoice]) -> HashMap<String, String> {
-    let mut map = HashMap::new();
-    for choice in choices {
-        map.entry(choice.mime_type.clone())
-            .or_insert_with(|| choice.extension.clone());
+    let warnings_heading = Label::new(Some(if warnings.is_empty() {
+        "No truncation or translation problems found"
+    } else {
+        "Problems"
+    }));
+    warnings_heading.set_halign(gtk4::Align::Start);
+    warnings_heading.add_css_class("heading");
+    container.append(&warnings_heading);
+    for warning in warnings {
+        let label = Label::new(Some(&format!("• {}", warning)));
+        label.set_halign(gtk4::Align::Start);
+        label.set_wrap(true);
+        label.add_css_class("warning");
+        container.append(&label);
     }
-    map
 }
 
-fn load_known_extensions() -> Vec<MimeChoice> {
-    let mut map: HashMap<String, (String, u32)> = HashMap::new();
+fn show_menu_explainer(parent: &ApplicationWindow, initial: Option<std::path::PathBuf>) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .title("Why Isn't This App in My Menu?")
+        .default_width(760)
+        .default_height(520)
+        .build();
 
-    for path in mime_database_paths() {
-        if let Ok(file) = File::open(&path) {
-            let reader = BufReader::new(file);
-            for line in reader.lines().flatten() {
-                let trimmed = line.trim();
-                if trimmed.is_empty() || trimmed.starts_with('#') {
-                    continue;
-                }
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    window.set_child(Some(&content));
 
-                let mut parts = trimmed.splitn(3, ':');
-                let weight_str = parts.next().unwrap_or("0");
-                let mime = parts.next().unwrap_or("").trim();
-                let pattern = parts.next().unwrap_or("").trim();
+    let input_box = GtkBox::new(Orientation::Horizontal, 6);
+    let target_entry = Entry::builder()
+        .placeholder_text("Desktop file path or desktop ID, e.g. org.gnome.Calculator.desktop")
+        .hexpand(true)
+        .build();
+    let browse_button = Button::with_label("Browse...");
+    let explain_button = Button::with_label("Explain");
+    input_box.append(&target_entry);
+    input_box.append(&browse_button);
+    input_box.append(&explain_button);
+    content.append(&input_box);
+
+    let verdict_label = Label::new(Some(
+        "Pick a desktop file to see every check that decides whether it appears in the menu.",
+    ));
+    verdict_label.set_halign(gtk4::Align::Start);
+    verdict_label.set_selectable(true);
+    verdict_label.set_wrap(true);
+    content.append(&verdict_label);
+
+    let checks_scrolled = ScrolledWindow::builder()
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    let checks_list = ListBox::new();
+    checks_list.set_selection_mode(gtk4::SelectionMode::None);
+    checks_scrolled.set_child(Some(&checks_list));
+    content.append(&checks_scrolled);
+
+    let run_explanation: Rc<dyn Fn(&str)> = {
+        let verdict_label = verdict_label.clone();
+        let checks_list = checks_list.clone();
+        Rc::new(move |input: &str| {
+            clear_list_box(&checks_list);
+
+            // A bare desktop ID is looked up the same way menus find it
+            let input = input.trim();
+            let path = if input.contains('/') {
+                Some(std::path::PathBuf::from(input))
+            } else {
+                let id = if input.ends_with(".desktop") {
+                    input.to_string()
+                } else {
+                    format!("{}.desktop", input)
+                };
+                desktop_id_owner(&id)
+            };
+            let Some(path) = path else {
+                verdict_label.set_text(&format!("No desktop file provides the ID '{}'", input));
+                return;
+            };
 
-                if mime.is_empty() || pattern.is_empty() {
-                    continue;
+            let explanation = explain_menu_visibility(&path);
+            let blocking = explanation.blocking();
+            let mut verdict = match (&explanation.desktop_id, blocking.first()) {
+                (_, Some(reason)) => format!("Not in the menu: {}", reason.message),
+                (Some(id), None) if explanation.shown_in.is_empty() => {
+                    format!("{} passes every check but is not placed by the menu layout", id)
+                }
+                (_, None) => format!(
+                    "Shown in: {}",
+                    explanation
+                        .shown_in
+                        .iter()
+                        .map(|menu_path| {
+                            if menu_path.is_empty() {
+                                String::from("(top level)")
+                            } else {
+                                menu_path.join(" / ")
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            };
+            if let Some(reason) = blocking.first() {
+                verdict.push_str(&format!("\n{}    {}", reason.source, reason.line));
+            }
+            verdict_label.set_text(&verdict);
+
+            for check in &explanation.checks {
+                let row_box = GtkBox::new(Orientation::Vertical, 2);
+                row_box.set_margin_top(4);
+                row_box.set_margin_bottom(4);
+
+                let headline = Label::new(Some(&format!("{} — {}", check.status.label(), check.message)));
+                headline.set_halign(gtk4::Align::Start);
+                headline.set_wrap(true);
+                match check.status {
+                    CheckStatus::Fail => headline.add_css_class("error"),
+                    CheckStatus::Warning => headline.add_css_class("warning"),
+                    CheckStatus::Pass => {}
                 }
 
-                if let Some(extension) = extract_extension_from_pattern(pattern) {
-                    let weight = weight_str.parse::<u32>().unwrap_or(0);
-                    let entry = map
-                        .entry(extension.clone())
-                        .or_insert_with(|| (mime.to_string(), weight));
+                let source_label = Label::new(Some(&format!("{}    {}", check.source, check.line)));
+                source_label.set_halign(gtk4::Align::Start);
+                source_label.set_selectable(true);
+                source_label.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+                source_label.add_css_class("monospace");
+                source_label.add_css_class("dim-label");
 
-                    if weight >= entry.1 {
-                        *entry = (mime.to_string(), weight);
-                    }
-                }
+                row_box.append(&headline);
+                row_box.append(&source_label);
+                checks_list.append(&row_box);
             }
-        }
-    }
+        })
+    };
 
-    let mut choices = Vec::new();
-    for (extension, (mime, _weight)) in map {
-        let description = mime_description(&mime);
-        choices.push(MimeChoice {
-            extension: format!(".{}", extension),
-            mime_type: mime,
-            description,
+    let run_for_button = run_explanation.clone();
+    let entry_for_button = target_entry.clone();
+    explain_button.connect_clicked(move |_| {
+        run_for_button(&entry_for_button.text());
+    });
+
+    let run_for_entry = run_explanation.clone();
+    target_entry.connect_activate(move |entry| {
+        run_for_entry(&entry.text());
+    });
+
+    let window_for_browse = window.clone();
+    let entry_for_browse = target_entry.clone();
+    let run_for_browse = run_explanation.clone();
+    browse_button.connect_clicked(move |_| {
+        let chooser = FileChooserDialog::new(
+            Some("Select Desktop File"),
+            Some(&window_for_browse),
+            FileChooserAction::Open,
+            &[("Cancel", ResponseType::Cancel), ("Open", ResponseType::Accept)],
+        );
+        let filter = FileFilter::new();
+        filter.set_name(Some("Desktop files"));
+        filter.add_pattern("*.desktop");
+        chooser.add_filter(&filter);
+
+        let entry_clone = entry_for_browse.clone();
+        let run_clone = run_for_browse.clone();
+        chooser.connect_response(move |chooser, response| {
+            if response == ResponseType::Accept {
+                if let Some(path) = chooser.file().and_then(|file| file.path()) {
+                    let path_str = path.display().to_string();
+                    entry_clone.set_text(&path_str);
+                    run_clone(&path_str);
+                }
+            }
+            chooser.close();
         });
+
+        chooser.show();
+    });
+
+    if let Some(path) = initial {
+        let path_str = path.display().to_string();
+        target_entry.set_text(&path_str);
+        run_explanation(&path_str);
     }
 
-    choices.sort_by(|a, b| a.extension.cmp(&b.extension));
-    choices
+    window.present();
 }
 
-fn mime_database_paths() -> Vec<String> {
-    let mut paths = Vec::new();
+fn show_open_with_resolver(
+    parent: &ApplicationWindow,
+    entries: Rc<RefCell<Vec<DesktopEntry>>>,
+    magic: Rc<MagicDatabase>,
+) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .title("Which App Opens This File?")
+        .default_width(760)
+        .default_height(600)
+        .build();
 
-    if let Ok(xdg_data_home) = env::var("XDG_DATA_HOME") {
-        if !xdg_data_home.is_empty() {
-            push_unique_path(&mut paths, format!(
-                "{}/mime/globs2",
-                xdg_data_home.trim_end_matches('/')
-            ));
-        }
-    }
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    window.set_child(Some(&content));
 
-    if let Ok(home) = env::var("HOME") {
-        push_unique_path(&mut paths, format!("{}/.local/share/mime/globs2", home));
-    }
+    let input_box = GtkBox::new(Orientation::Horizontal, 6);
+    let target_entry = Entry::builder()
+        .placeholder_text("Drop a file here, or enter a path, URI, extension or MIME type")
+        .hexpand(true)
+        .build();
+    let browse_button = Button::with_label("Browse...");
+    let resolve_button = Button::with_label("Resolve");
+    input_box.append(&target_entry);
+    input_box.append(&browse_button);
+    input_box.append(&resolve_button);
+    content.append(&input_box);
+
+    let summary_label = Label::new(Some("Pick a file to see how its application is chosen."));
+    summary_label.set_halign(gtk4::Align::Start);
+    summary_label.set_selectable(true);
+    summary_label.set_wrap(true);
+    content.append(&summary_label);
+
+    let candidates_label = Label::new(Some("Candidate applications (in order):"));
+    candidates_label.set_halign(gtk4::Align::Start);
+    content.append(&candidates_label);
+
+    let candidates_scrolled = ScrolledWindow::builder()
+        .min_content_height(120)
+        .hexpand(true)
+        .build();
+    let candidates_list = ListBox::new();
+    candidates_list.set_selection_mode(gtk4::SelectionMode::None);
+    candidates_scrolled.set_child(Some(&candidates_list));
+    content.append(&candidates_scrolled);
+
+    let steps_label = Label::new(Some("How each association was decided:"));
+    steps_label.set_halign(gtk4::Align::Start);
+    content.append(&steps_label);
+
+    let steps_scrolled = ScrolledWindow::builder()
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    let steps_list = ListBox::new();
+    steps_list.set_selection_mode(gtk4::SelectionMode::None);
+    steps_scrolled.set_child(Some(&steps_list));
+    content.append(&steps_scrolled);
+
+    let hierarchy = Rc::new(MimeHierarchy::load());
+
+    let run_resolution: Rc<dyn Fn(&str)> = {
+        let summary_label = summary_label.clone();
+        let candidates_list = candidates_list.clone();
+        let steps_list = steps_list.clone();
+        Rc::new(move |input: &str| {
+            clear_list_box(&candidates_list);
+            clear_list_box(&steps_list);
+
+            let Some(mime_type) = resolve_mime_for_target(input, &magic) else {
+                summary_label.set_text(&format!("Unable to determine MIME type of '{}'", input.trim()));
+                return;
+            };
+
+            let entries_ref = entries.borrow();
+            let resolution = resolve_mime_type(&mime_type, &hierarchy, &entries_ref);
+            let display_name = |id: &str| {
+                entries_ref
+                    .iter()
+                    .find(|entry| entry.desktop_id() == id)
+                    .map(|entry| format!("{} ({})", entry.name, id))
+                    .unwrap_or_else(|| id.to_string())
+            };
 
-    if let Ok(xdg_dirs) = env::var("XDG_DATA_DIRS") {
-        for dir in xdg_dirs.split(':') {
-            let trimmed = dir.trim();
-            if !trimmed.is_empty() {
-                push_unique_path(&mut paths, format!("{}/mime/globs2", trimmed.trim_end_matches('/')));
+            let mut summary = format!("MIME type: {}", resolution.mime_type);
+            if let Some(alias) = &resolution.alias_of {
+                summary.push_str(&format!("\nAlias: {} → {}", alias, resolution.mime_type));
             }
-        }
-    }
+            if !resolution.parents.is_empty() {
+                summary.push_str(&format!("\nParent types: {}", resolution.parents.join(" → ")));
+            }
+            match &resolution.default {
+                Some(default) => summary.push_str(&format!("\nOpens with: {}", display_name(default))),
+                None => summary.push_str("\nNo installed application handles this type."),
+            }
+            if let Some(source) = &resolution.default_source {
+                summary.push_str(&format!("\nDefault set in: {}", source));
+            }
+            summary_label.set_text(&summary);
 
-    push_unique_path(&mut paths, String::from("/usr/local/share/mime/globs2"));
-    push_unique_path(&mut paths, String::from("/usr/share/mime/globs2"));
+            for (index, id) in resolution.candidates.iter().enumerate() {
+                let label = Label::new(Some(&format!("{}. {}", index + 1, display_name(id))));
+                label.set_halign(gtk4::Align::Start);
+                if index == 0 {
+                    label.add_css_class("heading");
+                }
+                candidates_list.append(&label);
+            }
 
-    paths
-}
+            for step in &resolution.steps {
+                let row_box = GtkBox::new(Orientation::Vertical, 2);
+                row_box.set_margin_top(4);
+                row_box.set_margin_bottom(4);
+
+                let headline = Label::new(Some(&format!(
+                    "{} — {} for {}",
+                    step.desktop_id,
+                    step.verdict.label(),
+                    step.mime_type
+                )));
+                headline.set_halign(gtk4::Align::Start);
+                if step.verdict == Verdict::Default {
+                    headline.add_css_class("heading");
+                }
 
-fn push_unique_path(paths: &mut Vec<String>, candidate: String) {
-    if !paths.contains(&candidate) {
-        paths.push(candidate);
-    }
-}
+                let source_label = Label::new(Some(&format!("{}    {}", step.source, step.line)));
+                source_label.set_halign(gtk4::Align::Start);
+                source_label.set_selectable(true);
+                source_label.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+                source_label.add_css_class("monospace");
+                source_label.add_css_class("dim-label");
 
-fn extract_extension_from_pattern(pattern: &str) -> Option<String> {
-    if !pattern.starts_with("*.") {
-        return None;
-    }
+                row_box.append(&headline);
+                row_box.append(&source_label);
+                steps_list.append(&row_box);
+            }
+        })
+    };
 
-    let ext = &pattern[2..];
-    if ext.is_empty() {
-        return None;
-    }
+    let run_for_button = run_resolution.clone();
+    let entry_for_button = target_entry.clone();
+    resolve_button.connect_clicked(move |_| {
+        run_for_button(&entry_for_button.text());
+    });
 
-    if ext.chars().any(|c| matches!(c, '*' | '?' | '[' | ']' | '!')) {
-        return None;
-    }
+    let run_for_entry = run_resolution.clone();
+    target_entry.connect_activate(move |entry| {
+        run_for_entry(&entry.text());
+    });
 
-    Some(ext.to_lowercase())
+    let window_for_browse = window.clone();
+    let entry_for_browse = target_entry.clone();
+    let run_for_browse = run_resolution.clone();
+    browse_button.connect_clicked(move |_| {
+        let chooser = FileChooserDialog::new(
+            Some("Select File"),
+            Some(&window_for_browse),
+            FileChooserAction::Open,
+            &[("Cancel", ResponseType::Cancel), ("Open", ResponseType::Accept)],
+        );
+
+        let entry_clone = entry_for_browse.clone();
+        let run_clone = run_for_browse.clone();
+        chooser.connect_response(move |chooser, response| {
+            if response == ResponseType::Accept {
+                if let Some(path) = chooser.file().and_then(|file| file.path()) {
+                    let path_str = path.display().to_string();
+                    entry_clone.set_text(&path_str);
+                    run_clone(&path_str);
+                }
+            }
+            chooser.close();
+        });
+
+        chooser.show();
+    });
+
+    let drop_target = gtk4::DropTarget::new(gio::File::static_type(), gtk4::gdk::DragAction::COPY);
+    let entry_for_drop = target_entry.clone();
+    let run_for_drop = run_resolution.clone();
+    drop_target.connect_drop(move |_, value, _, _| {
+        let Ok(file) = value.get::<gio::File>() else {
+            return false;
+        };
+        let target = file
+            .path()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|| file.uri().to_string());
+        entry_for_drop.set_text(&target);
+        run_for_drop(&target);
+        true
+    });
+    window.add_controller(drop_target);
+
+    window.present();
 }
 
-fn mime_description(mime: &str) -> String {
-    if let Some(content_type) = gio::content_type_from_mime_type(mime) {
-        let description = gio::content_type_get_description(content_type.as_str());
-        let value = description.to_string();
-        if !value.is_empty() {
-            return value;
+// Everything a menu tree row needs to open entries and to redraw the tree after an edit
+#[derive(Clone)]
+struct MenuTreeContext {
+    tree: Rc<MenuTree>,
+    tree_box: GtkBox,
+    list_box: ListBox,
+    row_entry_map: Rc<RefCell<HashMap<gtk4::Widget, DesktopEntry>>>,
+    resolver: Rc<IconResolver>,
+}
+
+impl MenuTreeContext {
+    fn rebuild(&self) {
+        // Deferred, since the edit usually comes from a widget the rebuild removes
+        let context = self.clone();
+        gtk4::glib::idle_add_local_once(move || {
+            populate_menu_tree(&context.tree_box, &context.list_box, context.row_entry_map, context.resolver);
+        });
+    }
+
+    fn edit(&self, apply: impl FnOnce(&mut MenuEdits)) {
+        match self.save_edits(apply) {
+            Ok(path) => println!("Updated menu file {}", path.display()),
+            Err(e) => eprintln!("Failed to update the user menu file: {}", e),
         }
+        self.rebuild();
     }
 
-    mime.to_string()
+    fn save_edits(&self, apply: impl FnOnce(&mut MenuEdits)) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+        let mut edits = MenuEdits::load(&self.tree)?;
+        apply(&mut edits);
+        edits.save()?;
+        Ok(edits.path)
+    }
 }
 
-fn show_mime_selection_dialog(
-    parent: &ApplicationWindow,
-    known_choices: Rc<Vec<MimeChoice>>,
-    known_map: Rc<RefCell<HashMap<String, String>>>,
-    mime_state: Rc<RefCell<Vec<String>>>,
-    extension_state: Rc<RefCell<HashMap<String, String>>>,
-    mime_list: &ListBox,
-    current_entry: Rc<RefCell<Option<DesktopEntry>>>,
-) {
+fn menu_shows_entry(tree: &MenuTree, desktop_id: &str, path: &[String]) -> bool {
+    tree.root
+        .submenu(path)
+        .is_some_and(|node| node.contains_entry(desktop_id))
+}
+
+fn menu_entry_still_in(desktop_id: &str, from: &[String]) -> bool {
+    load_menu_tree().is_ok_and(|tree| menu_shows_entry(&tree, desktop_id, from))
+}
+
+// Shown when the entry did not arrive in the submenu it was dropped on, e.g. because a rule
+// in a file read after the merge file excludes it there
+fn show_menu_move_failed(context: &MenuTreeContext, desktop_id: &str, target: &[String]) {
     let dialog = gtk4::Dialog::builder()
-        .transient_for(parent)
         .modal(true)
-        .title("Add File Association")
-        .default_width(520)
-        .default_height(480)
+        .title("Entry Not Moved")
+        .default_width(460)
         .build();
-
+    if let Some(window) = context.tree_box.root().and_downcast::<gtk4::Window>() {
+        dialog.set_transient_for(Some(&window));
+    }
     dialog.add_button("Close", ResponseType::Close);
 
     let content = dialog.content_area();
-    content.set_spacing(8);
     content.set_margin_top(12);
     content.set_margin_bottom(12);
     content.set_margin_start(12);
     content.set_margin_end(12);
+    let message = Label::new(Some(&format!(
+        "{} is not shown in '{}' after the move: a menu file read after the changes made here \
+         excludes it. \"Why isn't an app in my menu?\" under Tools shows the rule.",
+        desktop_id,
+        target.join(" / ")
+    )));
+    message.set_wrap(true);
+    message.set_xalign(0.0);
+    content.append(&message);
 
-    let filter_entry = Entry::builder()
-        .placeholder_text("Search by extension, description, or MIME type")
-        .build();
-    content.append(&filter_entry);
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.present();
+}
 
-    let scrolled = ScrolledWindow::builder()
-        .min_content_height(260)
-        .hexpand(true)
-        .vexpand(true)
+// Shown when a move only added the entry to its new submenu: the old one keeps it because the
+// system menu file includes it after the merge file's <Exclude>
+fn show_menu_move_blocked(context: &MenuTreeContext, desktop_id: &str, from: &[String]) {
+    let dialog = gtk4::Dialog::builder()
+        .modal(true)
+        .title("Entry Still Shown")
+        .default_width(460)
         .build();
-
-    let list_box = ListBox::new();
-    list_box.set_selection_mode(gtk4::SelectionMode::Browse);
-    scrolled.set_child(Some(&list_box));
-    content.append(&scrolled);
-
-    if known_choices.is_empty() {
-        let empty_label = Label::new(Some("No known file types available. Use manual input below."));
-        empty_label.set_halign(gtk4::Align::Start);
-        empty_label.add_css_class("dim-label");
-        content.append(&empty_label);
+    if let Some(window) = context.tree_box.root().and_downcast::<gtk4::Window>() {
+        dialog.set_transient_for(Some(&window));
     }
+    dialog.add_button("Keep Both", ResponseType::Close);
+    dialog.add_button("Override in User Menu", ResponseType::Accept);
 
-    let select_box = GtkBox::new(Orientation::Horizontal, 6);
-    select_box.set_hexpand(true);
-
-    let hint_label = Label::new(Some("Double-click a row or use \"Add Selected\"."));
-    hint_label.set_halign(gtk4::Align::Start);
-    hint_label.set_hexpand(true);
-    hint_label.add_css_class("dim-label");
-    select_box.append(&hint_label);
-
-    let add_selected_button = Button::with_label("Add Selected");
-    add_selected_button.set_sensitive(false);
-    add_selected_button.set_halign(gtk4::Align::End);
-    select_box.append(&add_selected_button);
-
-    content.append(&select_box);
-
-    let manual_box = GtkBox::new(Orientation::Horizontal, 6);
-    manual_box.set_hexpand(true);
+    let content = dialog.content_area();
+    content.set_spacing(8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
 
-    let manual_entry = Entry::builder()
-        .placeholder_text("Enter extension (e.g. .txt) or MIME type")
-        .build();
-    manual_entry.set_hexpand(true);
-    manual_box.append(&manual_entry);
+    let message = Label::new(Some(&format!(
+        "{} was added to its new submenu but is still shown in '{}'. A rule in {} includes it \
+         after the menu changes made here are read, so they cannot remove it from there.",
+        desktop_id,
+        from.join(" / "),
+        context.tree.source.display()
+    )));
+    message.set_wrap(true);
+    message.set_xalign(0.0);
+    content.append(&message);
+
+    let user_menu = MenuEdits::load_user_menu(&context.tree);
+    let hint = Label::new(Some(&match &user_menu {
+        Ok(edits) => format!(
+            "Overriding adds the exclusion to {}, which merges the system menu first and is read after it.",
+            edits.path.display()
+        ),
+        Err(e) => format!("The user menu file cannot be used: {}", e),
+    }));
+    hint.set_wrap(true);
+    hint.set_xalign(0.0);
+    hint.add_css_class("dim-label");
+    content.append(&hint);
+
+    let status_label = Label::new(None);
+    status_label.set_wrap(true);
+    status_label.set_xalign(0.0);
+    status_label.add_css_class("error");
+    status_label.set_visible(false);
+    content.append(&status_label);
+
+    let user_menu = RefCell::new(user_menu.ok());
+    if user_menu.borrow().is_none() {
+        dialog.set_response_sensitive(ResponseType::Accept, false);
+    }
 
-    let manual_add_button = Button::with_label("Add From Text");
-    manual_box.append(&manual_add_button);
+    let context = context.clone();
+    let desktop_id = desktop_id.to_string();
+    let from = from.to_vec();
+    dialog.connect_response(move |dialog, response| {
+        if response != ResponseType::Accept {
+            dialog.close();
+            return;
+        }
+        let Some(mut edits) = user_menu.borrow_mut().take() else {
+            return;
+        };
+        edits.exclude_entry(&desktop_id, &from);
+        let result = edits.save();
+        let path = edits.path.clone();
+        *user_menu.borrow_mut() = Some(edits);
+        match result {
+            Err(e) => {
+                status_label.set_text(&format!("Failed to write {}: {}", path.display(), e));
+                status_label.set_visible(true);
+            }
+            Ok(_) if menu_entry_still_in(&desktop_id, &from) => {
+                status_label.set_text(&format!(
+                    "Wrote {}, but {} is still shown in '{}'.",
+                    path.display(),
+                    desktop_id,
+                    from.join(" / ")
+                ));
+                status_label.set_visible(true);
+                context.rebuild();
+            }
+            Ok(_) => {
+                println!("Updated menu file {}", path.display());
+                context.rebuild();
+                dialog.close();
+            }
+        }
+    });
 
-    content.append(&manual_box);
+    dialog.present();
+}
 
-    for choice in known_choices.iter() {
-        let row = gtk4::ListBoxRow::new();
-        row.set_activatable(true);
-        row.set_selectable(true);
+// Drag payload for menu entries: the desktop ID, then the path of the submenu it was dragged from
+fn menu_drag_payload(desktop_id: &str, from: &[String]) -> String {
+    std::iter::once(desktop_id.to_string())
+        .chain(from.iter().cloned())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-        unsafe {
-            row.set_data("mime-type", choice.mime_type.clone());
-            row.set_data("extension", choice.extension.clone());
-            row.set_data("description", choice.description.clone());
+fn add_menu_drop_target(widget: &impl IsA<gtk4::Widget>, context: &MenuTreeContext, target: Vec<String>) {
+    let drop_target = gtk4::DropTarget::new(String::static_type(), gtk4::gdk::DragAction::MOVE);
+    let context = context.clone();
+    drop_target.connect_drop(move |_, value, _, _| {
+        let Ok(payload) = value.get::<String>() else {
+            return false;
+        };
+        let mut parts = payload.split('\n').map(|part| part.to_string());
+        let Some(desktop_id) = parts.next() else {
+            return false;
+        };
+        let from: Vec<String> = parts.collect();
+        if from == target {
+            return false;
         }
+        let path = match context.save_edits(|edits| edits.move_entry(&desktop_id, &from, &target)) {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("Failed to update the user menu file: {}", e);
+                return false;
+            }
+        };
+        match MenuEdits::clear_user_menu_rules(&context.tree, &desktop_id) {
+            Ok(Some(user_menu)) => println!("Removed earlier rules for {} from {}", desktop_id, user_menu.display()),
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to update the user root menu file: {}", e),
+        }
+        context.rebuild();
+
+        let Ok(tree) = load_menu_tree() else {
+            return false;
+        };
+        if !menu_shows_entry(&tree, &desktop_id, &target) {
+            eprintln!(
+                "{} is not shown in '{}' after updating {}",
+                desktop_id,
+                target.join(" / "),
+                path.display()
+            );
+            show_menu_move_failed(&context, &desktop_id, &target);
+            return false;
+        }
+        // Merge files are read where <DefaultMergeDirs/> sits, so a later <Include> in the
+        // system menu file can still pull the entry back into its old submenu
+        if menu_shows_entry(&tree, &desktop_id, &from) {
+            eprintln!(
+                "{} is still shown in '{}' after updating {}",
+                desktop_id,
+                from.join(" / "),
+                path.display()
+            );
+            show_menu_move_blocked(&context, &desktop_id, &from);
+            return false;
+        }
+        println!("Updated menu file {}", path.display());
+        true
+    });
+    widget.add_controller(drop_target);
+}
 
-        let row_box = GtkBox::new(Orientation::Horizontal, 10);
-        row_box.set_hexpand(true);
-
-        let ext_label = Label::new(Some(&choice.extension));
-        ext_label.add_css_class("monospace");
-        ext_label.set_width_chars(8);
-        ext_label.set_halign(gtk4::Align::Start);
+fn populate_menu_tree(
+    tree_box: &GtkBox,
+    list_box: &ListBox,
+    row_entry_map: Rc<RefCell<HashMap<gtk4::Widget, DesktopEntry>>>,
+    resolver: Rc<IconResolver>,
+) {
+    while let Some(child) = tree_box.first_child() {
+        tree_box.remove(&child);
+    }
 
-        let description_label = Label::new(Some(&choice.description));
-        description_label.set_halign(gtk4::Align::Start);
-        description_label.set_hexpand(true);
-        description_label.set_wrap(true);
+    let tree = match load_menu_tree() {
+        Ok(tree) => tree,
+        Err(e) => {
+            let message = Label::new(Some(&format!("Could not build the menu tree: {}", e)));
+            message.set_wrap(true);
+            message.set_margin_top(12);
+            message.add_css_class("dim-label");
+            tree_box.append(&message);
+            return;
+        }
+    };
 
-        let mime_label = Label::new(Some(choice.mime_type.as_str()));
-        mime_label.add_css_class("dim-label");
-        mime_label.set_halign(gtk4::Align::End);
+    let context = MenuTreeContext {
+        tree: Rc::new(tree),
+        tree_box: tree_box.clone(),
+        list_box: list_box.clone(),
+        row_entry_map,
+        resolver,
+    };
+    let tree = context.tree.clone();
 
-        row_box.append(&ext_label);
-        row_box.append(&description_label);
-        row_box.append(&mime_label);
+    let toolbar = GtkBox::new(Orientation::Horizontal, 5);
+    let source_label = Label::new(Some(&if tree.source_guessed {
+        format!("{} (guessed)", tree.source.display())
+    } else {
+        tree.source.display().to_string()
+    }));
+    source_label.set_halign(gtk4::Align::Start);
+    source_label.set_hexpand(true);
+    source_label.set_ellipsize(gtk4::pango::EllipsizeMode::Start);
+    if tree.source_guessed {
+        source_label.add_css_class("warning");
+    } else {
+        source_label.add_css_class("dim-label");
+    }
+    source_label.set_tooltip_text(Some(&format!(
+        "{}Menu files read:\n{}",
+        if tree.source_guessed {
+            "XDG_MENU_PREFIX is not set, so this is one of the installed menu files; \
+             the desktop may use another one.\n\n"
+        } else {
+            ""
+        },
+        tree.merged_files
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    )));
+    toolbar.append(&source_label);
+
+    let new_submenu_button = Button::with_label("New Submenu...");
+    new_submenu_button.set_tooltip_text(Some(
+        "Drag entries onto a submenu to move them, or onto this pane to move them to the top level",
+    ));
+    let context_clone = context.clone();
+    new_submenu_button.connect_clicked(move |button| {
+        let Some(parent) = button.root().and_then(|root| root.downcast::<gtk4::Window>().ok()) else {
+            return;
+        };
+        show_new_submenu_dialog(&parent, context_clone.clone());
+    });
+    toolbar.append(&new_submenu_button);
+    tree_box.append(&toolbar);
+
+    let empty_count = tree.hidden_menus.iter().filter(|node| !node.deleted).count();
+    if empty_count > 0 {
+        let names: Vec<&str> = tree
+            .hidden_menus
+            .iter()
+            .filter(|node| !node.deleted)
+            .map(|node| node.display_name())
+            .collect();
+        let hidden_label = Label::new(Some(&format!("{} empty submenus are not shown", empty_count)));
+        hidden_label.set_halign(gtk4::Align::Start);
+        hidden_label.add_css_class("dim-label");
+        hidden_label.set_tooltip_text(Some(&names.join(", ")));
+        tree_box.append(&hidden_label);
+    }
 
-        row.set_child(Some(&row_box));
-        list_box.append(&row);
+    append_menu_items(tree_box, &tree.root, &context);
+    add_menu_drop_target(tree_box, &context, Vec::new());
+
+    // Hidden submenus stay listed so they can be shown again
+    let deleted: Vec<&MenuNode> = tree.hidden_menus.iter().filter(|node| node.deleted).collect();
+    if !deleted.is_empty() {
+        let heading = Label::new(Some("Hidden submenus"));
+        heading.set_halign(gtk4::Align::Start);
+        heading.set_margin_top(12);
+        heading.add_css_class("heading");
+        tree_box.append(&heading);
+
+        for node in deleted {
+            let row = GtkBox::new(Orientation::Horizontal, 6);
+            let label = Label::new(Some(&node.path.join(" / ")));
+            label.set_halign(gtk4::Align::Start);
+            label.set_hexpand(true);
+            label.add_css_class("dim-label");
+            row.append(&label);
+
+            let show_button = Button::with_label("Show");
+            show_button.add_css_class("flat");
+            let context_clone = context.clone();
+            let path = node.path.clone();
+            show_button.connect_clicked(move |_| {
+                context_clone.edit(|edits| edits.set_hidden(&path, false));
+            });
+            row.append(&show_button);
+            tree_box.append(&row);
+        }
     }
+}
 
-    let list_box_for_filter = list_box.clone();
-    filter_entry.connect_changed(move |entry| {
-        let query = entry.text().to_lowercase();
-        let mut child = list_box_for_filter.first_child();
-        while let Some(widget) = child {
-            if let Some(row) = widget.downcast_ref::<gtk4::ListBoxRow>() {
-                let extension = row_string_data(row, "extension")
-                    .unwrap_or_default()
-                    .to_lowercase();
-                let description = row_string_data(row, "description")
-                    .unwrap_or_default()
-                    .to_lowercase();
-                let mime = row_string_data(row, "mime-type")
-                    .unwrap_or_default()
-                    .to_lowercase();
+fn append_menu_items(container: &GtkBox, node: &MenuNode, context: &MenuTreeContext) {
+    for item in &node.items {
+        match item {
+            MenuItem::Separator => {
+                container.append(&gtk4::Separator::new(Orientation::Horizontal));
+            }
+            MenuItem::Submenu(submenu) => {
+                let header = GtkBox::new(Orientation::Horizontal, 6);
+                let icon_name = submenu
+                    .directory
+                    .as_ref()
+                    .map(|directory| directory.icon.as_str())
+                    .filter(|icon| !icon.is_empty() && context.resolver.icon_exists(icon))
+                    .unwrap_or("folder");
+                let icon = Image::from_icon_name(strip_icon_extension(icon_name));
+                icon.set_pixel_size(24);
+                header.append(&icon);
+                header.append(&Label::new(Some(&format!(
+                    "{} ({})",
+                    submenu.display_name(),
+                    submenu.entry_count()
+                ))));
+                if let Some(directory) = &submenu.directory {
+                    header.set_tooltip_text(Some(&directory.path.display().to_string()));
+                }
 
-                let matches = query.is_empty()
-                    || extension.contains(&query)
-                    || description.contains(&query)
-                    || mime.contains(&query);
-                row.set_visible(matches);
+                let hide_button = Button::from_icon_name("view-conceal-symbolic");
+                hide_button.add_css_class("flat");
+                hide_button.set_tooltip_text(Some("Hide this submenu"));
+                let context_clone = context.clone();
+                let path = submenu.path.clone();
+                hide_button.connect_clicked(move |_| {
+                    context_clone.edit(|edits| edits.set_hidden(&path, true));
+                });
+                header.append(&hide_button);
+
+                let children = GtkBox::new(Orientation::Vertical, 2);
+                children.set_margin_start(18);
+                append_menu_items(&children, submenu, context);
+
+                let expander = gtk4::Expander::new(None);
+                expander.set_label_widget(Some(&header));
+                expander.set_child(Some(&children));
+                add_menu_drop_target(&expander, context, submenu.path.clone());
+                container.append(&expander);
+            }
+            MenuItem::Entry(entry) => {
+                let row = Button::new();
+                row.add_css_class("flat");
+                let row_box = GtkBox::new(Orientation::Horizontal, 6);
+                let icon = if entry.icon.is_empty() || !context.resolver.icon_exists(&entry.icon) {
+                    Image::from_icon_name("application-x-executable")
+                } else if entry.icon.starts_with('/') {
+                    Image::from_file(&entry.icon)
+                } else {
+                    Image::from_icon_name(strip_icon_extension(&entry.icon))
+                };
+                icon.set_pixel_size(24);
+                row_box.append(&icon);
+                let label = Label::new(Some(&entry.name));
+                label.set_halign(gtk4::Align::Start);
+                row_box.append(&label);
+                row.set_child(Some(&row_box));
+                row.set_tooltip_text(Some(&format!("{}\n{}", entry.id, entry.path.display())));
+
+                let drag_source = gtk4::DragSource::new();
+                drag_source.set_actions(gtk4::gdk::DragAction::MOVE);
+                drag_source.set_content(Some(&gtk4::gdk::ContentProvider::for_value(
+                    &menu_drag_payload(&entry.id, &node.path).to_value(),
+                )));
+                row.add_controller(drag_source);
+
+                // Selecting the matching list row opens the entry in the editor
+                let list_box = context.list_box.clone();
+                let row_entry_map = context.row_entry_map.clone();
+                let path = entry.path.clone();
+                row.connect_clicked(move |_| {
+                    let map = row_entry_map.borrow();
+                    let mut child = list_box.first_child();
+                    while let Some(widget) = child {
+                        if let Some(list_row) = widget.downcast_ref::<gtk4::ListBoxRow>() {
+                            let found = list_row
+                                .child()
+                                .and_then(|row_child| map.get(&row_child))
+                                .is_some_and(|desktop_entry| desktop_entry.path == path);
+                            if found {
+                                list_box.select_row(Some(list_row));
+                                return;
+                            }
+                        }
+                        child = widget.next_sibling();
+                    }
+                    eprintln!("{} is not in the application list", path.display());
+                });
+                container.append(&row);
             }
-            child = widget.next_sibling();
         }
-    });
+    }
+}
 
-    let add_selected_button_clone = add_selected_button.clone();
-    list_box.connect_row_selected(move |_, row| {
-        add_selected_button_clone.set_sensitive(row.is_some());
-    });
+// Collects the path of every displayed submenu, in tree order
+fn collect_submenu_paths(node: &MenuNode, paths: &mut Vec<Vec<String>>) {
+    for item in &node.items {
+        if let MenuItem::Submenu(submenu) = item {
+            paths.push(submenu.path.clone());
+            collect_submenu_paths(submenu, paths);
+        }
+    }
+}
 
-    let state_for_activation = mime_state.clone();
-    let list_for_activation = mime_list.clone();
-    let entry_for_activation = current_entry.clone();
-    let map_for_activation = known_map.clone();
-    let extensions_for_activation = extension_state.clone();
-    list_box.connect_row_activated(move |_, row| {
-        add_mime_from_row(
-            row,
-            &state_for_activation,
-            &list_for_activation,
-            &entry_for_activation,
-            &map_for_activation,
-            &extensions_for_activation,
-        );
-    });
+fn show_new_submenu_dialog(parent: &gtk4::Window, context: MenuTreeContext) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .modal(true)
+        .title("New Submenu")
+        .default_width(380)
+        .build();
 
-    let list_for_button = list_box.clone();
-    let state_for_button = mime_state.clone();
-    let mime_list_for_button = mime_list.clone();
-    let entry_for_button = current_entry.clone();
-    let map_for_button = known_map.clone();
-    let extensions_for_button = extension_state.clone();
-    add_selected_button.connect_clicked(move |_| {
-        if let Some(row) = list_for_button.selected_row() {
-            add_mime_from_row(
-                &row,
-                &state_for_button,
-                &mime_list_for_button,
-                &entry_for_button,
-                &map_for_button,
-                &extensions_for_button,
-            );
-        }
-    });
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    window.set_child(Some(&content));
 
-    let state_for_manual = mime_state.clone();
-    let mime_list_for_manual = mime_list.clone();
-    let entry_for_manual = current_entry.clone();
-    let map_for_manual = known_map.clone();
-    let extensions_for_manual = extension_state.clone();
-    let manual_entry_button = manual_entry.clone();
-    manual_add_button.connect_clicked(move |_| {
-        try_add_manual_mime(
-            &manual_entry_button,
-            &state_for_manual,
-            &mime_list_for_manual,
-            &entry_for_manual,
-            &map_for_manual,
-            &extensions_for_manual,
-        );
+    let name_label = Label::new(Some("Name:"));
+    name_label.set_halign(gtk4::Align::Start);
+    let name_entry = Entry::new();
+    content.append(&name_label);
+    content.append(&name_entry);
+
+    let icon_label = Label::new(Some("Icon:"));
+    icon_label.set_halign(gtk4::Align::Start);
+    let icon_entry = Entry::builder().placeholder_text("folder").build();
+    content.append(&icon_label);
+    content.append(&icon_entry);
+
+    let parent_label = Label::new(Some("Inside:"));
+    parent_label.set_halign(gtk4::Align::Start);
+    let mut parent_paths: Vec<Vec<String>> = vec![Vec::new()];
+    collect_submenu_paths(&context.tree.root, &mut parent_paths);
+    let parent_names: Vec<String> = parent_paths
+        .iter()
+        .map(|path| if path.is_empty() { String::from("Top level") } else { path.join(" / ") })
+        .collect();
+    let parent_refs: Vec<&str> = parent_names.iter().map(|name| name.as_str()).collect();
+    let parent_dropdown = gtk4::DropDown::from_strings(&parent_refs);
+    content.append(&parent_label);
+    content.append(&parent_dropdown);
+
+    let note = Label::new(Some(
+        "The submenu and its .directory file are stored in your user menu files; no entry is changed.",
+    ));
+    note.set_wrap(true);
+    note.set_halign(gtk4::Align::Start);
+    note.add_css_class("dim-label");
+    content.append(&note);
+
+    let buttons = GtkBox::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    buttons.set_margin_top(6);
+    let cancel_button = Button::with_label("Cancel");
+    let create_button = Button::with_label("Create");
+    create_button.add_css_class("suggested-action");
+    create_button.set_sensitive(false);
+    buttons.append(&cancel_button);
+    buttons.append(&create_button);
+    content.append(&buttons);
+
+    let create_button_clone = create_button.clone();
+    name_entry.connect_changed(move |entry| {
+        create_button_clone.set_sensitive(!entry.text().trim().is_empty());
     });
 
-    let state_for_entry = mime_state.clone();
-    let mime_list_for_entry = mime_list.clone();
-    let entry_for_entry = current_entry.clone();
-    let map_for_entry = known_map.clone();
-    let extensions_for_entry = extension_state.clone();
-    manual_entry.connect_activate(move |entry| {
-        try_add_manual_mime(
-            entry,
-            &state_for_entry,
-            &mime_list_for_entry,
-            &entry_for_entry,
-            &map_for_entry,
-            &extensions_for_entry,
-        );
+    let window_clone = window.clone();
+    cancel_button.connect_clicked(move |_| window_clone.close());
+
+    let window_clone = window.clone();
+    create_button.connect_clicked(move |_| {
+        let name = name_entry.text().trim().to_string();
+        let icon = icon_entry.text().trim().to_string();
+        let parent_path = parent_paths
+            .get(parent_dropdown.selected() as usize)
+            .cloned()
+            .unwrap_or_default();
+
+        match create_directory_file(&name, &icon) {
+            Ok(directory_file) => {
+                context.edit(|edits| edits.add_submenu(&parent_path, &name, &directory_file));
+                window_clone.close();
+            }
+            Err(e) => eprintln!("Failed to create the submenu directory file: {}", e),
+        }
     });
 
-    dialog.connect_response(|dialog, _| dialog.close());
-    dialog.show();
+    window.present();
+}
+
+// Existence is checked through the icon theme caches, GTK still does the loading
+fn resolved_icon_image(icon_name: &str, resolver: &IconResolver) -> Image {
+    if icon_name.is_empty() {
+        Image::from_icon_name("application-x-executable")
+    } else if !resolver.icon_exists(icon_name) {
+        let missing = Image::from_icon_name("image-missing");
+        missing.set_tooltip_text(Some(&format!("Icon '{}' was not found", icon_name)));
+        missing
+    } else if icon_name.starts_with('/') {
+        Image::from_file(icon_name)
+    } else {
+        Image::from_icon_name(strip_icon_extension(icon_name))
+    }
 }
 
-fn create_list_row(name: &str, comment: &str, icon_name: &str) -> GtkBox {
+fn create_list_row(name: &str, comment: &str, icon_name: &str, resolver: &IconResolver) -> GtkBox {
     let row_box = GtkBox::new(Orientation::Horizontal, 10);
     row_box.set_margin_start(5);
     row_box.set_margin_end(5);
@@ -1311,16 +6320,7 @@ fn create_list_row(name: &str, comment: &str, icon_name: &str) -> GtkBox {
     row_box.set_margin_bottom(5);
 
     // Icon
-    let icon = if !icon_name.is_empty() {
-        // Try loading as icon name first, then as file path
-        if std::path::Path::new(icon_name).exists() {
-            Image::from_file(icon_name)
-        } else {
-            Image::from_icon_name(icon_name)
-        }
-    } else {
-        Image::from_icon_name("application-x-executable")
-    };
+    let icon = resolved_icon_image(icon_name, resolver);
     icon.set_pixel_size(48);
     row_box.append(&icon);
 
@@ -0,0 +1,340 @@
+use ini::Ini;
+use std::path::PathBuf;
+
+use crate::exec::{escape_percent, find_program, quote_exec_argument, split_exec, split_exec_spans, unescape_percent};
+use crate::mime_apps::config_home;
+
+const WRAPPERS_FILE: &str = "wrappers.ini";
+// Marks where a wrapper's user-set value goes in its arguments
+const PARAMETER: &str = "{}";
+
+struct BuiltinWrapper {
+    id: &'static str,
+    name: &'static str,
+    program: &'static str,
+    arguments: &'static str,
+    default_parameter: &'static str,
+    description: &'static str,
+}
+
+const BUILTIN_WRAPPERS: &[BuiltinWrapper] = &[
+    BuiltinWrapper {
+        id: "prime-run",
+        name: "NVIDIA PRIME offload",
+        program: "prime-run",
+        arguments: "",
+        default_parameter: "",
+        description: "Render on the discrete NVIDIA GPU",
+    },
+    BuiltinWrapper {
+        id: "gamemode",
+        name: "GameMode",
+        program: "gamemoderun",
+        arguments: "",
+        default_parameter: "",
+        description: "Apply Feral GameMode performance tweaks while running",
+    },
+    BuiltinWrapper {
+        id: "mangohud",
+        name: "MangoHud overlay",
+        program: "mangohud",
+        arguments: "",
+        default_parameter: "",
+        description: "Show an FPS and system load overlay in Vulkan and OpenGL apps",
+    },
+    BuiltinWrapper {
+        id: "firejail",
+        name: "Firejail sandbox",
+        program: "firejail",
+        arguments: "--profile={}",
+        default_parameter: "default",
+        description: "Run inside a Firejail sandbox with the given profile name or file",
+    },
+    BuiltinWrapper {
+        id: "nice",
+        name: "CPU priority (nice)",
+        program: "nice",
+        arguments: "-n {}",
+        default_parameter: "10",
+        description: "Niceness from -20 (highest priority) to 19 (lowest)",
+    },
+    BuiltinWrapper {
+        id: "taskset",
+        name: "CPU affinity (taskset)",
+        program: "taskset",
+        arguments: "-c {}",
+        default_parameter: "0-3",
+        description: "Run only on the listed CPU cores, e.g. 0-3 or 0,2",
+    },
+    BuiltinWrapper {
+        id: "memory-limit",
+        name: "Memory limit (systemd scope)",
+        program: "systemd-run",
+        arguments: "--user --scope -p MemoryMax={}",
+        default_parameter: "4G",
+        description: "Run in a transient systemd user scope with a memory ceiling",
+    },
+];
+
+#[derive(Debug, Clone)]
+pub struct WrapperPreset {
+    pub id: String,
+    pub name: String,
+    pub program: String,
+    // Argument tokens after the program, one of which may hold the {} parameter
+    pub arguments: Vec<String>,
+    pub default_parameter: String,
+    pub description: String,
+}
+
+// A wrapper found in an Exec line, with the program as it was written there
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveWrapper {
+    pub id: String,
+    pub program: String,
+    pub parameter: String,
+}
+
+// An Exec command split into the wrappers it starts with and the wrapped command text
+#[derive(Debug, Clone, PartialEq)]
+pub struct WrappedCommand {
+    pub wrappers: Vec<ActiveWrapper>,
+    pub command: String,
+}
+
+impl WrapperPreset {
+    pub fn has_parameter(&self) -> bool {
+        self.arguments.iter().any(|arg| arg.contains(PARAMETER))
+    }
+
+    pub fn installed(&self) -> Option<PathBuf> {
+        find_program(&self.program)
+    }
+
+    fn tokens(&self, program: &str, parameter: &str) -> Vec<String> {
+        std::iter::once(program.to_string())
+            .chain(
+                self.arguments
+                    .iter()
+                    .map(|arg| arg.replace(PARAMETER, &escape_percent(parameter))),
+            )
+            .collect()
+    }
+
+    // Matches this wrapper at the start of `args`, returning its parameter
+    fn match_args(&self, args: &[String]) -> Option<String> {
+        let (program, rest) = args.split_first()?;
+        if program_name(program) != program_name(&self.program) || rest.len() < self.arguments.len() {
+            return None;
+        }
+        let mut parameter = String::new();
+        for (template, arg) in self.arguments.iter().zip(rest) {
+            match template.split_once(PARAMETER) {
+                Some((prefix, suffix)) => {
+                    let value = arg.strip_prefix(prefix)?.strip_suffix(suffix)?;
+                    parameter = unescape_percent(value);
+                }
+                None if template == arg => {}
+                None => return None,
+            }
+        }
+        Some(parameter)
+    }
+}
+
+fn program_name(program: &str) -> &str {
+    program.rsplit('/').next().unwrap_or(program)
+}
+
+pub fn wrappers_path() -> PathBuf {
+    PathBuf::from(format!("{}/{}/{}", config_home(), crate::CONFIG_DIR_NAME, WRAPPERS_FILE))
+}
+
+fn builtin_wrappers() -> Vec<WrapperPreset> {
+    BUILTIN_WRAPPERS
+        .iter()
+        .map(|builtin| WrapperPreset {
+            id: builtin.id.to_string(),
+            name: builtin.name.to_string(),
+            program: builtin.program.to_string(),
+            arguments: split_exec(builtin.arguments).unwrap_or_default(),
+            default_parameter: builtin.default_parameter.to_string(),
+            description: builtin.description.to_string(),
+        })
+        .collect()
+}
+
+// Built-in presets, then the user's wrappers.ini: a [section] per wrapper with Name, Program,
+// Arguments (Exec quoting, {} for the value), Default and Description. A section named after
+// a built-in replaces it, and Hidden=true removes it.
+pub fn load_wrappers() -> Vec<WrapperPreset> {
+    let mut presets = builtin_wrappers();
+
+    let path = wrappers_path();
+    if !path.exists() {
+        return presets;
+    }
+    let conf = match Ini::load_from_file(&path) {
+        Ok(conf) => conf,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            return presets;
+        }
+    };
+
+    for (section, properties) in conf.iter() {
+        let Some(id) = section else {
+            continue;
+        };
+        presets.retain(|preset| preset.id != id);
+        if properties.get("Hidden") == Some("true") {
+            continue;
+        }
+        let Some(program) = properties.get("Program").filter(|program| !program.trim().is_empty()) else {
+            eprintln!("Wrapper [{}] in {} has no Program", id, path.display());
+            continue;
+        };
+        let arguments = match split_exec(properties.get("Arguments").unwrap_or("")) {
+            Ok(arguments) => arguments,
+            Err(e) => {
+                eprintln!("Wrapper [{}] in {}: {}", id, path.display(), e);
+                continue;
+            }
+        };
+        presets.push(WrapperPreset {
+            id: id.to_string(),
+            name: properties.get("Name").unwrap_or(id).to_string(),
+            program: program.trim().to_string(),
+            arguments,
+            default_parameter: properties.get("Default").unwrap_or("").to_string(),
+            description: properties.get("Description").unwrap_or("").to_string(),
+        });
+    }
+    presets
+}
+
+// Peels known wrappers off the start of an Exec command, outermost first
+pub fn parse_wrappers(command: &str, presets: &[WrapperPreset]) -> WrappedCommand {
+    let plain = WrappedCommand {
+        wrappers: Vec::new(),
+        command: command.trim().to_string(),
+    };
+    let Ok(spans) = split_exec_spans(command) else {
+        return plain;
+    };
+    let args: Vec<String> = spans.iter().map(|(arg, _)| arg.clone()).collect();
+
+    let mut wrappers: Vec<ActiveWrapper> = Vec::new();
+    let mut index = 0;
+    while let Some((preset, parameter)) = presets
+        .iter()
+        .find_map(|preset| preset.match_args(&args[index..]).map(|parameter| (preset, parameter)))
+    {
+        wrappers.push(ActiveWrapper {
+            id: preset.id.clone(),
+            program: args[index].clone(),
+            parameter,
+        });
+        index += 1 + preset.arguments.len();
+    }
+
+    WrappedCommand {
+        wrappers,
+        command: spans
+            .get(index)
+            .map(|(_, start)| command[*start..].trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+pub fn format_wrappers(wrapped: &WrappedCommand, presets: &[WrapperPreset]) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for wrapper in &wrapped.wrappers {
+        let Some(preset) = presets.iter().find(|preset| preset.id == wrapper.id) else {
+            continue;
+        };
+        parts.extend(
+            preset
+                .tokens(&wrapper.program, &wrapper.parameter)
+                .iter()
+                .map(|token| quote_exec_argument(token)),
+        );
+    }
+    if !wrapped.command.is_empty() {
+        parts.push(wrapped.command.clone());
+    }
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrapper(id: &str, program: &str, parameter: &str) -> ActiveWrapper {
+        ActiveWrapper {
+            id: id.to_string(),
+            program: program.to_string(),
+            parameter: parameter.to_string(),
+        }
+    }
+
+    #[test]
+    fn nested_wrappers_round_trip() {
+        let presets = builtin_wrappers();
+        let command = "nice -n 10 firejail --profile=x app %U";
+        let wrapped = parse_wrappers(command, &presets);
+        assert_eq!(
+            wrapped.wrappers,
+            vec![wrapper("nice", "nice", "10"), wrapper("firejail", "firejail", "x")]
+        );
+        assert_eq!(wrapped.command, "app %U");
+        assert_eq!(format_wrappers(&wrapped, &presets), command);
+    }
+
+    #[test]
+    fn program_path_is_kept() {
+        let presets = builtin_wrappers();
+        let command = "/usr/bin/prime-run /opt/app/app --x";
+        let wrapped = parse_wrappers(command, &presets);
+        assert_eq!(wrapped.wrappers, vec![wrapper("prime-run", "/usr/bin/prime-run", "")]);
+        assert_eq!(wrapped.command, "/opt/app/app --x");
+        assert_eq!(format_wrappers(&wrapped, &presets), command);
+    }
+
+    #[test]
+    fn parameters_with_spaces_and_percent() {
+        let presets = builtin_wrappers();
+        let wrapped = WrappedCommand {
+            wrappers: vec![
+                wrapper("firejail", "firejail", "/home/me/my profile"),
+                wrapper("memory-limit", "systemd-run", "50%"),
+            ],
+            command: String::from("app %F"),
+        };
+        let command = format_wrappers(&wrapped, &presets);
+        assert_eq!(
+            command,
+            r#"firejail "--profile=/home/me/my profile" systemd-run --user --scope -p MemoryMax=50%% app %F"#
+        );
+        assert_eq!(parse_wrappers(&command, &presets), wrapped);
+    }
+
+    #[test]
+    fn partial_match_is_not_a_wrapper() {
+        let presets = builtin_wrappers();
+        for command in ["nice app", "firejail app", "taskset -c"] {
+            let wrapped = parse_wrappers(command, &presets);
+            assert!(wrapped.wrappers.is_empty(), "{}", command);
+            assert_eq!(format_wrappers(&wrapped, &presets), command);
+        }
+    }
+
+    #[test]
+    fn wrappers_without_command() {
+        let presets = builtin_wrappers();
+        let wrapped = parse_wrappers("gamemoderun", &presets);
+        assert_eq!(wrapped.wrappers, vec![wrapper("gamemode", "gamemoderun", "")]);
+        assert_eq!(wrapped.command, "");
+        assert_eq!(format_wrappers(&wrapped, &presets), "gamemoderun");
+    }
+}